@@ -0,0 +1,52 @@
+use std::io::{Read, Write};
+
+use tempfile::TempDir;
+use tracing::{error, info, warn};
+
+use crate::state::CanvasState;
+
+/// Receives an asset pushed by the server (`a{kind}{length:08}`, then a 10 byte id, then the payload) and caches it
+pub fn receive_asset<S: Read + Write>(command: &str, socket: &mut S, image_cache: &TempDir) {
+    let kind = &command[1..2];
+    let length = match command[2..10].parse::<usize>() {
+        Ok(length) => length,
+        Err(_) => {
+            error!("Failed to parse asset length: {}", &command[2..10]);
+            panic!();
+        }
+    };
+    let mut id_buf = [0; 10];
+    if socket.read_exact(&mut id_buf).is_err() {
+        error!("Connection to server closed while receiving asset... shutting down");
+        panic!();
+    }
+    let asset_id = String::from_utf8_lossy(&id_buf).trim_end_matches('=').to_string();
+    let mut payload = vec![0; length];
+    if socket.read_exact(&mut payload).is_err() {
+        error!("Connection to server closed while receiving asset '{}'... shutting down", &asset_id);
+        panic!();
+    }
+    let file_name = match kind {
+        "i" => format!("{}.bmp", &asset_id),
+        "f" => format!("{}.bdf", &asset_id),
+        _ => {
+            warn!("Ignoring asset '{}' of unknown kind '{}'", &asset_id, kind);
+            return;
+        }
+    };
+    let file_path = image_cache.path().join(&file_name);
+    if let Err(e) = std::fs::write(&file_path, &payload) {
+        error!("Unable to cache asset at [{:#?}]:\n{}", &file_path, e);
+        return;
+    }
+    info!("Cached asset '{}' ({} bytes)", &file_name, length);
+    let _ = socket.write_all(format!("k{}{}\n", kind, &asset_id).as_bytes());
+}
+
+/// Tells the server which assets could not be found in the cache so they get sent again
+pub fn report_missing_assets<S: Write>(socket: &mut S, state: &mut CanvasState) {
+    for asset in state.missing_assets.drain(..) {
+        warn!("Requesting missing asset '{}'", &asset);
+        let _ = socket.write_all(format!("n{}\n", &asset).as_bytes());
+    }
+}
\ No newline at end of file
@@ -9,7 +9,7 @@ use crate::state::CanvasState;
 
 const USER_AGENT: &str = "Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/128.0.0.0 Safari/537.3";
 
-pub fn draw_image<T: DrawTarget<Color = Rgb888>>(command: &str, canvas: &mut T, state: &CanvasState, image_cache: &TempDir) {
+pub fn draw_image<T: DrawTarget<Color = Rgb888>>(command: &str, canvas: &mut T, state: &mut CanvasState, image_cache: &TempDir) {
     let pos_str = [&command[1..3], &command[3..5]];
     let mut pos: [i32;2] = [0;2];
     for (idx, num_str) in pos_str.iter().enumerate() {
@@ -24,6 +24,12 @@ pub fn draw_image<T: DrawTarget<Color = Rgb888>>(command: &str, canvas: &mut T,
     let image_hash = &command[5..10];
     let expected_image_path = image_cache.path().join(format!("{}.bmp", image_hash));
     if !expected_image_path.exists() {
+        if state.inband_assets {
+            // The server pushes images over the matrix connection, so ask for it again instead of downloading it
+            tracing::warn!("Image ({}) has not been received from the server", image_hash);
+            state.missing_assets.push(format!("i{}", image_hash.trim_end_matches('=')));
+            return;
+        }
         download_image(&state.server_http_uri, image_cache, image_hash);
     }
     let image_data = std::fs::read(&expected_image_path);
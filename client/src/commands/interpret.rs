@@ -4,7 +4,7 @@ use tracing::info;
 
 use crate::state::CanvasState;
 
-use super::{clear::clear, colour::set_colour, image::draw_image, line::draw_line, pixel::{draw_coloured_pixel, draw_pixel}, text::{draw_character, set_font}};
+use super::{clear::clear, colour::set_colour, image::draw_image, line::draw_line, pixel::{draw_coloured_pixel, draw_pixel}, shape::{add_vertex, draw_arc, draw_closed_shape, draw_vertices, set_arc_angles, set_shape_style}, text::{draw_character, set_font}};
 #[cfg(not(target_arch = "x86_64"))]
 use super::brightness::set_brightness;
 
@@ -33,9 +33,15 @@ pub fn interpret<T: DrawTarget<Color = Rgb888>>(
         'l' => draw_line(command, canvas, state),
         'p' => draw_pixel(command, canvas, state),
         'q' => draw_coloured_pixel(command, canvas),
-        'f' => set_font(command, state),
+        'f' => set_font(command, state, image_cache),
         't' | 'j' => draw_character(command, canvas, state),
         'i' => draw_image(command, canvas, state, image_cache),
+        'd' => set_shape_style(command, state),
+        'r' | 'R' | 'o' | 'e' => draw_closed_shape(command, canvas, state),
+        'v' => add_vertex(command, state),
+        'T' | 'P' => draw_vertices(command, canvas, state),
+        'u' => set_arc_angles(command, state),
+        'A' => draw_arc(command, canvas, state),
         // 'I' => draw_image_of_day(command, canvas),
         's' => info!("Done.\n"),
         _ => info!("{}\n", command),
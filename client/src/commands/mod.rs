@@ -1,3 +1,4 @@
+pub mod asset;
 #[cfg(not(target_arch = "x86_64"))]
 pub mod brightness;
 pub mod clear;
@@ -6,4 +7,5 @@ pub mod image;
 pub mod interpret;
 pub mod line;
 pub mod pixel;
+pub mod shape;
 pub mod text;
\ No newline at end of file
@@ -0,0 +1,108 @@
+use embedded_graphics::{geometry::{Point, Size}, pixelcolor::Rgb888, prelude::*, primitives::{Arc, Circle, Ellipse, Polyline, PrimitiveStyle, PrimitiveStyleBuilder, Rectangle, StrokeAlignment, RoundedRectangle, Triangle}};
+use tracing::{error, info};
+
+use crate::state::CanvasState;
+
+/// `d{R}{G}{B}{filled}{stroke width:02}` sets the style of the shapes after it
+pub fn set_shape_style(command: &str, state: &mut CanvasState) {
+    let mut fill: [u8; 3] = [0; 3];
+    for (idx, channel) in command[1..4].chars().enumerate() {
+        match channel.to_digit(16) {
+            Some(value) => fill[idx] = value as u8 * 0x10,
+            None => {
+                error!("Failed to parse fill colour: {}", command);
+                return;
+            }
+        }
+    }
+    let Ok(stroke_width) = command[5..7].parse::<u32>() else {
+        error!("Failed to parse stroke width: {}", command);
+        return;
+    };
+    state.fill_colour = if &command[4..5] == "1" { Some(Rgb888::new(fill[0], fill[1], fill[2])) } else { None };
+    state.stroke_width = stroke_width;
+    info!("Setting shape style to fill {:?}, stroke width {}", state.fill_colour, stroke_width);
+}
+
+/// `r`, `R`, `o` and `e` commands, which fit their whole shape in one command
+pub fn draw_closed_shape<T: DrawTarget<Color = Rgb888>>(command: &str, canvas: &mut T, state: &CanvasState) {
+    let count = if command.starts_with('o') { 3 } else { 4 };
+    let mut data: [u32; 4] = [0; 4];
+    for idx in 0..count {
+        let num_str = &command[1 + idx * 2..3 + idx * 2];
+        match num_str.parse::<u32>() {
+            Ok(num) => data[idx] = num,
+            Err(_) => {
+                error!("Failed to parse integer: {}", num_str);
+                return;
+            }
+        }
+    }
+    let top_left = Point::new(data[0] as i32, data[1] as i32);
+    let style = shape_style(state);
+    let _ = match command.chars().nth(0).unwrap() {
+        'r' => Rectangle::new(top_left, Size::new(data[2], data[3])).into_styled(style).draw(canvas),
+        'R' => {
+            let radius = u32::from_str_radix(&command[9..10], 16).unwrap_or(0);
+            let rectangle = Rectangle::new(top_left, Size::new(data[2], data[3]));
+            RoundedRectangle::with_equal_corners(rectangle, Size::new(radius, radius)).into_styled(style).draw(canvas)
+        }
+        'o' => Circle::new(top_left, data[2]).into_styled(style).draw(canvas),
+        _ => Ellipse::new(top_left, Size::new(data[2], data[3])).into_styled(style).draw(canvas),
+    };
+}
+
+/// `v{x:02}{y:02}` adds a point for the next triangle or polyline
+pub fn add_vertex(command: &str, state: &mut CanvasState) {
+    match (command[1..3].parse::<i32>(), command[3..5].parse::<i32>()) {
+        (Ok(x), Ok(y)) => state.vertices.push(Point::new(x, y)),
+        _ => error!("Failed to parse vertex: {}", command),
+    }
+}
+
+/// `T` and `P` draw the points collected so far as a triangle or polyline
+pub fn draw_vertices<T: DrawTarget<Color = Rgb888>>(command: &str, canvas: &mut T, state: &mut CanvasState) {
+    let vertices = std::mem::take(&mut state.vertices);
+    let style = shape_style(state);
+    if command.starts_with('T') {
+        if let [a, b, c, ..] = vertices[..] {
+            let _ = Triangle::new(a, b, c).into_styled(style).draw(canvas);
+        }
+    } else {
+        let _ = Polyline::new(&vertices).into_styled(PrimitiveStyle::with_stroke(state.colour, state.stroke_width)).draw(canvas);
+    }
+}
+
+/// `u{start:03}{sweep:03}` sets the angles of the next arc, clockwise from 3 o'clock
+pub fn set_arc_angles(command: &str, state: &mut CanvasState) {
+    match (command[1..4].parse::<i32>(), command[4..7].parse::<i32>()) {
+        (Ok(start), Ok(sweep)) => state.arc_angles = (start, sweep),
+        _ => error!("Failed to parse arc angles: {}", command),
+    }
+}
+
+/// `A{x:02}{y:02}{diameter:02}` draws an arc in the current colour, with the stroke kept inside the diameter
+pub fn draw_arc<T: DrawTarget<Color = Rgb888>>(command: &str, canvas: &mut T, state: &CanvasState) {
+    let (Ok(x), Ok(y), Ok(diameter)) = (command[1..3].parse::<i32>(), command[3..5].parse::<i32>(), command[5..7].parse::<u32>()) else {
+        error!("Failed to parse arc: {}", command);
+        return;
+    };
+    let (start, sweep) = state.arc_angles;
+    let style = PrimitiveStyleBuilder::new()
+        .stroke_color(state.colour)
+        .stroke_width(state.stroke_width)
+        .stroke_alignment(StrokeAlignment::Inside)
+        .build();
+    let _ = Arc::new(Point::new(x, y), diameter, (start as f32).deg(), (sweep as f32).deg()).into_styled(style).draw(canvas);
+}
+
+fn shape_style(state: &CanvasState) -> PrimitiveStyle<Rgb888> {
+    let mut style = PrimitiveStyleBuilder::new();
+    if state.stroke_width > 0 {
+        style = style.stroke_color(state.colour).stroke_width(state.stroke_width);
+    }
+    if let Some(fill) = state.fill_colour {
+        style = style.fill_color(fill);
+    }
+    style.build()
+}
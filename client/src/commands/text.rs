@@ -1,14 +1,31 @@
-use embedded_graphics::{geometry::Point, mono_font::iso_8859_1::{FONT_5X8, FONT_7X14_BOLD}, pixelcolor::Rgb888, prelude::*, text::Text};
-use tracing::{error, info};
+use embedded_graphics::{geometry::Point, mono_font::iso_8859_1::{FONT_5X8, FONT_7X14_BOLD}, pixelcolor::Rgb888, prelude::*, text::Text, Pixel};
+use tempfile::TempDir;
+use tracing::{error, info, warn};
 
 use crate::state::CanvasState;
 
-pub fn set_font(command: &str, state: &mut CanvasState) {
+pub fn set_font(command: &str, state: &mut CanvasState, image_cache: &TempDir) {
     let mut font_name_end: usize = 10;
     if let Some(len) = command.find('=') {
         font_name_end = len;
     }
-    match &command[1..font_name_end] {
+    let font_name = &command[1..font_name_end];
+    state.bdf_font = None;
+    // Fonts pushed by the server take priority over the built-in fonts
+    let cached_font_path = image_cache.path().join(format!("{}.bdf", font_name));
+    if cached_font_path.exists() {
+        match bdf2::open(&cached_font_path) {
+            Ok(font) => {
+                state.bdf_font = Some(font);
+                return;
+            }
+            Err(e) => error!("Failed to load cached font ({}):\n{}", font_name, e),
+        }
+    } else if state.inband_assets {
+        warn!("Font ({}) has not been received from the server", font_name);
+        state.missing_assets.push(format!("f{}", font_name));
+    }
+    match font_name {
         "5x8" => {
             state.font = &FONT_5X8;
             state.font_offset = 6;
@@ -17,7 +34,7 @@ pub fn set_font(command: &str, state: &mut CanvasState) {
             state.font = &FONT_7X14_BOLD;
             state.font_offset = 12;
         },
-        _=>{info!("Invalid font: {}", &command[1..font_name_end])},
+        _=>{info!("Invalid font: {}", font_name)},
     };
 }
 
@@ -41,5 +58,31 @@ pub fn draw_character<T: DrawTarget<Color = Rgb888>>(command: &str, canvas: &mut
         }
     };
     info!("Drawing character ({}) at ({},{})", character, data[0], data[1]);
+    if let Some(font) = &state.bdf_font {
+        draw_bdf_character(character, Point::new(data[0] as i32, data[1] as i32), font, canvas, state.colour);
+        return;
+    }
     let _ = Text::new(character.to_string().as_str(), Point::new(data[0] as i32, ((data[1]+state.font_offset) as u32) as i32), state.text_style()).draw(canvas);
+}
+
+/// Draws a glyph from a BDF font with the top of the font's bounding box at `position`
+fn draw_bdf_character<T: DrawTarget<Color = Rgb888>>(character: char, position: Point, font: &bdf2::Font, canvas: &mut T, colour: Rgb888) {
+    let glyph = match font.glyphs().get(&character) {
+        Some(glyph) => glyph,
+        None => {
+            error!("Character '{}' is missing from the current font", character);
+            return;
+        }
+    };
+    let font_bounds = font.bounds();
+    let baseline = position.y + font_bounds.height as i32 + font_bounds.y;
+    let glyph_origin = Point::new(
+        position.x + glyph.bounds().x,
+        baseline - (glyph.bounds().height as i32 + glyph.bounds().y),
+    );
+    let pixels = glyph
+        .pixels()
+        .filter(|(_, lit)| *lit)
+        .map(|((x, y), _)| Pixel(glyph_origin + Point::new(x as i32, y as i32), colour));
+    let _ = canvas.draw_iter(pixels);
 }
\ No newline at end of file
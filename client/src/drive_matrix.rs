@@ -5,7 +5,7 @@ use embedded_graphics::{mono_font::iso_8859_1::FONT_5X8, pixelcolor::Rgb888, pre
 use rpi_led_panel::{Canvas, HardwareMapping, RGBMatrix, RGBMatrixConfig};
 use tempfile::TempDir;
 
-use crate::{commands::interpret::rgb_interpret, state::CanvasState};
+use crate::{commands::{asset::{receive_asset, report_missing_assets}, interpret::rgb_interpret}, state::CanvasState};
 
 
 
@@ -13,6 +13,7 @@ pub fn start_matrix(image_cache: TempDir) {
     // Get arguments
     let server_uri: String = env::var("SERVER_URI").unwrap_or(String::from("192.168.1.64:12312"));
     let server_http_uri: String = env::var("SERVER_HTTP_URI").unwrap_or(String::from("http://192.168.1.64:12345"));
+    let inband_assets = env::var("SERVER_INBAND_ASSETS").is_ok_and(|x| x == "1" || x.eq_ignore_ascii_case("true"));
     // let mut args = Arguments::from_env();
     // let server_uri: String = args.value_from_str("-s").unwrap_or(String::from("192.168.1.64:12312"));
     // let server_http_uri: String = args.value_from_str("-h").unwrap_or(String::from("http://192.168.1.64:12345"));
@@ -22,9 +23,9 @@ pub fn start_matrix(image_cache: TempDir) {
     let mut canvas = *canvas;
     //
     let mut socket = connect_socket(&server_uri);
-    // Protocol V1
+    // Protocol V1 (V2 when assets are sent over the matrix connection)
     {
-        let _ = socket.write("1\n".as_bytes());
+        let _ = socket.write(if inband_assets { "2\n" } else { "1\n" }.as_bytes());
         let _ = socket.write(format!("{}\n", canvas.width()).as_bytes());
         let _ = socket.write(format!("{}\n", canvas.height()).as_bytes());
     }
@@ -33,8 +34,15 @@ pub fn start_matrix(image_cache: TempDir) {
         colour: Rgb888::WHITE,
         font: &FONT_5X8,
         font_offset: 0,
+        bdf_font: None,
         brightness: 100,
         server_http_uri,
+        inband_assets,
+        missing_assets: Vec::new(),
+        fill_colour: None,
+        stroke_width: 1,
+        vertices: Vec::new(),
+        arc_angles: (0, 360),
     };
     let canvas = Arc::new(Mutex::new(canvas));
     let background_canvas = canvas.clone();
@@ -69,12 +77,17 @@ fn connect_socket(server: &str) -> TcpStream {
 
 fn render(socket: &mut TcpStream, display: Arc<Mutex<Canvas>>, state: &mut CanvasState, image_cache: &TempDir) {
     let mut buf = [0; 10];
-    let result = socket.read(&mut buf);
+    let result = socket.read_exact(&mut buf);
     if result.is_err() {
         tracing::error!("Connection to server closed... shutting down");
         panic!();
     }
     let command = std::str::from_utf8(&buf).unwrap();
     // info!("{}", command);
+    if command.starts_with('a') {
+        receive_asset(command, socket, image_cache);
+        return;
+    }
     rgb_interpret(command, &mut *display.lock().unwrap(), state, image_cache);
+    report_missing_assets(socket, state);
 }
@@ -5,7 +5,7 @@ use embedded_graphics_simulator::{OutputSettingsBuilder, SimulatorDisplay, Simul
 use pico_args::Arguments;
 use tempfile::TempDir;
 
-use crate::{commands::interpret::interpret, state::CanvasState};
+use crate::{commands::{asset::{receive_asset, report_missing_assets}, interpret::interpret}, state::CanvasState};
 
 
 pub fn run_emulator(image_cache: TempDir) {
@@ -15,12 +15,13 @@ pub fn run_emulator(image_cache: TempDir) {
     let server_http_uri: String = args.value_from_str("-h").unwrap_or(String::from("http://192.168.1.64:12345"));
     let size_x = args.value_from_str("-x").unwrap_or(64);
     let size_y = args.value_from_str("-y").unwrap_or(32);
+    let inband_assets = args.contains("--inband-assets");
     let size = Size::new(size_x, size_y);
     //
     let mut socket = connect_socket(&server_uri);
-    // Protocol V1
+    // Protocol V1 (V2 when assets are sent over the matrix connection)
     {
-        let _ = socket.write("1\n".as_bytes());
+        let _ = socket.write(if inband_assets { "2\n" } else { "1\n" }.as_bytes());
         let _ = socket.write(format!("{}\n", size.width).as_bytes());
         let _ = socket.write(format!("{}\n", size.height).as_bytes());
     }
@@ -28,8 +29,15 @@ pub fn run_emulator(image_cache: TempDir) {
         colour: Rgb888::WHITE,
         font: &FONT_5X8,
         font_offset: 0,
+        bdf_font: None,
         brightness: 100,
         server_http_uri,
+        inband_assets,
+        missing_assets: Vec::new(),
+        fill_colour: None,
+        stroke_width: 1,
+        vertices: Vec::new(),
+        arc_angles: (0, 360),
     };
 
     let mut display = SimulatorDisplay::<Rgb888>::new(size.clone());
@@ -64,12 +72,17 @@ fn connect_socket(server: &str) -> TcpStream {
 
 fn render<T: DrawTarget<Color = Rgb888>>(socket: &mut TcpStream, display: &mut T, state: &mut CanvasState, image_cache: &TempDir) {
     let mut buf = [0; 10];
-    let result = socket.read(&mut buf);
+    let result = socket.read_exact(&mut buf);
     if result.is_err() {
         tracing::error!("Connection to server closed... shutting down");
         panic!();
     }
     let command = std::str::from_utf8(&buf).unwrap();
     // info!("{}", command);
-    interpret(command, display, state, image_cache)
+    if command.starts_with('a') {
+        receive_asset(command, socket, image_cache);
+        return;
+    }
+    interpret(command, display, state, image_cache);
+    report_missing_assets(socket, state);
 }
\ No newline at end of file
@@ -1,11 +1,20 @@
-use embedded_graphics::{mono_font::{MonoFont, MonoTextStyle}, pixelcolor::Rgb888};
+use embedded_graphics::{geometry::Point, mono_font::{MonoFont, MonoTextStyle}, pixelcolor::Rgb888};
 
 pub struct CanvasState<'a> {
     pub colour: Rgb888,
     pub font: &'a MonoFont<'a>,
     pub font_offset: u8,
+    pub bdf_font: Option<bdf2::Font>,
     pub brightness: u8,
     pub server_http_uri: String,
+    pub inband_assets: bool,
+    pub missing_assets: Vec<String>,
+    pub fill_colour: Option<Rgb888>,
+    pub stroke_width: u32,
+    /// Points for the next triangle or polyline
+    pub vertices: Vec<Point>,
+    /// (start, sweep) in degrees for the next arc
+    pub arc_angles: (i32, i32),
 }
 impl<'a> CanvasState<'a> {
     pub fn text_style(&self) -> MonoTextStyle<'a, Rgb888> {
@@ -0,0 +1,137 @@
+use std::{collections::HashMap, fmt::Display, io::Cursor, sync::Arc, time::{Duration, SystemTime}};
+
+use base64::{engine::general_purpose, Engine};
+use image::{codecs::gif::GifDecoder, AnimationDecoder, ImageFormat, RgbImage};
+use sha2::{Digest, Sha256};
+use shared::{canvas::PixelImage, image_animation::{gif_frame_delay, split_sprite_strip}};
+use tokio::fs;
+
+use crate::{config_manager::ConfigWrapper, image_manager::get_image_path, state_manager::StateWrapper};
+
+/// Frames cut out of animated images, each one encoded as a BMP so devices can draw it like any other image.
+/// Entries are rebuilt when the file on disk changes.
+#[derive(Default, Debug)]
+pub(crate) struct AnimationCache {
+    animations: HashMap<(String, u8), CachedAnimation>,
+    /// BMP data of every frame by its image id
+    frames: HashMap<String, Arc<Vec<u8>>>,
+}
+impl AnimationCache {
+    pub(crate) fn frame(&self, frame_id: &str) -> Option<Arc<Vec<u8>>> {
+        self.frames.get(frame_id).cloned()
+    }
+}
+
+#[derive(Debug)]
+struct CachedAnimation {
+    modified: SystemTime,
+    frames: Arc<Vec<AnimationFrame>>,
+}
+
+/// A frame of an animation, drawn with `i` using `id` in place of an image hash
+#[derive(Debug, Clone)]
+pub(crate) struct AnimationFrame {
+    pub(crate) id: String,
+    pub(crate) delay: Duration,
+}
+
+#[derive(Debug)]
+pub(crate) enum AnimationError {
+    NotFound(String),
+    Unreadable(String, String),
+}
+impl Display for AnimationError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AnimationError::NotFound(image_path) => write!(f, "Image ({}) does not exist!", image_path),
+            AnimationError::Unreadable(image_path, e) => write!(f, "Image ({}) could not be animated: {}", image_path, e),
+        }
+    }
+}
+impl std::error::Error for AnimationError {}
+
+/// Gets the frames of a GIF, or of a sprite strip when `sprite_frames` is set, decoding them if the image is new or has changed
+pub(crate) async fn get_animation(config: ConfigWrapper, state: StateWrapper, image_path: &str, sprite_frames: u8, frame_time: Duration) -> Result<Arc<Vec<AnimationFrame>>, AnimationError> {
+    let file_path = get_image_path(config, Some(image_path)).await;
+    let modified = match fs::metadata(&file_path).await.and_then(|x| x.modified()) {
+        Ok(x) if file_path.ends_with(image_path) => x,
+        _ => return Err(AnimationError::NotFound(image_path.to_string())),
+    };
+    let key = (image_path.to_string(), sprite_frames);
+    if let Some(cached) = state.lock().await.animation_cache.animations.get(&key) {
+        if cached.modified == modified {
+            return Ok(retime_sprites(cached.frames.clone(), sprite_frames, frame_time));
+        }
+    }
+    let data = fs::read(&file_path).await.map_err(|e| AnimationError::Unreadable(image_path.to_string(), e.to_string()))?;
+    let decoded = decode_frames(&data, sprite_frames).map_err(|e| AnimationError::Unreadable(image_path.to_string(), e.to_string()))?;
+    let mut state = state.lock().await;
+    let mut frames = Vec::with_capacity(decoded.len());
+    for (idx, (image, delay)) in decoded.into_iter().enumerate() {
+        let id = frame_id(image_path, sprite_frames, idx);
+        let bmp = encode_bmp(&image).map_err(|e| AnimationError::Unreadable(image_path.to_string(), e.to_string()))?;
+        state.animation_cache.frames.insert(id.clone(), Arc::new(bmp));
+        frames.push(AnimationFrame { id, delay });
+    }
+    tracing::info!("Loaded {} animation frames from '{}'", frames.len(), file_path.display());
+    let frames = Arc::new(frames);
+    state.animation_cache.animations.insert(key, CachedAnimation { modified, frames: frames.clone() });
+    Ok(retime_sprites(frames, sprite_frames, frame_time))
+}
+
+/// Sprite strips have no timing of their own, so every frame gets the element's frame time
+fn retime_sprites(frames: Arc<Vec<AnimationFrame>>, sprite_frames: u8, frame_time: Duration) -> Arc<Vec<AnimationFrame>> {
+    if sprite_frames == 0 {
+        return frames;
+    }
+    Arc::new(frames.iter().map(|frame| AnimationFrame { delay: frame_time, ..frame.clone() }).collect())
+}
+
+/// Every frame flattened onto black, with GIF frame delays. Sprite strip delays are filled in later.
+fn decode_frames(data: &[u8], sprite_frames: u8) -> Result<Vec<(PixelImage, Duration)>, image::ImageError> {
+    if sprite_frames > 0 {
+        let strip = to_pixel_image(&image::load_from_memory(data)?.to_rgba8());
+        return Ok(split_sprite_strip(&strip, sprite_frames).into_iter().map(|frame| (frame, Duration::ZERO)).collect());
+    }
+    if image::guess_format(data)? != ImageFormat::Gif {
+        // A still image is an animation with one frame
+        let image = to_pixel_image(&image::load_from_memory(data)?.to_rgba8());
+        return Ok(vec![(image, Duration::ZERO)]);
+    }
+    let frames = GifDecoder::new(Cursor::new(data))?.into_frames().collect_frames()?;
+    Ok(frames
+        .into_iter()
+        .map(|frame| {
+            let (numerator, denominator) = frame.delay().numer_denom_ms();
+            (to_pixel_image(frame.buffer()), gif_frame_delay(numerator / denominator.max(1)))
+        })
+        .collect())
+}
+
+/// Devices have no transparency, so transparent pixels fade to black
+fn to_pixel_image(image: &image::RgbaImage) -> PixelImage {
+    PixelImage {
+        width: image.width(),
+        height: image.height(),
+        pixels: image
+            .pixels()
+            .map(|pixel| {
+                let [r, g, b, a] = pixel.0;
+                [r, g, b].map(|channel| (channel as u16 * a as u16 / 0xFF) as u8)
+            })
+            .collect(),
+    }
+}
+
+fn encode_bmp(image: &PixelImage) -> Result<Vec<u8>, image::ImageError> {
+    let rgb = RgbImage::from_fn(image.width, image.height, |x, y| image::Rgb(image.pixels[(y * image.width + x) as usize]));
+    let mut bmp = Vec::new();
+    rgb.write_to(&mut Cursor::new(&mut bmp), ImageFormat::Bmp)?;
+    Ok(bmp)
+}
+
+/// Frame ids are short hashes like image hashes, so they fit in an `i` command
+fn frame_id(image_path: &str, sprite_frames: u8, frame: usize) -> String {
+    let hash = Sha256::digest(format!("{}#{}#{}", image_path, sprite_frames, frame));
+    general_purpose::URL_SAFE_NO_PAD.encode(hash).chars().take(5).collect()
+}
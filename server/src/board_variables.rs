@@ -1,25 +1,36 @@
-use chrono::{Datelike, Timelike, Weekday};
-use serde_json::Value;
-use shared::board_variables::{BoardVariable, TimeData};
-use tracing::{info, warn};
+use std::collections::HashMap;
+
+use chrono::{DateTime, Local};
+use shared::{
+    board_variables::{BoardVariable, DeviceTimeSettings},
+    countdowns::{CountdownData, CountdownTarget},
+    expressions::{find_cycle, find_variable_cycle, Expression},
+    templates::Template,
+};
+use tracing::info;
 
 static DEBUG: bool = false;
+/// Shown by JSON extractors whose path is invalid or can't be used on the data, and by expressions and countdowns
+/// that can't be evaluated, so the board shows something is wrong
+const VARIABLE_ERROR: &str = "ERR";
 
-use crate::{
-    config_manager::Config,
-    state_manager::{StateWrapper, VariableCache},
-};
+tokio::task_local! {
+    /// Time zone and locale of the device a board is being rendered for, for DateTime variables without their own
+    pub(crate) static DEVICE_TIME_SETTINGS: DeviceTimeSettings;
+}
+
+use crate::{config_manager::Config, json_path, state_manager::StateWrapper, variable_refresher::{can_wait_for_fetch, longest_fetch, start_fetch, Refresh}};
 
 pub trait EvaluateBoardVariable {
+    /// `variable_name` is the variable's placeholder, like `__time__`
     async fn eval_variable(
         &self,
         variable_name: &str,
         config: &Config,
         state: StateWrapper,
+        now: &DateTime<Local>,
     ) -> String;
     fn determine_substr_end(e_in: i16, data: &str) -> usize;
-    fn weekday_to_string(day: Weekday) -> String;
-    fn month_to_string(month: u32) -> String;
 }
 impl EvaluateBoardVariable for BoardVariable {
     #[allow(dead_code)]
@@ -28,55 +39,41 @@ impl EvaluateBoardVariable for BoardVariable {
         variable_name: &str,
         config: &Config,
         state: StateWrapper,
+        now: &DateTime<Local>,
     ) -> String {
         match self {
-            BoardVariable::URL(var_id, url, expiry, headers) => {
-                let datetime = chrono::Utc::now();
-                let mut state = state.lock().await;
+            BoardVariable::URL(request) => {
+                // The refresher keeps these up to date in the background, so a cached value is always used as it is
+                let mut state_lock = state.lock().await;
                 if DEBUG {
-                    info!("{:#?}", &state.board_variable_values);
+                    info!("{:#?}", &state_lock.board_variable_values);
                 }
-                if let Some(var_cache) = state.board_variable_values.get(&var_id.to_string()) {
+                let var_id = request.var_id.to_string();
+                state_lock.variable_refreshes.mark_read(&var_id, now.timestamp());
+                if let Some(var_cache) = state_lock.board_variable_values.get(&var_id) {
                     if DEBUG {
-                        info!("Cache Debug:\nTime Entered: {}\nCurrent Time: {}\nExpiry: {}\nCurrent-Expiry: {}", &var_cache.time_entered, &datetime.timestamp(), expiry, &(datetime.timestamp()-expiry));
+                        info!("Cache Debug:\nTime Entered: {}\nCurrent Time: {}", &var_cache.time_entered, &now.timestamp());
                     }
-                    if !(var_cache.time_entered < datetime.timestamp() - expiry) {
-                        // Use Cached Variable if not Expired
-                        return var_cache.value.clone();
-                    }
-                }
-                // Update Variable Cache
-                info!(
-                    "Updating Variable Cache for URL variable '{}'",
-                    &variable_name
-                );
-                let mut req = ureq::get(url);
-                for (header, value) in headers {
-                    req = req.set(header, value);
+                    return var_cache.value.clone();
                 }
-                let response = req.call();
-                match response {
-                    Ok(resp) => match resp.into_string() {
-                        Ok(x) => {
-                            state.board_variable_values.insert(
-                                var_id.to_string(),
-                                VariableCache {
-                                    time_entered: datetime.timestamp(),
-                                    value: x.clone(),
-                                },
-                            );
-                            return x;
-                        }
-                        Err(e) => {
-                            warn!("Error requesting url ({}):\n{}", url, e.to_string());
-                            return String::new();
-                        }
-                    },
-                    Err(e) => {
-                        warn!("Error requesting url ({}):\n{}", url, e.to_string());
-                        return String::new();
+                // Never fetched, so wait for it, sharing the request with anything else rendering it right now
+                let refresh = state_lock.variable_refreshes.refresh(request, now.timestamp());
+                drop(state_lock);
+                let mut fetch = match refresh {
+                    Refresh::Joined(fetch) => fetch,
+                    Refresh::Start(pending) => {
+                        let fetch = pending.subscribe();
+                        start_fetch(pending, request, variable_name, config, state.clone(), now).await;
+                        fetch
                     }
+                    Refresh::NotDue => return String::new(),
+                };
+                if !can_wait_for_fetch() {
+                    return String::new();
                 }
+                let _ = tokio::time::timeout(longest_fetch(request), fetch.wait_for(|done| *done)).await;
+                let state = state.lock().await;
+                state.board_variable_values.get(&var_id).map(|var_cache| var_cache.value.clone()).unwrap_or_default()
             }
             BoardVariable::JsonURL(url_var_id, path, round_numbers, substring) => {
                 let url_var = config
@@ -89,8 +86,12 @@ impl EvaluateBoardVariable for BoardVariable {
                 }
                 let (url_var_name, url_var) = url_var.unwrap();
                 let return_val =
-                    Box::pin(url_var.eval_variable(&url_var_name, config, state.clone()));
+                    Box::pin(url_var.eval_variable(&url_var_name, config, state.clone(), now));
                 let return_val = return_val.await;
+                if return_val.is_empty() {
+                    // Not fetched yet
+                    return String::new();
+                }
                 let json_data = serde_json::from_str::<serde_json::Value>(&return_val);
                 if json_data.is_err() {
                     tracing::warn!(
@@ -99,42 +100,22 @@ impl EvaluateBoardVariable for BoardVariable {
                     );
                     return String::new();
                 }
-                let mut json_data = json_data.unwrap();
-                for item in path.split(".") {
-                    let temp: Option<&Value>;
-                    if json_data.is_array() {
-                        let item_num = usize::from_str_radix(item, 10);
-                        if item_num.is_err() {
-                            tracing::warn!(
-                                "Error parsing number '{}' for use in indexing array:\n{}",
-                                item,
-                                json_data.to_string()
-                            );
-                        }
-                        let item_num = item_num.unwrap();
-                        temp = json_data.get(item_num);
-                    } else {
-                        temp = json_data.get(item);
-                    }
-                    if temp.is_none() {
-                        tracing::warn!(
-                            "Error finding item '{}' on object with data:\n{}",
-                            item,
-                            json_data.to_string()
-                        );
+                let json_data = json_data.unwrap();
+                let data = match json_path::query(&json_data, path) {
+                    Ok(Some(value)) => json_path::value_to_text(&value),
+                    Ok(None) => {
+                        tracing::warn!("Nothing matches '{}' in data:\n{}", path, json_data.to_string());
                         return String::new();
                     }
-                    json_data = temp.unwrap().to_owned();
-                }
-                let mut data = json_data.to_string();
-                if json_data.is_string() {
-                    data = data.split_off(1);
-                    let _ = data.split_off(data.len() - 1);
-                }
+                    Err(e) => {
+                        tracing::warn!("Unable to use path '{}' for URL id '{}': {}", path, url_var_id, e);
+                        return String::from(VARIABLE_ERROR);
+                    }
+                };
                 if let Some((start, end)) = substring {
                     let start = *start as usize;
                     let end = Self::determine_substr_end(*end, &data);
-                    let new_str = &data[start..end];
+                    let new_str = data.get(start..end.max(start)).unwrap_or_default();
                     if *round_numbers {
                         if let Ok(num) = new_str.parse::<f32>() {
                             let num = num.round();
@@ -155,51 +136,35 @@ impl EvaluateBoardVariable for BoardVariable {
                 }
             }
             BoardVariable::Time(time_data) => {
-                let datetime = chrono::Local::now();
-                match time_data {
-                    TimeData::Weekday(offset, substring) => {
-                        let mut weekday = datetime.weekday();
-                        for _ in 0..*offset {
-                            weekday = weekday.succ();
-                        }
-                        if let Some((start, end)) = substring {
-                            let data = Self::weekday_to_string(weekday);
-                            let start = *start as usize;
-                            let end = Self::determine_substr_end(*end, &data);
-                            let new_str = &data[start..end];
-                            return new_str.to_string();
-                        } else {
-                            return Self::weekday_to_string(weekday);
-                        }
-                    }
-                    TimeData::Time => {
-                        let am_pm_text = match (datetime.hour() / 12) as i32 == 0 {
-                            true => "AM",
-                            false => "PM",
-                        };
-                        let hour_text = format!(
-                            "{:02}",
-                            match datetime.hour() % 12 {
-                                0 => 12,
-                                x => x,
-                            }
-                        );
-                        let min_text = format!("{:02}", datetime.minute());
-                        return format!("{}:{} {}", &hour_text, &min_text, &am_pm_text);
-                    }
-                    TimeData::Date => {
-                        return format!(
-                            "{} {} {}",
-                            Self::month_to_string(datetime.month()),
-                            datetime.day(),
-                            datetime.year()
-                        );
+                let device = DEVICE_TIME_SETTINGS.try_with(|settings| settings.clone()).unwrap_or_default();
+                match time_data.format(&now.to_utc(), &device, &config.date_names) {
+                    Ok(value) => value,
+                    Err(e) => {
+                        tracing::warn!("Unable to format DateTime variable '{}': {}", variable_name, e);
+                        String::from(VARIABLE_ERROR)
                     }
                 }
-                // DOW
-                // TIME AM/PM
-                // Mon DY YEAR
             }
+            BoardVariable::Expression(text) => match evaluate_expression(text, config, state, now).await {
+                Ok(value) => value,
+                Err(e) => {
+                    tracing::warn!("Unable to evaluate expression variable '{}': {}", variable_name, e);
+                    String::from(VARIABLE_ERROR)
+                }
+            },
+            BoardVariable::Pushed(pushed) => {
+                // Pushed values are stored by the variable's name, without the underscores of its placeholder
+                let name = variable_name.strip_prefix("__").and_then(|name| name.strip_suffix("__")).unwrap_or(variable_name);
+                let state = state.lock().await;
+                state.pushed_values.get(name, pushed.ttl, now.timestamp()).unwrap_or(&pushed.default).to_string()
+            }
+            BoardVariable::Countdown(countdown) => match evaluate_countdown(countdown, config, state, now).await {
+                Ok(value) => value,
+                Err(e) => {
+                    tracing::warn!("Unable to evaluate countdown variable '{}': {}", variable_name, e);
+                    String::from(VARIABLE_ERROR)
+                }
+            },
         }
     }
 
@@ -217,34 +182,69 @@ impl EvaluateBoardVariable for BoardVariable {
             }
         };
     }
+}
 
-    fn weekday_to_string(day: Weekday) -> String {
-        match day {
-            Weekday::Sun => "Sunday".to_string(),
-            Weekday::Mon => "Monday".to_string(),
-            Weekday::Tue => "Tuesday".to_string(),
-            Weekday::Wed => "Wednesday".to_string(),
-            Weekday::Thu => "Thursday".to_string(),
-            Weekday::Fri => "Friday".to_string(),
-            Weekday::Sat => "Saturday".to_string(),
+/// Replaces a text's `__name__` placeholders with their variables' values, after running any filters on them
+pub(crate) async fn fill_template(text: &str, config: &Config, state: StateWrapper, now: &DateTime<Local>) -> String {
+    let template = Template::parse(text, |name| config.board_variables.contains_key(name));
+    for error in template.errors() {
+        tracing::warn!("Unable to use placeholder in '{}': {}", text, error);
+    }
+    let mut values = HashMap::new();
+    for name in template.variables() {
+        if values.contains_key(name) {
+            continue;
+        }
+        if let Some(variable) = config.board_variables.get(name) {
+            let value = Box::pin(variable.eval_variable(&format!("__{}__", name), config, state.clone(), now)).await;
+            if DEBUG {
+                info!("\tSubstituted '__{}__' with '{}'", name, &value);
+            }
+            values.insert(name, value);
         }
     }
+    template.render(|name| values.get(name).cloned().unwrap_or_default())
+}
 
-    fn month_to_string(month: u32) -> String {
-        match month {
-            1 => "Jan".to_string(),
-            2 => "Feb".to_string(),
-            3 => "Mar".to_string(),
-            4 => "Apr".to_string(),
-            5 => "May".to_string(),
-            6 => "Jun".to_string(),
-            7 => "July".to_string(),
-            8 => "Aug".to_string(),
-            9 => "Sept".to_string(),
-            10 => "Oct".to_string(),
-            11 => "Nov".to_string(),
-            12 => "Dec".to_string(),
-            _ => "Nul".to_string(),
+/// Evaluates an expression with the current values of the variables it uses. While a variable it uses has no value
+/// yet, like a request that hasn't been fetched, a failed evaluation is empty instead of an error.
+pub(crate) async fn evaluate_expression(text: &str, config: &Config, state: StateWrapper, now: &DateTime<Local>) -> anyhow::Result<String> {
+    let expression = Expression::parse(text)?;
+    if let Some(cycle) = find_cycle(&expression, &config.board_variables) {
+        anyhow::bail!("Variables refer to each other in a loop: {}", cycle.join(" -> "));
+    }
+    let mut values = HashMap::new();
+    for name in expression.variables() {
+        if let Some(variable) = config.board_variables.get(&name) {
+            let value = Box::pin(variable.eval_variable(&format!("__{}__", name), config, state.clone(), now)).await;
+            values.insert(name, value);
         }
     }
+    match expression.evaluate(now.timestamp(), &|name| values.get(name).cloned()) {
+        Ok(value) => Ok(value.to_string()),
+        Err(_) if values.values().any(|value| value.is_empty()) => Ok(String::new()),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// The countdown in the device's time zone. While a target variable has no value yet the countdown is empty.
+async fn evaluate_countdown(countdown: &CountdownData, config: &Config, state: StateWrapper, now: &DateTime<Local>) -> anyhow::Result<String> {
+    let target_value = match &countdown.target {
+        CountdownTarget::Variable(name) => {
+            if let Some(cycle) = find_variable_cycle(name, &config.board_variables) {
+                anyhow::bail!("Variables refer to each other in a loop: {}", cycle.join(" -> "));
+            }
+            let Some(variable) = config.board_variables.get(name) else {
+                anyhow::bail!("No variable named '{}' for the target", name);
+            };
+            let value = Box::pin(variable.eval_variable(&format!("__{}__", name), config, state, now)).await;
+            if value.trim().is_empty() {
+                return Ok(String::new());
+            }
+            Some(value)
+        }
+        _ => None,
+    };
+    let device = DEVICE_TIME_SETTINGS.try_with(|settings| settings.clone()).unwrap_or_default();
+    countdown.format(&now.to_utc(), &device.time_zone, target_value.as_deref()).map_err(anyhow::Error::msg)
 }
@@ -1,43 +1,139 @@
-use crate::{board_variables::EvaluateBoardVariable, config_manager::ConfigWrapper, matrix_server::helpers::{image_helper::draw_image, text_helpers::draw_text}, state_manager::StateWrapper};
-use shared::{boards::{BoardDefinition, BoardElement, BoardElementValue, ElementColour}, device_config::{get_current_brightness, DeviceConfig}};
+use std::time::Duration;
+
+use anyhow::{Context, Error};
+use chrono::{DateTime, Local};
+
+use crate::{board_variables::{fill_template, DEVICE_TIME_SETTINGS}, config_manager::ConfigWrapper, matrix_server::helpers::{graph_helper::draw_graph, image_helper::{draw_image, ImageAnimation}, shape_helper::{draw_indicator, draw_shape}, text_helpers::{draw_text, MarqueeAnimation, TextBoxAnimation}}, state_manager::StateWrapper};
+use shared::{board_variables::DeviceTimeSettings, boards::{BoardDefinition, BoardElement, BoardElementValue, ElementColour}, device_config::{get_brightness_at, DeviceConfig}};
 
 static DEBUG: bool = false;
 
+/// Commands for the first frame of a board and anything on it that keeps moving afterwards
+pub struct RenderedBoard {
+    pub commands: String,
+    pub animations: Vec<ElementAnimation>,
+}
+
+/// An element that is redrawn over time while its board is shown
+pub enum ElementAnimation {
+    Marquee(MarqueeAnimation),
+    TextBox(TextBoxAnimation),
+    Image(ImageAnimation),
+}
+impl ElementAnimation {
+    pub fn duration(&self) -> Duration {
+        match self {
+            ElementAnimation::Marquee(marquee) => marquee.duration(),
+            ElementAnimation::TextBox(text_box) => text_box.duration(),
+            ElementAnimation::Image(image) => image.duration(),
+        }
+    }
+    pub fn frame_interval(&self) -> Duration {
+        match self {
+            ElementAnimation::Marquee(marquee) => marquee.frame_interval(),
+            ElementAnimation::TextBox(text_box) => text_box.frame_interval(),
+            ElementAnimation::Image(image) => image.frame_interval(),
+        }
+    }
+    /// Commands that bring the element up to date `elapsed` after the board was first drawn
+    pub fn frame(&self, elapsed: Duration) -> String {
+        match self {
+            ElementAnimation::Marquee(marquee) => marquee.frame(elapsed),
+            ElementAnimation::TextBox(text_box) => text_box.frame(elapsed),
+            ElementAnimation::Image(image) => image.frame(elapsed),
+        }
+    }
+}
+
 pub trait BoardRender {
-    async fn render(&self, device_config: &DeviceConfig, config:ConfigWrapper, state:StateWrapper) -> Option<String>;
+    async fn render(&self, device_config: &DeviceConfig, config:ConfigWrapper, state:StateWrapper, now: &DateTime<Local>) -> Result<Option<RenderedBoard>, Error>;
 }
 impl BoardRender for BoardDefinition {
-    async fn render(&self, device_config: &DeviceConfig, config: ConfigWrapper, state: StateWrapper) -> Option<String> {
-        let current_brightness = get_current_brightness(&device_config.brightness);
+    async fn render(&self, device_config: &DeviceConfig, config: ConfigWrapper, state: StateWrapper, now: &DateTime<Local>) -> Result<Option<RenderedBoard>, Error> {
+        let current_brightness = get_brightness_at(&device_config.brightness, now);
         let mut render_buffer = format!("b{:>03}======x=========cFFF======", current_brightness);
+        let mut animations = Vec::new();
         // Send clear board when brightness is 0
         if current_brightness == 0 {
-            return Some(render_buffer);
+            return Ok(Some(RenderedBoard { commands: render_buffer, animations }));
         }
         // Do not send an update if the board is marked to skip if the device is below the brightness threshold and the device is below the threshold
         if self.use_skip_brightness_threshold && current_brightness < device_config.skip_brightness_threshold {
-            return None;
-        }
-        // Continue normally otherwise
-        for board_element in &self.board_elements {
-            render_buffer.push_str(&board_element.draw(config.clone(), state.clone(), device_config, &self.name).await);
+            return Ok(None);
         }
-        return Some(render_buffer);
+        // Continue normally otherwise, with times shown in the device's time zone and language
+        let time_settings = DeviceTimeSettings { time_zone: device_config.time_zone.clone(), locale: device_config.locale.clone() };
+        DEVICE_TIME_SETTINGS.scope(time_settings, async {
+            for board_element in &self.board_elements {
+                let (element, animation) = board_element.draw(config.clone(), state.clone(), device_config, &self.name, now).await
+                    .with_context(|| format!("Unable to draw element [{}] of board [{}]", &board_element.name, &self.name))?;
+                render_buffer.push_str(&element);
+                animations.extend(animation);
+            }
+            Ok::<_, Error>(())
+        }).await?;
+        return Ok(Some(RenderedBoard { commands: render_buffer, animations }));
     }
 }
 
 pub trait DrawBoardElement {
-    async fn draw(&self, config: ConfigWrapper, state: StateWrapper, device_config: &DeviceConfig, board_name: &str) -> String;
+    async fn draw(&self, config: ConfigWrapper, state: StateWrapper, device_config: &DeviceConfig, board_name: &str, now: &DateTime<Local>) -> Result<(String, Option<ElementAnimation>), Error>;
 }
 impl DrawBoardElement for BoardElement {
-    async fn draw(&self, config: ConfigWrapper, state: StateWrapper, device_config: &DeviceConfig, board_name: &str) -> String {
+    async fn draw(&self, config: ConfigWrapper, state: StateWrapper, device_config: &DeviceConfig, board_name: &str, now: &DateTime<Local>) -> Result<(String, Option<ElementAnimation>), Error> {
         let legacy_mode = device_config.proto_version == 0;
         match self.value {
             BoardElementValue::Text(_) => {
-                return draw_text(config.clone(), device_config, board_name, self.x, self.y, &self.colour, &self.font, self.value.substitute_variables(config.clone(), state.clone()).await).await;
+                return Ok((draw_text(config.clone(), state.clone(), device_config, board_name, self, self.value.substitute_variables(config.clone(), state.clone(), now).await).await?, None));
+            },
+            BoardElementValue::Marquee(_, settings) => {
+                let text = self.value.substitute_variables(config.clone(), state.clone(), now).await;
+                let marquee = MarqueeAnimation::new(config.clone(), state.clone(), device_config, board_name, self, settings, text).await?;
+                let first_frame = marquee.frame(Duration::ZERO);
+                // Text that fits does not need redrawing
+                let animation = (!marquee.duration().is_zero()).then_some(ElementAnimation::Marquee(marquee));
+                Ok((first_frame, animation))
+            },
+            BoardElementValue::TextBox(_, ref settings) => {
+                let text = self.value.substitute_variables(config.clone(), state.clone(), now).await;
+                let text_box = TextBoxAnimation::new(config.clone(), state.clone(), device_config, board_name, self, settings.clone(), text).await?;
+                let first_frame = text_box.frame(Duration::ZERO);
+                // Only paged text changes after it is drawn
+                let animation = (!text_box.duration().is_zero()).then_some(ElementAnimation::TextBox(text_box));
+                Ok((first_frame, animation))
+            },
+            BoardElementValue::Rect(..) | BoardElementValue::RoundedRect(..) | BoardElementValue::Circle(..)
+            | BoardElementValue::Ellipse(..) | BoardElementValue::Triangle(..) | BoardElementValue::Polyline(..) => {
+                Ok((draw_shape(self), None))
+            },
+            BoardElementValue::Bar(..) | BoardElementValue::Gauge(..) => {
+                let value = self.value.substitute_variables(config.clone(), state.clone(), now).await;
+                Ok((draw_indicator(self, &value, device_config), None))
+            },
+            BoardElementValue::Graph(ref variable_name, ..) => {
+                let samples = state.lock().await.variable_history.get(variable_name).cloned().unwrap_or_default();
+                Ok((draw_graph(self, &samples, now.timestamp(), device_config), None))
             },
             BoardElementValue::Img(_,_) => {
-                return draw_image(self.x, self.y, self.value.substitute_variables(config.clone(), state.clone()).await, legacy_mode, config.clone(), state.clone()).await;
+                return Ok((draw_image(self.x, self.y, self.value.substitute_variables(config.clone(), state.clone(), now).await, legacy_mode, config.clone(), state.clone()).await, None));
+            },
+            BoardElementValue::Animation(ref image, settings) => {
+                // Older devices can only show their built in images
+                if legacy_mode {
+                    return Ok((String::new(), None));
+                }
+                // Missing images leave a gap the same as still images do
+                let animation = match ImageAnimation::new(config.clone(), state.clone(), self, image, settings).await {
+                    Ok(x) => x,
+                    Err(e) => {
+                        tracing::warn!("{}", e);
+                        return Ok((String::new(), None));
+                    }
+                };
+                let first_frame = animation.frame(Duration::ZERO);
+                // Still images and single frame strips are drawn once
+                let animation = (!animation.duration().is_zero()).then_some(ElementAnimation::Image(animation));
+                Ok((first_frame, animation))
             },
             BoardElementValue::Pixel => {
                 let x = self.x.unwrap_or(0);
@@ -50,12 +146,12 @@ impl DrawBoardElement for BoardElement {
                 let mut colour = colour.to_string();
                 colour = colour.replace('=',"");
                 colour = colour.split_off(1);
-                return format!("q{x:02}{y:02}{colour}==");
+                return Ok((format!("q{x:02}{y:02}{colour}=="), None));
             },
             BoardElementValue::Line(x2, y2, _) => {
                 let x = self.x.unwrap_or(0);
                 let y = self.y;
-                let temp_var = self.value.substitute_variables(config.clone(), state.clone()).await;
+                let temp_var = self.value.substitute_variables(config.clone(), state.clone(), now).await;
                 let colour = match self.colour {
                     shared::boards::ColourOption::Default => ElementColour::default(),
                     shared::boards::ColourOption::Specific(col) => col.clone(),
@@ -74,81 +170,31 @@ impl DrawBoardElement for BoardElement {
                     },
                 };
                 let colour = colour.to_string();
-                return format!("{colour}l{x:02}{y:02}{x2:02}{y2:02}=");
+                return Ok((format!("{colour}l{x:02}{y:02}{x2:02}{y2:02}="), None));
             }
         }
     }
 }
 
 pub trait BoardElementValueSubstitute {
-    async fn substitute_variables(&self, config: ConfigWrapper, state: StateWrapper) -> String;
+    async fn substitute_variables(&self, config: ConfigWrapper, state: StateWrapper, now: &DateTime<Local>) -> String;
 }
 impl BoardElementValueSubstitute for BoardElementValue {
-    async fn substitute_variables(&self, config: ConfigWrapper, state: StateWrapper) -> String {
-        match self {
-            BoardElementValue::Text(x) => {
-                if DEBUG {
-                    tracing::info!("Substituting var '{}'", &x);
-                }
-                let config = config.read().await;
-                let mut display_text = x.clone();
-                for (key, val) in &config.board_variables {
-                    if DEBUG {
-                        tracing::info!("\tSubstituting  '__{}__'", key);
-                    }
-                    let key_match = format!("__{}__", key);
-                    if display_text.contains(&key_match) {
-                        display_text = display_text.replace(&key_match, &val.eval_variable(&key_match, &config, state.clone()).await);
-                    }
-                    if DEBUG {
-                        tracing::info!("\t\tSubstitution Complete... New String: '{}'", &display_text);
-                    }
-                }
-                return display_text;
-            }
-            BoardElementValue::Img(x, dynamic) => {
-                if DEBUG {
-                    tracing::info!("Substituting var '{}'", &x);
-                }
-                let config = config.read().await;
-                let mut display_text = x.clone();
-                if *dynamic {
-                    for (key, val) in &config.board_variables {
-                        if DEBUG {
-                            tracing::info!("\tSubstituting  '__{}__'", key);
-                        }
-                        let key_match = format!("__{}__", key);
-                        if display_text.contains(&key_match) {
-                            display_text = display_text.replace(&key_match, &val.eval_variable(&key_match, &config, state.clone()).await);
-                        }
-                        if DEBUG {
-                            tracing::info!("\t\tSubstitution Complete... New String: '{}'", &display_text);
-                        }
-                    }
-                }
-                return display_text;
-            },
-            BoardElementValue::Pixel => String::new(),
-            BoardElementValue::Line(_, _, x) => {
-                if DEBUG {
-                    tracing::info!("Substituting var '{}'", &x);
-                }
-                let config = config.read().await;
-                let mut display_text = x.clone();
-                for (key, val) in &config.board_variables {
-                    if DEBUG {
-                        tracing::info!("\tSubstituting  '__{}__'", key);
-                    }
-                    let key_match = format!("__{}__", key);
-                    if display_text.contains(&key_match) {
-                        display_text = display_text.replace(&key_match, &val.eval_variable(&key_match, &config, state.clone()).await);
-                    }
-                    if DEBUG {
-                        tracing::info!("\t\tSubstitution Complete... New String: '{}'", &display_text);
-                    }
-                }
-                return display_text;
-            },
+    async fn substitute_variables(&self, config: ConfigWrapper, state: StateWrapper, now: &DateTime<Local>) -> String {
+        let Some(text) = self.template() else {
+            return match self {
+                BoardElementValue::Img(x, false) | BoardElementValue::Animation(x, _) => x.clone(),
+                _ => String::new(),
+            };
+        };
+        if DEBUG {
+            tracing::info!("Substituting var '{}'", text);
+        }
+        let config = config.read().await;
+        let display_text = fill_template(text, &config, state, now).await;
+        if DEBUG {
+            tracing::info!("\tSubstitution Complete... New String: '{}'", &display_text);
         }
+        display_text
     }
 }
\ No newline at end of file
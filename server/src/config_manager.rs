@@ -15,6 +15,7 @@ use tracing::error;
 
 use shared::{
     board_variables::{BoardVariable, BoardVariables, TimeData},
+    date_names::DateNameTables,
     boards::{BoardDefinition, BoardElementBuilder, BoardElementValue}, device_config::{DeviceConfig, DeviceConfigs},
 };
 
@@ -31,6 +32,9 @@ pub(crate) struct Config {
     #[serde(serialize_with = "sorted_map")]
     pub(crate) board_variables: BoardVariables,
     boards: Boards,
+    /// Custom weekday and month names DateTime variables and devices can pick by name
+    #[serde(default, skip_serializing_if = "HashMap::is_empty", serialize_with = "sorted_map")]
+    pub(crate) date_names: DateNameTables,
 }
 
 pub fn sorted_map<S: Serializer, K: Serialize + Ord, V: Serialize>(
@@ -78,18 +82,16 @@ impl Config {
             return config;
         }
         let mut default_board_variables = HashMap::new();
-        default_board_variables.insert(
-            String::from("weekday"),
-            BoardVariable::Time(TimeData::Weekday(0, None)),
-        );
-        default_board_variables.insert(String::from("time"), BoardVariable::Time(TimeData::Time));
-        default_board_variables.insert(String::from("date"), BoardVariable::Time(TimeData::Date));
+        default_board_variables.insert(String::from("weekday"), BoardVariable::Time(TimeData::with_format("%A")));
+        default_board_variables.insert(String::from("time"), BoardVariable::Time(TimeData::with_format("%I:%M %p")));
+        default_board_variables.insert(String::from("date"), BoardVariable::Time(TimeData::with_format("%b %-d %Y")));
 
         let mut new_config = Config {
             config_path: String::from(config_file.to_str().unwrap()),
             device_configs: HashMap::new(),
             board_variables: default_board_variables,
             boards: HashMap::new(),
+            date_names: HashMap::new(),
         };
 
         new_config.device_configs.insert(String::from("default"), DeviceConfig {
@@ -186,7 +188,7 @@ impl Config {
     }
 }
 
-const USER_AGENT: &str = "Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/128.0.0.0 Safari/537.3";
+pub(crate) const USER_AGENT: &str = "Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/128.0.0.0 Safari/537.3";
 async fn download_default_assets(config_file: &Path) {
     let config_dir = config_file
         .parent()
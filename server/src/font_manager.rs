@@ -1,10 +1,58 @@
-use std::path::PathBuf;
+use std::{collections::HashMap, fmt::Display, path::PathBuf, sync::Arc, time::SystemTime};
 
 use tokio::fs;
 
-use crate::config_manager::ConfigWrapper;
+use crate::{config_manager::ConfigWrapper, state_manager::StateWrapper};
 
+/// Parsed fonts shared by every render. Entries are reloaded when the file on disk changes.
+#[derive(Default, Debug)]
+pub(crate) struct FontCache {
+    fonts: HashMap<String, CachedFont>,
+}
+
+#[derive(Debug)]
+struct CachedFont {
+    modified: SystemTime,
+    font: Arc<bdf2::Font>,
+}
 
+#[derive(Debug)]
+pub(crate) enum FontError {
+    NotFound(String),
+    Unreadable(String, String),
+}
+impl Display for FontError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FontError::NotFound(font_name) => write!(f, "Font ({}) does not exist!", font_name),
+            FontError::Unreadable(font_name, e) => write!(f, "Font ({}) could not be loaded: {}", font_name, e),
+        }
+    }
+}
+impl std::error::Error for FontError {}
+
+/// Gets a font (by name, without `.bdf`) from the cache, loading it if it is new or has changed on disk
+pub(crate) async fn get_font(config: ConfigWrapper, state: StateWrapper, font_name: &str) -> Result<Arc<bdf2::Font>, FontError> {
+    let font_path = get_font_path(config, Some(&format!("{}.bdf", font_name))).await;
+    let modified = match fs::metadata(&font_path).await.and_then(|x| x.modified()) {
+        Ok(x) => x,
+        Err(_) => {
+            state.lock().await.font_cache.fonts.remove(font_name);
+            return Err(FontError::NotFound(font_name.to_string()));
+        }
+    };
+    if let Some(cached) = state.lock().await.font_cache.fonts.get(font_name) {
+        if cached.modified == modified {
+            return Ok(cached.font.clone());
+        }
+    }
+    let data = fs::read(&font_path).await.map_err(|e| FontError::Unreadable(font_name.to_string(), e.to_string()))?;
+    let font = bdf2::read(data.as_slice()).map_err(|e| FontError::Unreadable(font_name.to_string(), e.to_string()))?;
+    let font = Arc::new(font);
+    tracing::info!("Loaded font ({}) from '{}'", font_name, font_path.display());
+    state.lock().await.font_cache.fonts.insert(font_name.to_string(), CachedFont { modified, font: font.clone() });
+    Ok(font)
+}
 
 pub async fn get_font_list(config: ConfigWrapper) -> Vec<String> {
     let fonts_path = get_font_path(config, None).await;
@@ -33,13 +81,7 @@ pub async fn get_font_path(config: ConfigWrapper, font_name: Option<&str>) -> Pa
         if font_name.contains("..") {
             panic!();
         }
-        let mut temp_path = fonts_path.clone();
-        temp_path.push(font_name);
-        if !temp_path.exists() {
-            fonts_path.push("5x8.bdf");
-        } else {
-            fonts_path.push(font_name);
-        }
+        fonts_path.push(font_name);
     }
     return fonts_path;
 }
\ No newline at end of file
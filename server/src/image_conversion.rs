@@ -0,0 +1,91 @@
+use std::{collections::{HashMap, VecDeque}, io::Cursor, sync::Arc};
+
+use image::{DynamicImage, ImageFormat, Rgb, RgbImage};
+use sha2::{Digest, Sha256};
+
+use crate::state_manager::StateWrapper;
+
+/// How many converted images are kept before the oldest are dropped
+const MAX_CACHED_CONVERSIONS: usize = 128;
+
+/// What happens to transparent pixels when an image is converted to a BMP for devices
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub(crate) enum ImageConversion {
+    /// Blend onto a solid colour, giving a 24 bit BMP every device can draw
+    Flatten([u8; 3]),
+    /// Keep the alpha channel in a 32 bit BMP, for clients that blend images themselves
+    Preserve,
+}
+impl Default for ImageConversion {
+    /// Unlit LEDs are black, so that is what shows through transparent pixels on a panel
+    fn default() -> Self {
+        ImageConversion::Flatten([0; 3])
+    }
+}
+
+/// Converted images by the hash of the source file's contents, so renamed or copied images share an entry
+/// and edited ones are converted again
+#[derive(Default, Debug)]
+pub(crate) struct ImageConversionCache {
+    converted: HashMap<(String, ImageConversion), Arc<Vec<u8>>>,
+    /// Keys oldest first
+    order: VecDeque<(String, ImageConversion)>,
+}
+
+/// Whether devices can draw the image as it is. Only BMPs are, the client decodes nothing else.
+pub(crate) fn is_device_format(data: &[u8]) -> bool {
+    matches!(image::guess_format(data), Ok(ImageFormat::Bmp))
+}
+
+/// Converts a PNG, JPEG, GIF (first frame) or QOI image to a BMP, reusing an earlier conversion of the same contents
+pub(crate) async fn convert_image(state: StateWrapper, data: &[u8], conversion: ImageConversion) -> Result<Arc<Vec<u8>>, image::ImageError> {
+    let key = (format!("{:x}", Sha256::digest(data)), conversion);
+    if let Some(converted) = state.lock().await.image_conversions.converted.get(&key) {
+        return Ok(converted.clone());
+    }
+    let converted = Arc::new(encode_bmp(image::load_from_memory(data)?, conversion)?);
+    let mut state = state.lock().await;
+    let cache = &mut state.image_conversions;
+    if cache.converted.insert(key.clone(), converted.clone()).is_none() {
+        cache.order.push_back(key);
+    }
+    while cache.order.len() > MAX_CACHED_CONVERSIONS {
+        if let Some(oldest) = cache.order.pop_front() {
+            cache.converted.remove(&oldest);
+        }
+    }
+    Ok(converted)
+}
+
+fn encode_bmp(image: DynamicImage, conversion: ImageConversion) -> Result<Vec<u8>, image::ImageError> {
+    let image = match conversion {
+        ImageConversion::Preserve => DynamicImage::ImageRgba8(image.to_rgba8()),
+        ImageConversion::Flatten(background) => {
+            let image = image.to_rgba8();
+            DynamicImage::ImageRgb8(RgbImage::from_fn(image.width(), image.height(), |x, y| {
+                let [r, g, b, a] = image.get_pixel(x, y).0;
+                let mut pixel = [r, g, b];
+                for (channel, background) in pixel.iter_mut().zip(background) {
+                    *channel = ((*channel as u16 * a as u16 + background as u16 * (0xFF - a as u16)) / 0xFF) as u8;
+                }
+                Rgb(pixel)
+            }))
+        }
+    };
+    let mut bmp = Cursor::new(Vec::new());
+    image.write_to(&mut bmp, ImageFormat::Bmp)?;
+    Ok(bmp.into_inner())
+}
+
+/// Parses a background colour given as `RRGGBB`
+pub(crate) fn parse_background(colour: &str) -> Option<[u8; 3]> {
+    let colour = colour.trim_start_matches('#');
+    if colour.len() != 6 {
+        return None;
+    }
+    let mut background = [0; 3];
+    for (idx, channel) in background.iter_mut().enumerate() {
+        *channel = u8::from_str_radix(colour.get(idx * 2..idx * 2 + 2)?, 16).ok()?;
+    }
+    Some(background)
+}
@@ -1,14 +1,17 @@
-use std::{collections::HashMap, path::PathBuf};
+use std::{collections::HashMap, fmt::Display, path::{Component, Path, PathBuf}};
 
 use base64::{engine::general_purpose, Engine};
 use serde::{Deserialize, Serialize};
 use sha2::{Sha256, Digest};
 use tokio::fs::{self, ReadDir};
 
-use crate::{config_manager::ConfigWrapper, state_manager::StateWrapper};
+use crate::{config_manager::ConfigWrapper, image_conversion::{convert_image, is_device_format, ImageConversion}, state_manager::StateWrapper};
 
 pub type HashedImages = HashMap<String, String>;
 
+/// Extensions of the image files picked up from the images folder, anything other than a BMP is converted before it is sent to a device
+const IMAGE_EXTENSIONS: [&str; 6] = ["bmp", "png", "jpg", "jpeg", "gif", "qoi"];
+
 #[derive(Serialize, Deserialize, Clone, Debug)]
 pub enum FileTree {
     File(String), Dir(String, Vec<FileTree>)
@@ -35,6 +38,52 @@ pub async fn get_image_list(config: ConfigWrapper, state: StateWrapper, force_re
     return images;
 }
 
+/// An image by its hash (or animation frame id) as a BMP devices can draw, converting it if it is in another format
+pub async fn get_image_data(config: ConfigWrapper, state: StateWrapper, image_hash: &str, conversion: ImageConversion) -> Option<Vec<u8>> {
+    let data = get_image_source(config, state.clone(), image_hash).await?;
+    to_device_format(state, image_hash, data, conversion).await
+}
+
+/// Image data as a BMP, leaving BMPs as they are
+pub async fn to_device_format(state: StateWrapper, image_hash: &str, data: Vec<u8>, conversion: ImageConversion) -> Option<Vec<u8>> {
+    if is_device_format(&data) {
+        return Some(data);
+    }
+    match convert_image(state, &data, conversion).await {
+        Ok(converted) => Some(converted.to_vec()),
+        Err(e) => {
+            tracing::warn!("Unable to convert image ({}):\n{}", image_hash, e);
+            None
+        }
+    }
+}
+
+/// Contents of an image file by its hash, which can also be the id of an animation frame
+pub async fn get_image_source(config: ConfigWrapper, state: StateWrapper, image_hash: &str) -> Option<Vec<u8>> {
+    if let Some(image_path) = find_image_path(config.clone(), state.clone(), image_hash).await {
+        let image_path = get_image_path(config, Some(&image_path)).await;
+        return match fs::read(&image_path).await {
+            Ok(data) => Some(data),
+            Err(e) => {
+                tracing::warn!("Unable to read image '{}':\n{}", image_path.display(), e);
+                None
+            }
+        };
+    }
+    state.lock().await.animation_cache.frame(image_hash).map(|frame| frame.to_vec())
+}
+
+/// The path of an image by its hash, only hashing the image folder when that hasn't been done yet
+async fn find_image_path(config: ConfigWrapper, state: StateWrapper, image_hash: &str) -> Option<String> {
+    {
+        let state = state.lock().await;
+        if !state.image_hashes.is_empty() {
+            return state.image_hashes.get(image_hash).cloned();
+        }
+    }
+    get_image_list(config, state, false).await.remove(image_hash)
+}
+
 pub async fn get_hash_by_image_path(image_path: &str, config: ConfigWrapper, state: StateWrapper) -> Option<String> {
     let image_list: HashMap<String, String> = get_image_list(config, state, false).await;
     let reversed: HashMap<String, String> = image_list.into_iter().map(|(k,v)|(v,k)).collect();
@@ -73,7 +122,7 @@ async fn enumerate_dir(dir_name: Option<String>, dir: &mut ReadDir, images: &mut
     while let Some(file) = dir.next_entry().await.expect("Unable to enumerate images") {
         if file.file_type().await.unwrap().is_file() {
             let file_name = file.file_name().into_string().unwrap();
-            if !file_name.ends_with(".bmp") {
+            if !is_image_file_name(&file_name) {
                 continue;
             }
             local_tree.push(FileTree::File(file_name)); // .replace(".bmp", "")
@@ -98,16 +147,144 @@ pub async fn get_image_path(config: ConfigWrapper, image_name: Option<&str>) ->
         fs::create_dir_all(&images_path).await.expect(&format!("Couldn't make images directory at '{}'", images_path.to_string_lossy()));
     }
     if let Some(image_name) = image_name {
-        if image_name.contains("..") {
-            panic!();
-        }
-        let mut temp_path = images_path.clone();
-        temp_path.push(image_name);
-        if !temp_path.exists() {
-            images_path.push("icons/wind.bmp");
-        } else {
-            images_path.push(image_name);
+        match resolve_image_path(&images_path, image_name) {
+            Some(image_path) if image_path.exists() => return image_path,
+            Some(_) => {}
+            None => tracing::warn!("Refusing to load image from outside of the images folder: '{}'", image_name),
         }
+        images_path.push("icons/wind.bmp");
     }
     return images_path;
+}
+
+#[derive(Debug)]
+pub(crate) enum ImageFileError {
+    InvalidPath(String),
+    UnsupportedFormat(String),
+    NotFound(String),
+    AlreadyExists(String),
+    Io(String, std::io::Error),
+}
+impl Display for ImageFileError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ImageFileError::InvalidPath(path) => write!(f, "'{}' is not a valid path inside the images folder", path),
+            ImageFileError::UnsupportedFormat(path) => write!(f, "'{}' is not a supported image ({})", path, IMAGE_EXTENSIONS.join(", ")),
+            ImageFileError::NotFound(path) => write!(f, "'{}' does not exist", path),
+            ImageFileError::AlreadyExists(path) => write!(f, "'{}' already exists", path),
+            ImageFileError::Io(path, e) => write!(f, "Unable to update '{}': {}", path, e),
+        }
+    }
+}
+impl std::error::Error for ImageFileError {}
+
+/// Joins a `/` separated path onto the images folder, or `None` if it could end up anywhere else
+/// (`..`, absolute paths, backslashes or empty segments)
+pub(crate) fn resolve_image_path(images_path: &Path, relative: &str) -> Option<PathBuf> {
+    if relative.is_empty() || relative.contains('\\') || relative.split('/').any(|segment| segment.is_empty() || segment.starts_with('.')) {
+        return None;
+    }
+    let relative = Path::new(relative);
+    if !relative.components().all(|component| matches!(component, Component::Normal(_))) {
+        return None;
+    }
+    Some(images_path.join(relative))
+}
+
+/// Whether a file name has one of the image extensions
+pub(crate) fn is_image_file_name(file_name: &str) -> bool {
+    let extension = file_name.rsplit_once('.').map(|(_, extension)| extension.to_ascii_lowercase());
+    extension.is_some_and(|extension| IMAGE_EXTENSIONS.contains(&extension.as_str()))
+}
+
+async fn resolve_or_reject(config: ConfigWrapper, relative: &str) -> Result<PathBuf, ImageFileError> {
+    let images_path = get_image_path(config, None).await;
+    resolve_image_path(&images_path, relative).ok_or_else(|| ImageFileError::InvalidPath(relative.to_string()))
+}
+
+/// Writes an image into the images folder and returns its path relative to it
+pub(crate) async fn save_image(config: ConfigWrapper, state: StateWrapper, folder: &str, file_name: &str, data: &[u8], overwrite: bool) -> Result<String, ImageFileError> {
+    let image_name = match folder.trim_matches('/') {
+        "" => file_name.to_string(),
+        folder => format!("{}/{}", folder, file_name),
+    };
+    if !is_image_file_name(file_name) {
+        return Err(ImageFileError::UnsupportedFormat(image_name));
+    }
+    let image_path = resolve_or_reject(config.clone(), &image_name).await?;
+    if image_path.parent().is_none_or(|parent| !parent.is_dir()) {
+        return Err(ImageFileError::NotFound(folder.to_string()));
+    }
+    if !overwrite && image_path.exists() {
+        return Err(ImageFileError::AlreadyExists(image_name));
+    }
+    fs::write(&image_path, data).await.map_err(|e| ImageFileError::Io(image_name.clone(), e))?;
+    tracing::info!("Saved image '{}'", image_path.display());
+    get_image_list(config, state, true).await;
+    Ok(image_name)
+}
+
+/// Deletes an image, or a folder once it is empty
+pub(crate) async fn delete_image(config: ConfigWrapper, state: StateWrapper, image_name: &str) -> Result<(), ImageFileError> {
+    let image_path = resolve_or_reject(config.clone(), image_name).await?;
+    let result = if image_path.is_dir() {
+        fs::remove_dir(&image_path).await
+    } else if image_path.is_file() {
+        fs::remove_file(&image_path).await
+    } else {
+        return Err(ImageFileError::NotFound(image_name.to_string()));
+    };
+    result.map_err(|e| ImageFileError::Io(image_name.to_string(), e))?;
+    tracing::info!("Deleted '{}'", image_path.display());
+    get_image_list(config, state, true).await;
+    Ok(())
+}
+
+/// Renames or moves an image or folder within the images folder. Images have to keep an image extension.
+pub(crate) async fn rename_image(config: ConfigWrapper, state: StateWrapper, from: &str, to: &str) -> Result<(), ImageFileError> {
+    let from_path = resolve_or_reject(config.clone(), from).await?;
+    let to_path = resolve_or_reject(config.clone(), to).await?;
+    if !from_path.exists() {
+        return Err(ImageFileError::NotFound(from.to_string()));
+    }
+    if from_path.is_file() && !is_image_file_name(to) {
+        return Err(ImageFileError::UnsupportedFormat(to.to_string()));
+    }
+    if to_path.exists() {
+        return Err(ImageFileError::AlreadyExists(to.to_string()));
+    }
+    // Moving a folder into itself would detach it from the tree
+    if to_path.starts_with(&from_path) {
+        return Err(ImageFileError::InvalidPath(to.to_string()));
+    }
+    fs::rename(&from_path, &to_path).await.map_err(|e| ImageFileError::Io(from.to_string(), e))?;
+    tracing::info!("Renamed '{}' to '{}'", from_path.display(), to_path.display());
+    get_image_list(config, state, true).await;
+    Ok(())
+}
+
+/// Creates a folder (and any missing parents) in the images folder
+pub(crate) async fn create_image_folder(config: ConfigWrapper, folder: &str) -> Result<(), ImageFileError> {
+    let folder_path = resolve_or_reject(config, folder.trim_matches('/')).await?;
+    if folder_path.exists() {
+        return Err(ImageFileError::AlreadyExists(folder.to_string()));
+    }
+    fs::create_dir_all(&folder_path).await.map_err(|e| ImageFileError::Io(folder.to_string(), e))
+}
+
+/// Every folder in the images folder, including empty ones, as `/` separated paths
+pub async fn get_image_folders(config: ConfigWrapper) -> Vec<String> {
+    fn collect(prefix: &str, files: &[FileTree], folders: &mut Vec<String>) {
+        for file in files {
+            if let FileTree::Dir(name, children) = file {
+                let folder = if prefix.is_empty() { name.clone() } else { format!("{}/{}", prefix, name) };
+                folders.push(folder.clone());
+                collect(&folder, children, folders);
+            }
+        }
+    }
+    let mut folders = Vec::new();
+    collect("", &get_images(config).await, &mut folders);
+    folders.sort();
+    folders
 }
\ No newline at end of file
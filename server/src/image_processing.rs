@@ -0,0 +1,101 @@
+use std::io::Cursor;
+
+use image::{imageops::FilterType, DynamicImage, ImageFormat, RgbaImage};
+use shared::image_upload::{Dithering, ImageFit, ImageUploadOptions};
+
+/// 4x4 Bayer matrix, thresholds from 0 to 15
+const BAYER_MATRIX: [[u8; 4]; 4] = [[0, 8, 2, 10], [12, 4, 14, 6], [3, 11, 1, 9], [15, 7, 13, 5]];
+
+/// Whether an upload needs decoding and re-encoding, or can be saved exactly as it was sent
+pub(crate) fn needs_processing(options: &ImageUploadOptions) -> bool {
+    options.fit != ImageFit::Original || options.levels > 0
+}
+
+/// Resizes and quantises an uploaded image, giving a PNG so any transparency survives
+pub(crate) fn process_image(data: &[u8], options: &ImageUploadOptions) -> Result<Vec<u8>, image::ImageError> {
+    let image = image::load_from_memory(data)?;
+    let (width, height) = (options.width as u32, options.height as u32);
+    let image = match options.fit {
+        ImageFit::Original => image,
+        ImageFit::Contain => image.resize(width, height, FilterType::Lanczos3),
+        ImageFit::Crop => image.resize_to_fill(width, height, FilterType::Lanczos3),
+    };
+    let mut image = image.to_rgba8();
+    if options.levels > 0 {
+        quantize(&mut image, options.levels, options.dithering);
+    }
+    let mut png = Cursor::new(Vec::new());
+    DynamicImage::ImageRgba8(image).write_to(&mut png, ImageFormat::Png)?;
+    Ok(png.into_inner())
+}
+
+/// Rounds every channel to one of `levels` evenly spaced values, leaving alpha alone.
+/// Dithering skips pixels darker than half a level: on a panel those LEDs would otherwise
+/// flicker on and off across what should be a black background.
+fn quantize(image: &mut RgbaImage, levels: u8, dithering: Dithering) {
+    let step = 255. / (levels - 1) as f32;
+    let nearest = |value: f32| ((value / step).round() * step).clamp(0., 255.);
+    let (width, height) = (image.width() as usize, image.height() as usize);
+    let is_dark = |pixel: &[u8; 4]| pixel[..3].iter().all(|channel| (*channel as f32) < step / 2.);
+    match dithering {
+        Dithering::None => {
+            for pixel in image.pixels_mut() {
+                for channel in 0..3 {
+                    pixel.0[channel] = nearest(pixel.0[channel] as f32) as u8;
+                }
+            }
+        }
+        Dithering::Ordered => {
+            for (x, y, pixel) in image.enumerate_pixels_mut() {
+                if is_dark(&pixel.0) {
+                    pixel.0[..3].fill(0);
+                    continue;
+                }
+                let threshold = (BAYER_MATRIX[y as usize % 4][x as usize % 4] as f32 + 0.5) / 16. - 0.5;
+                for channel in 0..3 {
+                    pixel.0[channel] = nearest(pixel.0[channel] as f32 + threshold * step) as u8;
+                }
+            }
+        }
+        Dithering::FloydSteinberg => {
+            let mut values: Vec<[f32; 3]> = image.pixels().map(|pixel| [pixel.0[0], pixel.0[1], pixel.0[2]].map(|channel| channel as f32)).collect();
+            let dark: Vec<bool> = image.pixels().map(|pixel| is_dark(&pixel.0)).collect();
+            // Serpentine order stops the error from piling up along one edge
+            for y in 0..height {
+                let left_to_right = y % 2 == 0;
+                for i in 0..width {
+                    let x = if left_to_right { i } else { width - 1 - i };
+                    let idx = y * width + x;
+                    if dark[idx] {
+                        values[idx] = [0.; 3];
+                        continue;
+                    }
+                    let forward: isize = if left_to_right { 1 } else { -1 };
+                    let mut error = [0.; 3];
+                    for (value, error) in values[idx].iter_mut().zip(error.iter_mut()) {
+                        let new = nearest(*value);
+                        *error = *value - new;
+                        *value = new;
+                    }
+                    for (dx, dy, weight) in [(forward, 0, 7.), (-forward, 1, 3.), (0, 1, 5.), (forward, 1, 1.)] {
+                        let (nx, ny) = (x as isize + dx, y + dy);
+                        if nx < 0 || nx >= width as isize || ny >= height {
+                            continue;
+                        }
+                        let neighbour = ny * width + nx as usize;
+                        if !dark[neighbour] {
+                            for (value, error) in values[neighbour].iter_mut().zip(error) {
+                                *value += error * weight / 16.;
+                            }
+                        }
+                    }
+                }
+            }
+            for (pixel, value) in image.pixels_mut().zip(values) {
+                for (channel, value) in pixel.0.iter_mut().zip(value) {
+                    *channel = value as u8;
+                }
+            }
+        }
+    }
+}
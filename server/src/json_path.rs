@@ -0,0 +1,587 @@
+use std::fmt::Display;
+
+use serde_json::{Number, Value};
+
+/// Why a path couldn't be used. Paths that are fine but match nothing aren't errors.
+#[derive(Debug, PartialEq)]
+pub(crate) enum JsonPathError {
+    /// The path itself is malformed
+    Syntax(String),
+    /// A function was used on values it can't handle, like `sum()` over strings
+    Type(String),
+}
+impl Display for JsonPathError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            JsonPathError::Syntax(e) => write!(f, "Invalid path: {}", e),
+            JsonPathError::Type(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+/// Picks a value out of JSON with a JSONPath subset. Old dot paths like `0.title` still work as they did.
+///
+/// - `$` (optional) is the whole document, `.name` or `['name']` a member and `[2]`, `.2` or `[-1]` an array index
+/// - `*` or `[*]` is every member, `..name` every `name` at any depth and `[1:3]` a slice
+/// - `[?(@.type == 'rain' && @.mm > 2)]` keeps the items the filter is true for, compared with `== != < <= > >=`,
+///   joined with `&& || !`; a path on its own checks the item has it
+/// - `length`, `length()`, `min()`, `max()`, `sum()`, `avg()` and `join(', ')` work on everything selected so far,
+///   or on the items of a single array
+///
+/// Gives a single value, or an array when the path can select several (wildcards, slices, filters, `..`).
+pub(crate) fn query(json: &Value, path: &str) -> Result<Option<Value>, JsonPathError> {
+    let segments = Parser::new(path).parse_path()?;
+    evaluate(json, json, &segments)
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Segment {
+    /// An object member, or an array index if the name is a number
+    Member(String),
+    Wildcard,
+    /// The member (or every member for `None`) at any depth
+    Descendant(Option<String>),
+    Slice(Option<i64>, Option<i64>),
+    Filter(Filter),
+    Function(Function),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Function {
+    Length,
+    Min,
+    Max,
+    Sum,
+    Avg,
+    Join(String),
+}
+
+impl Function {
+    fn name(&self) -> &'static str {
+        match self {
+            Function::Length => "length()",
+            Function::Min => "min()",
+            Function::Max => "max()",
+            Function::Sum => "sum()",
+            Function::Avg => "avg()",
+            Function::Join(_) => "join()",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Filter {
+    Or(Box<Filter>, Box<Filter>),
+    And(Box<Filter>, Box<Filter>),
+    Not(Box<Filter>),
+    Exists(Operand),
+    Compare(Operand, Comparison, Operand),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Comparison {
+    Equal,
+    NotEqual,
+    Less,
+    LessOrEqual,
+    Greater,
+    GreaterOrEqual,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Operand {
+    /// `@...`, relative to the item being filtered
+    Current(Vec<Segment>),
+    /// `$...`
+    Root(Vec<Segment>),
+    Literal(Value),
+}
+
+struct Parser {
+    chars: Vec<char>,
+    pos: usize,
+    /// Names stop at spaces and operators inside filters
+    in_filter: bool,
+}
+impl Parser {
+    fn new(path: &str) -> Parser {
+        Parser { chars: path.chars().collect(), pos: 0, in_filter: false }
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+
+    fn eat(&mut self, expected: &str) -> bool {
+        let expected: Vec<char> = expected.chars().collect();
+        if self.chars[self.pos.min(self.chars.len())..].starts_with(&expected) {
+            self.pos += expected.len();
+            return true;
+        }
+        false
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self.peek().is_some_and(|c| c.is_whitespace()) {
+            self.pos += 1;
+        }
+    }
+
+    fn error<T>(&self, message: &str) -> Result<T, JsonPathError> {
+        Err(JsonPathError::Syntax(format!("{} at position {}", message, self.pos)))
+    }
+
+    fn parse_path(&mut self) -> Result<Vec<Segment>, JsonPathError> {
+        self.skip_whitespace();
+        self.eat("$");
+        let mut segments = Vec::new();
+        // Old paths start straight away with a name, `0.title`
+        if !self.in_filter && self.peek().is_some_and(|c| c != '.' && c != '[') {
+            segments.push(self.parse_name()?);
+        }
+        while let Some(segment) = self.parse_segment()? {
+            segments.push(segment);
+        }
+        if !self.in_filter && self.pos < self.chars.len() {
+            return self.error("Unexpected character");
+        }
+        Ok(segments)
+    }
+
+    /// The next segment, or `None` at the end of the path
+    fn parse_segment(&mut self) -> Result<Option<Segment>, JsonPathError> {
+        if self.eat("..") {
+            if self.eat("*") {
+                return Ok(Some(Segment::Descendant(None)));
+            }
+            if self.peek() == Some('[') {
+                return match self.parse_bracket()? {
+                    Segment::Member(name) => Ok(Some(Segment::Descendant(Some(name)))),
+                    Segment::Wildcard => Ok(Some(Segment::Descendant(None))),
+                    _ => self.error("Only names can follow '..'"),
+                };
+            }
+            return match self.parse_name()? {
+                Segment::Member(name) => Ok(Some(Segment::Descendant(Some(name)))),
+                _ => self.error("Only names can follow '..'"),
+            };
+        }
+        if self.eat(".") {
+            if self.eat("*") {
+                return Ok(Some(Segment::Wildcard));
+            }
+            return Ok(Some(self.parse_name()?));
+        }
+        if self.peek() == Some('[') {
+            return Ok(Some(self.parse_bracket()?));
+        }
+        Ok(None)
+    }
+
+    /// A member name, or a function call if it is followed by brackets
+    fn parse_name(&mut self) -> Result<Segment, JsonPathError> {
+        let start = self.pos;
+        while let Some(c) = self.peek() {
+            let ends_name = matches!(c, '.' | '[' | '(')
+                || (self.in_filter && (c.is_whitespace() || matches!(c, '=' | '!' | '<' | '>' | '&' | '|' | ')')));
+            if ends_name {
+                break;
+            }
+            self.pos += 1;
+        }
+        let name: String = self.chars[start..self.pos].iter().collect();
+        if name.is_empty() {
+            return self.error("Expected a name");
+        }
+        if !self.eat("(") {
+            return Ok(Segment::Member(name));
+        }
+        self.skip_whitespace();
+        let argument = match self.peek() {
+            Some('\'') | Some('"') => Some(self.parse_string()?),
+            _ => None,
+        };
+        self.skip_whitespace();
+        if !self.eat(")") {
+            return self.error("Expected ')'");
+        }
+        let function = match (name.as_str(), argument) {
+            ("length", None) => Function::Length,
+            ("min", None) => Function::Min,
+            ("max", None) => Function::Max,
+            ("sum", None) => Function::Sum,
+            ("avg", None) => Function::Avg,
+            ("join", separator) => Function::Join(separator.unwrap_or(String::from(", "))),
+            _ => return self.error(&format!("Unknown function '{}'", name)),
+        };
+        Ok(Segment::Function(function))
+    }
+
+    fn parse_bracket(&mut self) -> Result<Segment, JsonPathError> {
+        self.eat("[");
+        self.skip_whitespace();
+        let segment = if self.eat("*") {
+            Segment::Wildcard
+        } else if self.eat("?") {
+            self.skip_whitespace();
+            let in_filter = std::mem::replace(&mut self.in_filter, true);
+            // The brackets round filters are optional, any there are group like they would anywhere else
+            let filter = self.parse_or()?;
+            self.in_filter = in_filter;
+            Segment::Filter(filter)
+        } else if matches!(self.peek(), Some('\'') | Some('"')) {
+            Segment::Member(self.parse_string()?)
+        } else {
+            let start = self.parse_integer();
+            self.skip_whitespace();
+            if self.eat(":") {
+                self.skip_whitespace();
+                Segment::Slice(start, self.parse_integer())
+            } else {
+                match start {
+                    Some(index) => Segment::Member(index.to_string()),
+                    None => return self.error("Expected an index, a quoted name, '*' or a filter"),
+                }
+            }
+        };
+        self.skip_whitespace();
+        if !self.eat("]") {
+            return self.error("Expected ']'");
+        }
+        Ok(segment)
+    }
+
+    fn parse_integer(&mut self) -> Option<i64> {
+        let start = self.pos;
+        self.eat("-");
+        while self.peek().is_some_and(|c| c.is_ascii_digit()) {
+            self.pos += 1;
+        }
+        let number: String = self.chars[start..self.pos].iter().collect();
+        match number.parse() {
+            Ok(number) => Some(number),
+            Err(_) => {
+                self.pos = start;
+                None
+            }
+        }
+    }
+
+    fn parse_string(&mut self) -> Result<String, JsonPathError> {
+        let Some(quote) = self.peek() else {
+            return self.error("Expected a string");
+        };
+        self.pos += 1;
+        let mut string = String::new();
+        loop {
+            match self.peek() {
+                None => return self.error("Unterminated string"),
+                Some('\\') => {
+                    self.pos += 1;
+                    if let Some(c) = self.peek() {
+                        string.push(c);
+                        self.pos += 1;
+                    }
+                }
+                Some(c) if c == quote => {
+                    self.pos += 1;
+                    return Ok(string);
+                }
+                Some(c) => {
+                    string.push(c);
+                    self.pos += 1;
+                }
+            }
+        }
+    }
+
+    fn parse_or(&mut self) -> Result<Filter, JsonPathError> {
+        let mut filter = self.parse_and()?;
+        loop {
+            self.skip_whitespace();
+            if !self.eat("||") {
+                return Ok(filter);
+            }
+            filter = Filter::Or(Box::new(filter), Box::new(self.parse_and()?));
+        }
+    }
+
+    fn parse_and(&mut self) -> Result<Filter, JsonPathError> {
+        let mut filter = self.parse_unary()?;
+        loop {
+            self.skip_whitespace();
+            if !self.eat("&&") {
+                return Ok(filter);
+            }
+            filter = Filter::And(Box::new(filter), Box::new(self.parse_unary()?));
+        }
+    }
+
+    fn parse_unary(&mut self) -> Result<Filter, JsonPathError> {
+        self.skip_whitespace();
+        if self.peek() == Some('!') && self.chars.get(self.pos + 1) != Some(&'=') {
+            self.pos += 1;
+            return Ok(Filter::Not(Box::new(self.parse_unary()?)));
+        }
+        if self.eat("(") {
+            let filter = self.parse_or()?;
+            self.skip_whitespace();
+            if !self.eat(")") {
+                return self.error("Expected ')'");
+            }
+            return Ok(filter);
+        }
+        let left = self.parse_operand()?;
+        self.skip_whitespace();
+        let comparison = [
+            ("==", Comparison::Equal),
+            ("!=", Comparison::NotEqual),
+            ("<=", Comparison::LessOrEqual),
+            (">=", Comparison::GreaterOrEqual),
+            ("<", Comparison::Less),
+            (">", Comparison::Greater),
+        ]
+        .into_iter()
+        .find(|(op, _)| self.eat(op));
+        match comparison {
+            Some((_, comparison)) => Ok(Filter::Compare(left, comparison, self.parse_operand()?)),
+            None => Ok(Filter::Exists(left)),
+        }
+    }
+
+    fn parse_operand(&mut self) -> Result<Operand, JsonPathError> {
+        self.skip_whitespace();
+        match self.peek() {
+            Some('@') => {
+                self.pos += 1;
+                Ok(Operand::Current(self.parse_filter_path()?))
+            }
+            Some('$') => {
+                self.pos += 1;
+                Ok(Operand::Root(self.parse_filter_path()?))
+            }
+            Some('\'') | Some('"') => Ok(Operand::Literal(Value::String(self.parse_string()?))),
+            _ => {
+                let start = self.pos;
+                while self.peek().is_some_and(|c| c.is_alphanumeric() || matches!(c, '-' | '+' | '.')) {
+                    self.pos += 1;
+                }
+                let literal: String = self.chars[start..self.pos].iter().collect();
+                match literal.as_str() {
+                    "true" => Ok(Operand::Literal(Value::Bool(true))),
+                    "false" => Ok(Operand::Literal(Value::Bool(false))),
+                    "null" => Ok(Operand::Literal(Value::Null)),
+                    _ => match literal.parse::<f64>().ok().and_then(Number::from_f64) {
+                        Some(number) => Ok(Operand::Literal(Value::Number(number))),
+                        None => {
+                            self.pos = start;
+                            self.error("Expected '@', '$', a string, a number, true, false or null")
+                        }
+                    },
+                }
+            }
+        }
+    }
+
+    fn parse_filter_path(&mut self) -> Result<Vec<Segment>, JsonPathError> {
+        let mut segments = Vec::new();
+        while let Some(segment) = self.parse_segment()? {
+            segments.push(segment);
+        }
+        Ok(segments)
+    }
+}
+
+fn evaluate(root: &Value, json: &Value, segments: &[Segment]) -> Result<Option<Value>, JsonPathError> {
+    let mut nodes = vec![json.clone()];
+    // Whether the path so far can select several values
+    let mut multiple = false;
+    for segment in segments {
+        match segment {
+            Segment::Member(name) => {
+                // `length` is also a member name, it's only the function when there is no such member
+                if name == "length" && nodes.iter().all(|node| node.get("length").is_none()) {
+                    nodes = vec![apply_function(&Function::Length, nodes, multiple)?];
+                    multiple = false;
+                    continue;
+                }
+                nodes = nodes.iter().filter_map(|node| member(node, name)).cloned().collect();
+            }
+            Segment::Wildcard => {
+                nodes = nodes.iter().flat_map(children).cloned().collect();
+                multiple = true;
+            }
+            Segment::Descendant(name) => {
+                let mut found = Vec::new();
+                for node in &nodes {
+                    descendants(node, name.as_deref(), &mut found);
+                }
+                nodes = found;
+                multiple = true;
+            }
+            Segment::Slice(start, end) => {
+                nodes = nodes.iter().filter_map(|node| node.as_array()).flat_map(|items| slice(items, *start, *end)).cloned().collect();
+                multiple = true;
+            }
+            Segment::Filter(filter) => {
+                let mut kept = Vec::new();
+                for item in nodes.iter().flat_map(children) {
+                    if matches(root, item, filter)? {
+                        kept.push(item.clone());
+                    }
+                }
+                nodes = kept;
+                multiple = true;
+            }
+            Segment::Function(function) => {
+                nodes = vec![apply_function(function, nodes, multiple)?];
+                multiple = false;
+            }
+        }
+    }
+    if multiple {
+        return Ok(if nodes.is_empty() { None } else { Some(Value::Array(nodes)) });
+    }
+    Ok(nodes.into_iter().next())
+}
+
+fn member<'a>(node: &'a Value, name: &str) -> Option<&'a Value> {
+    match node {
+        Value::Object(map) => map.get(name),
+        Value::Array(items) => {
+            let index = name.parse::<i64>().ok()?;
+            let index = if index < 0 { items.len() as i64 + index } else { index };
+            items.get(usize::try_from(index).ok()?)
+        }
+        _ => None,
+    }
+}
+
+fn children(node: &Value) -> Vec<&Value> {
+    match node {
+        Value::Object(map) => map.values().collect(),
+        Value::Array(items) => items.iter().collect(),
+        _ => Vec::new(),
+    }
+}
+
+fn descendants(node: &Value, name: Option<&str>, found: &mut Vec<Value>) {
+    let members: Vec<(Option<&String>, &Value)> = match node {
+        Value::Object(map) => map.iter().map(|(key, value)| (Some(key), value)).collect(),
+        Value::Array(items) => items.iter().map(|value| (None, value)).collect(),
+        _ => return,
+    };
+    for (key, value) in members {
+        if name.is_none() || (key.is_some() && key.map(|key| key.as_str()) == name) {
+            found.push(value.clone());
+        }
+        descendants(value, name, found);
+    }
+}
+
+/// Python style, negative positions count back from the end
+fn slice(items: &[Value], start: Option<i64>, end: Option<i64>) -> &[Value] {
+    let len = items.len() as i64;
+    let position = |index: i64| if index < 0 { (len + index).max(0) } else { index.min(len) } as usize;
+    let start = start.map(position).unwrap_or(0);
+    let end = end.map(position).unwrap_or(items.len());
+    items.get(start..end.max(start)).unwrap_or_default()
+}
+
+fn matches(root: &Value, item: &Value, filter: &Filter) -> Result<bool, JsonPathError> {
+    Ok(match filter {
+        Filter::Or(left, right) => matches(root, item, left)? || matches(root, item, right)?,
+        Filter::And(left, right) => matches(root, item, left)? && matches(root, item, right)?,
+        Filter::Not(filter) => !matches(root, item, filter)?,
+        Filter::Exists(operand) => !matches!(operand_value(root, item, operand)?, None | Some(Value::Null) | Some(Value::Bool(false))),
+        Filter::Compare(left, comparison, right) => {
+            let (Some(left), Some(right)) = (operand_value(root, item, left)?, operand_value(root, item, right)?) else {
+                return Ok(*comparison == Comparison::NotEqual);
+            };
+            let ordering = match (&left, &right) {
+                (Value::Number(left), Value::Number(right)) => left.as_f64().partial_cmp(&right.as_f64()),
+                (Value::String(left), Value::String(right)) => Some(left.cmp(right)),
+                _ => None,
+            };
+            match (comparison, ordering) {
+                (Comparison::Equal, Some(ordering)) => ordering.is_eq(),
+                (Comparison::Equal, None) => left == right,
+                (Comparison::NotEqual, Some(ordering)) => ordering.is_ne(),
+                (Comparison::NotEqual, None) => left != right,
+                (Comparison::Less, Some(ordering)) => ordering.is_lt(),
+                (Comparison::LessOrEqual, Some(ordering)) => ordering.is_le(),
+                (Comparison::Greater, Some(ordering)) => ordering.is_gt(),
+                (Comparison::GreaterOrEqual, Some(ordering)) => ordering.is_ge(),
+                // Values of different types are never more or less than each other
+                (_, None) => false,
+            }
+        }
+    })
+}
+
+fn operand_value(root: &Value, item: &Value, operand: &Operand) -> Result<Option<Value>, JsonPathError> {
+    match operand {
+        Operand::Current(segments) => evaluate(root, item, segments),
+        Operand::Root(segments) => evaluate(root, root, segments),
+        Operand::Literal(value) => Ok(Some(value.clone())),
+    }
+}
+
+/// Runs a function over everything selected so far, or over the items of a lone array (or the characters of a
+/// lone string, for `length`)
+fn apply_function(function: &Function, nodes: Vec<Value>, multiple: bool) -> Result<Value, JsonPathError> {
+    let items = match (multiple, nodes.as_slice()) {
+        (false, [Value::Array(items)]) => items.clone(),
+        (false, [Value::String(text)]) if *function == Function::Length => return Ok(Value::from(text.chars().count())),
+        (false, [Value::Object(map)]) if *function == Function::Length => return Ok(Value::from(map.len())),
+        (false, [_]) => return Err(JsonPathError::Type(format!("{} needs an array", function.name()))),
+        _ => nodes,
+    };
+    if let Function::Length = function {
+        return Ok(Value::from(items.len()));
+    }
+    if let Function::Join(separator) = function {
+        let text: Vec<String> = items.iter().map(value_to_text).collect();
+        return Ok(Value::String(text.join(separator)));
+    }
+    let mut numbers = Vec::with_capacity(items.len());
+    for item in &items {
+        let number = match item {
+            Value::Number(number) => number.as_f64(),
+            Value::String(text) => text.trim().parse::<f64>().ok(),
+            _ => None,
+        };
+        match number {
+            Some(number) => numbers.push(number),
+            None => return Err(JsonPathError::Type(format!("{} needs numbers, found {}", function.name(), item))),
+        }
+    }
+    if numbers.is_empty() {
+        return Ok(if *function == Function::Sum { Value::from(0) } else { Value::Null });
+    }
+    let result = match function {
+        Function::Min => numbers.iter().copied().fold(f64::INFINITY, f64::min),
+        Function::Max => numbers.iter().copied().fold(f64::NEG_INFINITY, f64::max),
+        Function::Sum => numbers.iter().sum(),
+        Function::Avg => numbers.iter().sum::<f64>() / numbers.len() as f64,
+        Function::Length | Function::Join(_) => unreachable!(),
+    };
+    Ok(number_value(result))
+}
+
+/// Whole numbers are kept as integers so they print without a `.0`
+fn number_value(number: f64) -> Value {
+    if number.fract() == 0. && number.abs() < i64::MAX as f64 {
+        return Value::from(number as i64);
+    }
+    Number::from_f64(number).map(Value::Number).unwrap_or(Value::Null)
+}
+
+/// How a value is shown on a board, strings without their quotes
+pub(crate) fn value_to_text(value: &Value) -> String {
+    match value {
+        Value::String(text) => text.clone(),
+        Value::Null => String::new(),
+        value => value.to_string(),
+    }
+}
@@ -10,11 +10,19 @@ mod web_interface;
 mod matrix_server;
 mod board_variables;
 mod boards;
+mod json_path;
 
 mod config_manager;
 mod state_manager;
 mod font_manager;
 mod image_manager;
+mod image_conversion;
+mod image_processing;
+mod animation_manager;
+mod preview;
+mod pushed_values;
+mod variable_history;
+mod variable_refresher;
 
 #[tokio::main]
 async fn main() {
@@ -33,7 +41,11 @@ async fn main() {
 
     let web_server = tokio::spawn(web_interface::web::run_web_server(running_config.clone(), state.clone()));
     let matrix_server = tokio::spawn(matrix_server::server::run_matrix_server(running_config.clone(), state.clone()));
+    let history_sampler = tokio::spawn(variable_history::run_history_sampler(running_config.clone(), state.clone()));
+    let variable_refresher = tokio::spawn(variable_refresher::run_variable_refresher(running_config.clone(), state.clone()));
     let _ = matrix_server.await;
     web_server.abort();
+    history_sampler.abort();
+    variable_refresher.abort();
     let _ = web_server.await;
 }
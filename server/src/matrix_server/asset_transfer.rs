@@ -0,0 +1,127 @@
+use std::collections::HashSet;
+
+use tokio::fs;
+
+use crate::{config_manager::ConfigWrapper, font_manager::get_font_path, image_conversion::ImageConversion, image_manager::get_image_data, state_manager::StateWrapper};
+
+/// First protocol version where image and font payloads are pushed over the matrix connection
+pub(crate) const INBAND_ASSETS_PROTO_VERSION: u64 = 2;
+
+const COMMAND_LENGTH: usize = 10;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub(crate) enum AssetKind {
+    Image,
+    Font,
+}
+impl AssetKind {
+    fn to_char(self) -> char {
+        match self {
+            AssetKind::Image => 'i',
+            AssetKind::Font => 'f',
+        }
+    }
+    fn from_char(kind: char) -> Option<AssetKind> {
+        match kind {
+            'i' => Some(AssetKind::Image),
+            'f' => Some(AssetKind::Font),
+            _ => None,
+        }
+    }
+}
+
+/// Keeps track of which assets have been pushed to a single connected device
+#[derive(Default, Debug)]
+pub(crate) struct AssetTracker {
+    sent: HashSet<(AssetKind, String)>,
+    pending_message: Vec<u8>,
+}
+
+impl AssetTracker {
+    pub(crate) fn new() -> AssetTracker {
+        AssetTracker {
+            ..Default::default()
+        }
+    }
+
+    /// Buffer used to collect a (possibly partial) message sent back by the device
+    pub(crate) fn message_buffer(&mut self) -> &mut Vec<u8> {
+        &mut self.pending_message
+    }
+
+    /// Handles a complete `k`(ack) or `n`(nack) line from the device
+    pub(crate) fn handle_message(&mut self, address: &str) {
+        let message = String::from_utf8_lossy(&self.pending_message).trim_end().to_string();
+        self.pending_message.clear();
+        let mut chars = message.chars();
+        let (response, kind) = (chars.next(), chars.next().and_then(AssetKind::from_char));
+        let asset_id: String = chars.collect();
+        let Some(kind) = kind else {
+            tracing::warn!("[{}] Unknown message from device: \"{}\"", address, &message);
+            return;
+        };
+        match response {
+            Some('k') => {
+                tracing::info!("[{}] Device cached asset '{}'", address, &asset_id);
+            }
+            Some('n') => {
+                // The device lost or never received the asset, so send it again on the next render
+                tracing::warn!("[{}] Device is missing asset '{}', resending", address, &asset_id);
+                self.sent.remove(&(kind, asset_id));
+            }
+            _ => tracing::warn!("[{}] Unknown message from device: \"{}\"", address, &message),
+        }
+    }
+
+    /// Prepends every asset referenced by a rendered board that the device has not received yet
+    pub(crate) async fn inject_assets(&mut self, rendered_board: &str, config: ConfigWrapper, state: StateWrapper) -> Vec<u8> {
+        let mut output = Vec::with_capacity(rendered_board.len());
+        for command in rendered_board.as_bytes().chunks(COMMAND_LENGTH) {
+            let asset = match command.first() {
+                Some(b'i') if command.len() == COMMAND_LENGTH => Some((AssetKind::Image, String::from_utf8_lossy(&command[5..]).trim_end_matches('=').to_string())),
+                Some(b'f') => Some((AssetKind::Font, String::from_utf8_lossy(&command[1..]).trim_end_matches('=').to_string())),
+                _ => None,
+            };
+            if let Some(asset) = asset {
+                if !self.sent.contains(&asset) {
+                    if let Some(payload) = load_asset(&asset, config.clone(), state.clone()).await {
+                        output.extend_from_slice(&encode_asset(&asset, &payload));
+                        self.sent.insert(asset);
+                    }
+                }
+            }
+            output.extend_from_slice(command);
+        }
+        output
+    }
+}
+
+/// `a{kind}{length:08}` then `{id:=<10}` followed by exactly `length` bytes of payload
+fn encode_asset(asset: &(AssetKind, String), payload: &[u8]) -> Vec<u8> {
+    let (kind, asset_id) = asset;
+    let mut out = format!("a{}{:08}{:=<10}", kind.to_char(), payload.len(), asset_id).into_bytes();
+    out.extend_from_slice(payload);
+    out
+}
+
+async fn load_asset(asset: &(AssetKind, String), config: ConfigWrapper, state: StateWrapper) -> Option<Vec<u8>> {
+    let payload = match asset {
+        // Images can also be animation frames, which only exist in memory
+        (AssetKind::Image, hash) => get_image_data(config, state, hash, ImageConversion::default()).await?,
+        (AssetKind::Font, font_name) => {
+            let font_path = get_font_path(config, Some(&format!("{}.bdf", font_name))).await;
+            match fs::read(&font_path).await {
+                Ok(payload) => payload,
+                Err(e) => {
+                    tracing::warn!("Unable to read asset '{}' for in-band transfer:\n{}", font_path.display(), e);
+                    return None;
+                }
+            }
+        }
+    };
+    if payload.len() > 99_999_999 {
+        tracing::warn!("Asset '{}' is too large to send in-band", asset.1);
+        return None;
+    }
+    Some(payload)
+}
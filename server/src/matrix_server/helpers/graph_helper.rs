@@ -0,0 +1,26 @@
+use shared::{boards::BoardElement, device_config::DeviceConfig, graphs::element_graph};
+
+/// Commands for a graph element: a `c` colour change whenever the colour changes, then an `l` line per segment
+pub(crate) fn draw_graph(element: &BoardElement, samples: &[(i64, f64)], now: i64, device_config: &DeviceConfig) -> String {
+    let Some(segments) = element_graph(element, samples, now, &device_config.temperature_colours) else {
+        return String::new();
+    };
+    let mut instructions = String::new();
+    let mut current_colour = None;
+    for segment in segments {
+        let colour = segment.colour.to_string();
+        if current_colour.as_ref() != Some(&colour) {
+            instructions.push_str(&colour);
+            current_colour = Some(colour);
+        }
+        let clamp = |x: i32| x.clamp(0, 99);
+        instructions.push_str(&format!(
+            "l{:02}{:02}{:02}{:02}=",
+            clamp(segment.start.0),
+            clamp(segment.start.1),
+            clamp(segment.end.0),
+            clamp(segment.end.1)
+        ));
+    }
+    instructions
+}
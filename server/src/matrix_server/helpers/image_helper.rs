@@ -1,4 +1,8 @@
-use crate::{config_manager::ConfigWrapper, image_manager::get_hash_by_image_path, state_manager::StateWrapper};
+use std::{sync::Arc, time::Duration};
+
+use shared::{boards::{AnimationSettings, BoardElement}, image_animation::{animation_duration, animation_frame_at, animation_frame_interval}};
+
+use crate::{animation_manager::{get_animation, AnimationError, AnimationFrame}, config_manager::ConfigWrapper, image_manager::get_hash_by_image_path, state_manager::StateWrapper};
 
 pub(crate) async fn draw_image(x: Option<u8>, y: u8, image: String, legacy_mode: bool, config: ConfigWrapper, state: StateWrapper) -> String {
     if legacy_mode {
@@ -8,6 +12,16 @@ pub(crate) async fn draw_image(x: Option<u8>, y: u8, image: String, legacy_mode:
             return String::new();
         }
     }
+    // Devices only decode BMPs, so still GIFs show their first frame
+    if image.ends_with(".gif") {
+        return match get_animation(config, state, &image, 0, Duration::ZERO).await {
+            Ok(frames) => frames.first().map(|frame| format!("i{:02}{:02}{:=<5}", x.unwrap_or_default(), y, &frame.id)).unwrap_or_default(),
+            Err(e) => {
+                tracing::warn!("{}", e);
+                String::new()
+            }
+        };
+    }
     let img = get_hash_by_image_path(&image, config, state).await;
     if let Some(img_hash) = img {
         format!("i{:02}{:02}{:=<5}", x.unwrap_or_default(), y, &img_hash)
@@ -15,4 +29,42 @@ pub(crate) async fn draw_image(x: Option<u8>, y: u8, image: String, legacy_mode:
         tracing::warn!("No image matching path ({})", &image);
         String::new()
     }
-}
\ No newline at end of file
+}
+
+/// An animated image, played by drawing each of its frames as an image in turn
+pub struct ImageAnimation {
+    x: u8,
+    y: u8,
+    frames: Arc<Vec<AnimationFrame>>,
+    delays: Vec<Duration>,
+    settings: AnimationSettings,
+}
+impl ImageAnimation {
+    pub(crate) async fn new(config: ConfigWrapper, state: StateWrapper, element: &BoardElement, image: &str, settings: AnimationSettings) -> Result<ImageAnimation, AnimationError> {
+        let frame_time = Duration::from_millis(settings.frame_time as u64);
+        let frames = get_animation(config, state, image, settings.sprite_frames, frame_time).await?;
+        Ok(ImageAnimation {
+            x: element.x.unwrap_or_default(),
+            y: element.y,
+            delays: frames.iter().map(|frame| frame.delay).collect(),
+            frames,
+            settings,
+        })
+    }
+
+    pub fn duration(&self) -> Duration {
+        animation_duration(&self.delays, &self.settings)
+    }
+
+    pub fn frame_interval(&self) -> Duration {
+        animation_frame_interval(&self.delays, &self.settings)
+    }
+
+    /// Commands that draw the frame showing `elapsed` after the board was first drawn
+    pub fn frame(&self, elapsed: Duration) -> String {
+        match self.frames.get(animation_frame_at(&self.delays, &self.settings, elapsed)) {
+            Some(frame) => format!("i{:02}{:02}{:=<5}", self.x, self.y, &frame.id),
+            None => String::new(),
+        }
+    }
+}
@@ -1,2 +1,4 @@
 pub mod text_helpers;
-pub mod image_helper;
\ No newline at end of file
+pub mod image_helper;
+pub mod shape_helper;
+pub mod graph_helper;
\ No newline at end of file
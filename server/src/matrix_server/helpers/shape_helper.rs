@@ -0,0 +1,80 @@
+use shared::{boards::{BoardElement, ColourOption, ElementColour}, device_config::DeviceConfig, indicators::element_indicator, shapes::{element_shape, Shape}};
+
+/// Commands for a shape element: its outline colour, then `d` for the fill and stroke width, then the shape itself.
+/// Triangles and polylines send their points as `v` commands first since they do not fit in one command.
+pub(crate) fn draw_shape(element: &BoardElement) -> String {
+    let Some((shape, style)) = element_shape(element) else {
+        return String::new();
+    };
+    let colour = match element.colour {
+        ColourOption::Specific(colour) => colour,
+        _ => ElementColour::default(),
+    };
+    let mut instructions = colour.to_string();
+    let fill = match style.fill {
+        Some(fill) => format!("{}1", &fill.to_string()[1..4]),
+        None => String::from("0000"),
+    };
+    instructions.push_str(&format!("d{}{:02}===", fill, style.stroke_width.min(99)));
+    instructions.push_str(&shape_commands(shape));
+    instructions
+}
+
+/// Commands that draw a shape with the current colour and `d` style
+fn shape_commands(shape: Shape) -> String {
+    let mut instructions = String::new();
+    // Positions and sizes are sent as two digits
+    let (clamp, size) = (|x: i32| x.clamp(0, 99), |x: u32| x.min(99));
+    match shape {
+        Shape::Rect { x, y, width, height } => {
+            instructions.push_str(&format!("r{:02}{:02}{:02}{:02}=", clamp(x), clamp(y), size(width), size(height)));
+        }
+        Shape::RoundedRect { x, y, width, height, radius } => {
+            instructions.push_str(&format!("R{:02}{:02}{:02}{:02}{:X}", clamp(x), clamp(y), size(width), size(height), radius.min(0xF)));
+        }
+        Shape::Circle { x, y, diameter } => {
+            instructions.push_str(&format!("o{:02}{:02}{:02}===", clamp(x), clamp(y), size(diameter)));
+        }
+        Shape::Ellipse { x, y, width, height } => {
+            instructions.push_str(&format!("e{:02}{:02}{:02}{:02}=", clamp(x), clamp(y), size(width), size(height)));
+        }
+        Shape::Triangle(points) => {
+            for (x, y) in points {
+                instructions.push_str(&format!("v{:02}{:02}=====", clamp(x), clamp(y)));
+            }
+            instructions.push_str("T=========");
+        }
+        Shape::Polyline(points) => {
+            for (x, y) in points {
+                instructions.push_str(&format!("v{:02}{:02}=====", clamp(x), clamp(y)));
+            }
+            instructions.push_str("P=========");
+        }
+        Shape::Arc { x, y, diameter, start, sweep } => {
+            instructions.push_str(&format!("u{:03}{:03}===", start.rem_euclid(360), sweep.clamp(0, 360)));
+            instructions.push_str(&format!("A{:02}{:02}{:02}===", clamp(x), clamp(y), size(diameter)));
+        }
+    }
+    instructions
+}
+
+/// Commands for a bar or gauge element showing `value_text`. Bars are filled rectangles and gauges are arcs
+/// sent as `u{start:03}{sweep:03}` for the angles, then `A{x}{y}{diameter}` drawn with the `d` stroke width.
+pub(crate) fn draw_indicator(element: &BoardElement, value_text: &str, device_config: &DeviceConfig) -> String {
+    let Some(parts) = element_indicator(element, value_text, &device_config.temperature_colours) else {
+        return String::new();
+    };
+    let mut instructions = String::new();
+    for part in parts {
+        let colour = part.colour.to_string();
+        if part.stroke_width == 0 {
+            // Filled with no outline, so the current colour does not matter
+            instructions.push_str(&format!("d{}100===", &colour[1..4]));
+        } else {
+            instructions.push_str(&colour);
+            instructions.push_str(&format!("d0000{:02}===", part.stroke_width.min(99)));
+        }
+        instructions.push_str(&shape_commands(part.shape));
+    }
+    instructions
+}
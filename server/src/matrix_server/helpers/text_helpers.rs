@@ -1,19 +1,165 @@
-use std::path::PathBuf;
+use std::{sync::Arc, time::Duration};
 
-use bdf2::Bitmap;
-use shared::{boards::{ColourOption, ElementColour}, device_config::DeviceConfig};
+use shared::{boards::{BoardElement, ColourOption, ElementColour, MarqueeSettings, TextBoxSettings, TextOverflow}, canvas::COMMAND_LENGTH, device_config::DeviceConfig, text_layout::{element_text_box, element_wrap_box, glyph_pixels, layout_element_text, layout_lines, layout_marquee, marquee_duration, marquee_frame_interval, measure_text, page_at, paginate_text, text_box_duration, text_fits, FontMetrics, LayoutBox}};
 
-use crate::config_manager::ConfigWrapper;
+use crate::{config_manager::ConfigWrapper, font_manager::{get_font, FontError}, state_manager::StateWrapper};
 
-pub(crate) async fn draw_text(config:ConfigWrapper, device_config: &DeviceConfig, board_name: &str, x: Option<u8>, y: u8, colour: &ColourOption, font: &Option<String>, text: String) -> String {
+pub(crate) async fn draw_text(config:ConfigWrapper, state: StateWrapper, device_config: &DeviceConfig, board_name: &str, element: &BoardElement, text: String) -> Result<String, FontError> {
     let mut instructions = String::new();
-    let colour = match colour {
+    let colour = get_text_colour(element, device_config, &text);
+    let font_name = get_font_name(element);
+    let font = get_font(config.clone(), state, &font_name).await?;
+    instructions.push_str(&colour.to_string());
+    instructions.push_str(&format!("f{:=<9}", font_name));
+
+    let board_size = config.read().await.get_boards().get(board_name).unwrap().size;
+    let layout = layout_element_text(element, board_size, font.as_ref(), &text);
+    for glyph in layout.glyphs {
+        if !font.has_glyph(glyph.character) {
+            tracing::warn!("Font ({}) has no glyph for '{}'", &font_name, glyph.character);
+        }
+        // Positions are sent as two digits, anything else is off the board anyway
+        if !(0..100).contains(&glyph.x) || !(0..100).contains(&glyph.y) {
+            continue;
+        }
+        instructions.push_str(&encode_character(glyph.x, glyph.y, glyph.character));
+    }
+
+    Ok(instructions)
+}
+
+/// Text scrolling within its box, redrawn a frame at a time by the connection while the board is shown
+pub(crate) struct MarqueeAnimation {
+    element: BoardElement,
+    settings: MarqueeSettings,
+    text: String,
+    font_name: String,
+    font: Arc<bdf2::Font>,
+    colour: ElementColour,
+    board_size: (u8, u8),
+    clip: LayoutBox,
+}
+impl MarqueeAnimation {
+    pub(crate) async fn new(config: ConfigWrapper, state: StateWrapper, device_config: &DeviceConfig, board_name: &str, element: &BoardElement, settings: MarqueeSettings, text: String) -> Result<MarqueeAnimation, FontError> {
+        let colour = get_text_colour(element, device_config, &text);
+        let font_name = get_font_name(element);
+        let font = get_font(config.clone(), state, &font_name).await?;
+        let board_size = config.read().await.get_boards().get(board_name).unwrap().size;
+        let clip = element_text_box(element, board_size, font.as_ref());
+        Ok(MarqueeAnimation { element: element.clone(), settings, text, font_name, font, colour, board_size, clip })
+    }
+
+    pub(crate) fn duration(&self) -> Duration {
+        let overflow = measure_text(self.font.as_ref(), &self.text).saturating_sub(self.clip.width);
+        marquee_duration(&self.settings, overflow)
+    }
+
+    pub(crate) fn frame_interval(&self) -> Duration {
+        marquee_frame_interval(&self.settings)
+    }
+
+    /// Clears the box and redraws the text where it is `elapsed` into the scroll. Characters cut off by the
+    /// edge of the box are sent as single pixels since devices have no clipping of their own.
+    pub(crate) fn frame(&self, elapsed: Duration) -> String {
+        let mut instructions = clear_area(&self.clip);
+        instructions.push_str(&self.colour.to_string());
+        instructions.push_str(&format!("f{:=<9}", self.font_name));
+        let pixel_colour: String = self.colour.to_string().chars().skip(1).take(3).collect();
+        let layout = layout_marquee(&self.element, self.board_size, self.font.as_ref(), &self.text, &self.settings, elapsed);
+        let line_height = self.font.line_height() as i32;
+        for glyph in layout.glyphs {
+            let advance = self.font.advance(glyph.character) as i32;
+            let inside = self.clip.contains(glyph.x, glyph.y) && self.clip.contains(glyph.x + advance - 1, glyph.y + line_height - 1);
+            if inside && (0..100).contains(&glyph.x) && (0..100).contains(&glyph.y) {
+                instructions.push_str(&encode_character(glyph.x, glyph.y, glyph.character));
+                continue;
+            }
+            for (x, y) in glyph_pixels(self.font.as_ref(), glyph.character, glyph.x, glyph.y) {
+                if self.clip.contains(x, y) && (0..100).contains(&x) && (0..100).contains(&y) {
+                    instructions.push_str(&format!("q{:02}{:02}{}==", x, y, &pixel_colour));
+                }
+            }
+        }
+        instructions
+    }
+}
+
+/// Text wrapped within a box. When it is paged the connection redraws it as each page comes up.
+pub(crate) struct TextBoxAnimation {
+    element: BoardElement,
+    settings: TextBoxSettings,
+    font_name: String,
+    font: Arc<bdf2::Font>,
+    colour: ElementColour,
+    layout_box: LayoutBox,
+    pages: Vec<Vec<String>>,
+}
+impl TextBoxAnimation {
+    pub(crate) async fn new(config: ConfigWrapper, state: StateWrapper, device_config: &DeviceConfig, board_name: &str, element: &BoardElement, settings: TextBoxSettings, text: String) -> Result<TextBoxAnimation, FontError> {
+        let colour = get_text_colour(element, device_config, &text);
+        let mut font_name = get_font_name(element);
+        let mut font = get_font(config.clone(), state.clone(), &font_name).await?;
+        let board_size = config.read().await.get_boards().get(board_name).unwrap().size;
+        let layout_box = element_wrap_box(element, board_size);
+        if let TextOverflow::Shrink(small_font_name) = &settings.overflow {
+            if !text_fits(font.as_ref(), &text, &layout_box, settings.line_spacing) {
+                font_name = truncate_string(small_font_name.clone(), 9);
+                font = get_font(config.clone(), state, &font_name).await?;
+            }
+        }
+        let pages = paginate_text(font.as_ref(), &text, &layout_box, &settings);
+        Ok(TextBoxAnimation { element: element.clone(), settings, font_name, font, colour, layout_box, pages })
+    }
+
+    pub(crate) fn duration(&self) -> Duration {
+        text_box_duration(&self.settings, self.pages.len())
+    }
+
+    pub(crate) fn frame_interval(&self) -> Duration {
+        match self.settings.overflow {
+            TextOverflow::Page(page_time) => Duration::from_millis(page_time.max(1) as u64),
+            _ => Duration::ZERO,
+        }
+    }
+
+    /// Clears the box and draws the page shown `elapsed` after the board was drawn
+    pub(crate) fn frame(&self, elapsed: Duration) -> String {
+        let mut instructions = clear_area(&self.layout_box);
+        instructions.push_str(&self.colour.to_string());
+        instructions.push_str(&format!("f{:=<9}", self.font_name));
+        let Some(page) = self.pages.get(page_at(&self.settings, self.pages.len(), elapsed)) else {
+            return instructions;
+        };
+        let layout = layout_lines(self.font.as_ref(), page, &self.layout_box, self.element.get_horizontal_alignment(), self.element.vertical_alignment, self.settings.line_spacing);
+        for glyph in layout.glyphs {
+            if (0..100).contains(&glyph.x) && (0..100).contains(&glyph.y) {
+                instructions.push_str(&encode_character(glyph.x, glyph.y, glyph.character));
+            }
+        }
+        instructions
+    }
+}
+
+/// Blanks out an area of the board with a black filled rectangle
+fn clear_area(area: &LayoutBox) -> String {
+    // Positions and sizes are sent as two digits, so only the part of the area on the board is cleared
+    let (left, top) = (area.x.max(0), area.y.max(0));
+    let right = (area.x + area.width as i32).min(100);
+    let bottom = (area.y + area.height as i32).min(100);
+    if left >= right || top >= bottom {
+        return String::new();
+    }
+    format!("c000======d000101===r{:02}{:02}{:02}{:02}=", left, top, (right - left).min(99), (bottom - top).min(99))
+}
+
+fn get_text_colour(element: &BoardElement, device_config: &DeviceConfig, text: &str) -> ElementColour {
+    match &element.colour {
         ColourOption::Default => ElementColour::default(),
         ColourOption::Specific(element_colour) => element_colour.to_owned(),
         ColourOption::ParseTemperature => {
             let mut col = ElementColour::default();
             let re = regex::Regex::new(r"(?P<temp>\d+)").unwrap();
-            let cap = re.captures(&text);
+            let cap = re.captures(text);
             if cap.is_some() {
             let cap = cap.unwrap();
                 let temp = &cap["temp"];
@@ -23,37 +169,23 @@ pub(crate) async fn draw_text(config:ConfigWrapper, device_config: &DeviceConfig
             }
             col
         },
-    };
-    let font_name = truncate_string(font.clone().unwrap_or(String::from("5x8")), 9);
-    instructions.push_str(&colour.to_string());
-    instructions.push_str(&format!("f{:=<9}", font_name));
-
-    // Get Character Width
-    let char_width = get_glyph_from_char(config.clone(), &font_name, 'A').await.width();
-    
-    // Center text if x_pos is None
-    let x = match x {
-        Some(x) => x,
-        None => {
-            let board_width = config.read().await.get_boards().get(board_name).unwrap().size.0 as f32;
-            let text_width = char_width as f32*text.len() as f32;
-            let left_margin = ((board_width-text_width)/2f32).floor() as u8;
-            left_margin
-        }
-    };
-
-    let mut pos_x = x as u32;
-    for character in text.chars() {
-        let glyph = get_glyph_from_char(config.clone(), &font_name, character).await;
-        if character == '°' {
-            instructions.push_str(&format!("j{:02}{:02}1====", pos_x, y));
-        } else {
-            instructions.push_str(&format!("t{:02}{:02}{}====", pos_x, y, character));
-        }
-        pos_x+=glyph.width();
     }
+}
 
-    instructions
+fn get_font_name(element: &BoardElement) -> String {
+    truncate_string(element.font.clone().unwrap_or(String::from("5x8")), 9)
+}
+
+/// `t{x:02}{y:02}{char}` padded to a full command. Characters longer than a byte use up some of the padding.
+fn encode_character(x: i32, y: i32, character: char) -> String {
+    if character == '°' {
+        return format!("j{:02}{:02}1====", x, y);
+    }
+    let mut command = format!("t{:02}{:02}{}", x, y, character);
+    while command.len() < COMMAND_LENGTH {
+        command.push('=');
+    }
+    command
 }
 
 fn truncate_string(text: String, length: usize) -> String {
@@ -64,20 +196,3 @@ fn truncate_string(text: String, length: usize) -> String {
     }
     text
 }
-
-async fn get_glyph_from_char(config: ConfigWrapper, font_name: &str, character: char) -> Bitmap {
-    let mut font_path = PathBuf::from(config.read().await.config_path.clone()).parent().expect("Config file has no parent???").to_path_buf();
-    font_path.push(format!("assets/fonts/{}.bdf", font_name));
-    let font = match bdf2::open(font_path.as_path()) {
-        Ok(x) => x,
-        Err(_) => {
-            tracing::info!("Font path: {}", font_path.display());
-            tracing::warn!("Error loading font {}!", font_name);
-            return Bitmap::new(5, 8);
-        }
-    };
-    match font.glyphs().get(&character) {
-        Some(x) => x.map().to_owned(),
-        None => Bitmap::new(5, 8)
-    }
-}
\ No newline at end of file
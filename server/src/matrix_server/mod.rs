@@ -1,2 +1,5 @@
 pub mod server;
-pub mod helpers;
\ No newline at end of file
+pub mod helpers;
+pub mod asset_transfer;
+pub mod virtual_device;
+pub mod transitions;
\ No newline at end of file
@@ -1,8 +1,15 @@
-use std::{io, net::SocketAddr, time::Duration};
+use std::{io, time::Duration};
 
-use tokio::{io::{AsyncBufReadExt, AsyncWriteExt, BufReader}, net::{TcpListener, TcpStream}, time::sleep};
+use tokio::{io::{AsyncBufRead, AsyncBufReadExt, AsyncRead, AsyncWrite, AsyncWriteExt, BufReader}, net::TcpListener, time::{sleep, timeout_at, Instant}};
 
-use crate::{boards::BoardRender, config_manager::ConfigWrapper, state_manager::StateWrapper};
+use shared::transitions::{BoardTransition, TRANSITION_FRAME_INTERVAL};
+
+use crate::{boards::{BoardRender, ElementAnimation}, config_manager::ConfigWrapper, pushed_values::{pushed_variables_used, wait_for_push}, state_manager::StateWrapper};
+
+use super::{asset_transfer::{AssetTracker, INBAND_ASSETS_PROTO_VERSION}, transitions::{transition_frames, without_clear}};
+
+/// Minimum time each board stays on screen, boards with animations stay up until they finish
+const BOARD_DISPLAY_TIME: Duration = Duration::from_secs(5);
 
 pub async fn run_matrix_server(config: ConfigWrapper, state: StateWrapper) -> io::Result<()> {
     let listener = TcpListener::bind("0.0.0.0:12312").await?;
@@ -10,14 +17,16 @@ pub async fn run_matrix_server(config: ConfigWrapper, state: StateWrapper) -> io
         if let Ok((socket, addr)) = listener.accept().await {
             let config = config.clone();
             let state = state.clone();
-            tokio::spawn(process_connection(socket, addr, config, state));
+            tokio::spawn(process_connection(socket, addr.ip().to_string(), addr.to_string(), config, state));
         }
     }
 }
 
-async fn process_connection(mut socket: TcpStream, address: SocketAddr, config: ConfigWrapper, state: StateWrapper) {
-    tracing::info!("New connection from [{}:{}]", address.ip(), address.port());
-    let (reader, mut writer) = socket.split();
+/// Drives a single device over any byte stream. `device_id` is the key of the device's config,
+/// `connection` is only used for logging
+pub(crate) async fn process_connection<S: AsyncRead + AsyncWrite>(socket: S, device_id: String, connection: String, config: ConfigWrapper, state: StateWrapper) {
+    tracing::info!("New connection from [{}]", &connection);
+    let (reader, mut writer) = tokio::io::split(socket);
     let mut reader = BufReader::new(reader);
     // Setup Session
     {
@@ -26,12 +35,12 @@ async fn process_connection(mut socket: TcpStream, address: SocketAddr, config:
             Ok(_res) => {
                 let _ = version_buff.split_off(version_buff.len()-1); // Remove newline at end of message
                 let mut config = config.write().await;
-                if !config.device_configs.contains_key(&address.ip().to_string()) {
+                if !config.device_configs.contains_key(&device_id) {
                     let default_config = config.device_configs.get("default").unwrap().clone();
-                    config.device_configs.insert(address.ip().to_string(), default_config);
+                    config.device_configs.insert(device_id.clone(), default_config);
                     config.save();
                 }
-                let device_config = config.device_configs.get_mut(&address.ip().to_string()).unwrap();
+                let device_config = config.device_configs.get_mut(&device_id).unwrap();
                 if let Ok(version) = version_buff.parse::<u64>() {
                     device_config.proto_version = version;
                 } else {
@@ -39,11 +48,11 @@ async fn process_connection(mut socket: TcpStream, address: SocketAddr, config:
                 }
             },
             Err(_) => {
-                tracing::info!("Connection [{}:{}] did not send version. Falling back to legacy mode.", address.ip(), address.port());
+                tracing::info!("Connection [{}] did not send version. Falling back to legacy mode.", &connection);
                 let mut config = config.write().await;
-                if !config.device_configs.contains_key(&address.ip().to_string()) {
+                if !config.device_configs.contains_key(&device_id) {
                     let default_config = config.device_configs.get("default").unwrap().clone();
-                    config.device_configs.insert(address.ip().to_string(), default_config);
+                    config.device_configs.insert(device_id.clone(), default_config);
                     config.save();
                 }
             }
@@ -52,19 +61,19 @@ async fn process_connection(mut socket: TcpStream, address: SocketAddr, config:
     // Protocol-specific stuff
     {
         let mut config = config.write().await;
-        let device_config = config.device_configs.get_mut(&address.ip().to_string()).unwrap();
+        let device_config = config.device_configs.get_mut(&device_id).unwrap();
         match device_config.proto_version {
             0 => {
                 device_config.size = (64,32);
             }
-            1 => {
+            1 | INBAND_ASSETS_PROTO_VERSION => {
                 let mut size_x = String::new();
                 let res = reader.read_line(&mut size_x).await;
-                if res.is_err() { tracing::error!("[{}] Protocol Version 1: Couldn't receive board size x", address.ip()); panic!() }
+                if res.is_err() { tracing::error!("[{}] Protocol Version 1: Couldn't receive board size x", &device_id); panic!() }
                 let _ = size_x.split_off(size_x.len()-1); // Remove newline at end of message
                 let mut size_y = String::new();
                 let res = reader.read_line(&mut size_y).await;
-                if res.is_err() { tracing::error!("[{}] Protocol Version 1: Couldn't receive board size y", address.ip()); panic!() }
+                if res.is_err() { tracing::error!("[{}] Protocol Version 1: Couldn't receive board size y", &device_id); panic!() }
                 let _ = size_y.split_off(size_y.len()-1); // Remove newline at end of message
                 if let Ok(x) = size_x.parse::<u8>() {
                     if let Ok(y) = size_y.parse::<u8>() {
@@ -79,38 +88,61 @@ async fn process_connection(mut socket: TcpStream, address: SocketAddr, config:
     let mut current_board = 0;
     let mut board_errors = 0;
     let mut skipped_boards = 0;
+    let mut asset_tracker = AssetTracker::new();
+    // Commands that redraw what the device is showing, kept for the next transition when the device has any
+    let mut displayed_commands: Option<String> = None;
+    // Set when a value the board shows was pushed while it was up, so the same board is drawn again
+    let mut redraw = false;
+    // When the board on screen moves on, which redraws for pushed values don't put off
+    let mut board_deadline = Instant::now();
     loop {
+        let redrawing = std::mem::take(&mut redraw);
+        let inband_assets;
+        let animations;
+        let mut board_commands;
+        let transition: Option<(BoardTransition, (u8, u8))>;
+        let keep_displayed;
+        let pushed_variables;
+        let pushed_version;
         {
             let local_config = config.read().await;
             let current_board_name;
             let device_config;
             {
-                device_config = local_config.device_configs.get(&address.ip().to_string()).unwrap();
+                device_config = local_config.device_configs.get(&device_id).unwrap();
                 let board_count = device_config.boards.len();
                 if board_count <= 0 {
-                    tracing::warn!("Connection from [{}:{}] closed because there are no boards in the device config.", address.ip(), address.port());
+                    tracing::warn!("Connection from [{}] closed because there are no boards in the device config.", &connection);
                     return;
                 }
                 if current_board >= board_count {
                     current_board = 0;
                 }
-                current_board_name = local_config.device_configs.get(&address.ip().to_string()).unwrap().boards.get(current_board).unwrap();
+                current_board_name = local_config.device_configs.get(&device_id).unwrap().boards.get(current_board).unwrap();
             }
             let board = local_config.get_boards().get(current_board_name).expect(&format!("Failed to get board ({})", current_board_name));
             if board.size.0 > device_config.size.0 || board.size.1 > device_config.size.1 {
-                tracing::warn!("Board [{}] is too large for device [{}] to display!", current_board_name, address.ip());
+                tracing::warn!("Board [{}] is too large for device [{}] to display!", current_board_name, &device_id);
                 sleep(Duration::from_secs(1)).await;
                 current_board+=1;
                 board_errors+=1;
                 if board_errors >= device_config.boards.len() {
-                    tracing::error!("Board [{}] has no valid board candidates... terminating connection!", address.ip());
+                    tracing::error!("Board [{}] has no valid board candidates... terminating connection!", &device_id);
                     let _ = writer.shutdown();
                     return;
                 }
                 continue;
             }
             board_errors = 0;
-            let rendered_board = board.render(device_config, config.clone(), state.clone()).await;
+            pushed_variables = pushed_variables_used(board, &local_config.board_variables);
+            pushed_version = state.lock().await.pushed_values.version();
+            let rendered_board = match board.render(device_config, config.clone(), state.clone(), &chrono::Local::now()).await {
+                Ok(x) => x,
+                Err(e) => {
+                    tracing::error!("[{}] {:#}", &device_id, e);
+                    None
+                }
+            };
             if rendered_board.is_none() {
                 current_board+=1;
                 skipped_boards += 1;
@@ -120,12 +152,116 @@ async fn process_connection(mut socket: TcpStream, address: SocketAddr, config:
                 continue;
             }
             let rendered_board = rendered_board.unwrap();
-            if writer.write_all(rendered_board.as_bytes()).await.is_err() {
-                tracing::info!("Connection from [{}:{}] closed.", address.ip(), address.port());
+            inband_assets = device_config.proto_version >= INBAND_ASSETS_PROTO_VERSION;
+            animations = rendered_board.animations;
+            board_commands = rendered_board.commands;
+            // Legacy devices always switch straight over
+            keep_displayed = device_config.proto_version > 0 && device_config.has_transitions();
+            let board_transition = device_config.transition_to(current_board_name);
+            transition = (!redrawing && keep_displayed && board_transition.is_active()).then_some((board_transition, device_config.size));
+            current_board+=1;
+        }
+        // Played outside of the config lock, which would otherwise be held for the whole transition
+        if let (Some((transition, size)), Some(outgoing)) = (transition, displayed_commands.take()) {
+            let frames = transition_frames(transition, &outgoing, &board_commands, size, config.clone(), state.clone()).await;
+            for frame in frames {
+                if writer.write_all(frame.as_bytes()).await.is_err() {
+                    tracing::info!("Connection from [{}] closed.", &connection);
+                    return;
+                }
+                if inband_assets {
+                    wait_for_device_messages(&mut reader, &mut asset_tracker, &device_id, TRANSITION_FRAME_INTERVAL).await;
+                } else {
+                    sleep(TRANSITION_FRAME_INTERVAL).await;
+                }
+            }
+            board_commands = without_clear(&board_commands);
+        }
+        let rendered_board = if inband_assets {
+            asset_tracker.inject_assets(&board_commands, config.clone(), state.clone()).await
+        } else {
+            board_commands.clone().into_bytes()
+        };
+        if writer.write_all(&rendered_board).await.is_err() {
+            tracing::info!("Connection from [{}] closed.", &connection);
+            return;
+        }
+        let board_started = Instant::now();
+        if !redrawing {
+            board_deadline = board_started + BOARD_DISPLAY_TIME;
+        }
+        // Animations stop early if the connection goes away, the next write ends the session
+        let mut pending_animations: Vec<&ElementAnimation> = animations.iter().collect();
+        let mut last_frame = String::new();
+        while !pending_animations.is_empty() {
+            let frame_interval = pending_animations.iter().map(|x| x.frame_interval()).min().unwrap_or(BOARD_DISPLAY_TIME);
+            if inband_assets {
+                wait_for_device_messages(&mut reader, &mut asset_tracker, &device_id, frame_interval).await;
+            } else {
+                sleep(frame_interval).await;
+            }
+            let elapsed = board_started.elapsed();
+            let mut frame = String::new();
+            for animation in &pending_animations {
+                frame.push_str(&animation.frame(elapsed.min(animation.duration())));
+            }
+            // Each animation gets a last frame at its end position before it stops
+            pending_animations.retain(|x| elapsed < x.duration());
+            // Nothing moves while a marquee is paused
+            if frame == last_frame {
+                continue;
+            }
+            last_frame = frame.clone();
+            let frame = if inband_assets {
+                asset_tracker.inject_assets(&frame, config.clone(), state.clone()).await
+            } else {
+                frame.into_bytes()
+            };
+            if writer.write_all(&frame).await.is_err() {
+                break;
+            }
+        }
+        if keep_displayed {
+            // Every animation has finished, so the device shows the board with each one at its last frame
+            let mut displayed = board_commands;
+            for animation in &animations {
+                displayed.push_str(&animation.frame(animation.duration()));
+            }
+            displayed_commands = Some(displayed);
+        }
+        let remaining = board_deadline.saturating_duration_since(Instant::now());
+        let wait = async {
+            if inband_assets {
+                wait_for_device_messages(&mut reader, &mut asset_tracker, &device_id, remaining).await;
+            } else {
+                sleep(remaining).await;
+            }
+        };
+        // A board whose time is up moves on even if a value was pushed at the same moment
+        redraw = tokio::select! {
+            biased;
+            _ = wait => false,
+            _ = wait_for_push(&pushed_variables, pushed_version, state.clone()) => true,
+        };
+        if redraw {
+            // Drawn again, animations and all, for whatever is left of its time
+            current_board -= 1;
+        }
+    }
+}
+
+/// Sleeps until the next render while handling asset acknowledgements sent back by the device
+async fn wait_for_device_messages<R: AsyncBufRead + Unpin>(reader: &mut R, asset_tracker: &mut AssetTracker, device_id: &str, duration: Duration) {
+    let deadline = Instant::now() + duration;
+    loop {
+        match timeout_at(deadline, reader.read_until(b'\n', asset_tracker.message_buffer())).await {
+            Ok(Ok(0)) | Ok(Err(_)) => {
+                // Connection is closed or broken, the next write will end the session
+                tokio::time::sleep_until(deadline).await;
                 return;
             }
-            current_board+=1;
+            Ok(Ok(_)) => asset_tracker.handle_message(device_id),
+            Err(_) => return,
         }
-        sleep(Duration::from_secs(5)).await;
     }
 }
\ No newline at end of file
@@ -0,0 +1,90 @@
+use shared::{
+    canvas::MatrixCanvas,
+    transitions::{transition_frame, BoardTransition},
+};
+
+use crate::{config_manager::ConfigWrapper, preview::load_assets, state_manager::StateWrapper};
+
+/// Commands for every frame of a transition from `outgoing` (the commands that drew what the device is showing)
+/// to `incoming`. Frames only redraw the pixels that changed, the last one leaves the device showing `incoming`.
+pub(crate) async fn transition_frames(
+    transition: BoardTransition,
+    outgoing: &str,
+    incoming: &str,
+    size: (u8, u8),
+    config: ConfigWrapper,
+    state: StateWrapper,
+) -> Vec<String> {
+    let from = draw_commands(outgoing, size, config.clone(), state.clone()).await;
+    let to = draw_commands(incoming, size, config, state).await;
+    let (width, height) = (from.width, from.height);
+    let mut shown = quantize(&from.pixels);
+    let frame_count = transition.frame_count();
+    let mut frames = Vec::with_capacity(frame_count as usize);
+    for frame in 1..=frame_count {
+        let progress = frame as f64 / frame_count as f64;
+        let pixels = quantize(&transition_frame(&from.pixels, &to.pixels, width, height, transition.effect, progress));
+        frames.push(pixel_changes(&shown, &pixels, width));
+        shown = pixels;
+    }
+    frames
+}
+
+/// The board's commands without its leading clear. Once a transition has finished the device already shows the
+/// board, so redrawing it over the top avoids a blank frame.
+pub(crate) fn without_clear(commands: &str) -> String {
+    let commands: Vec<u8> = commands
+        .as_bytes()
+        .chunks(10)
+        .filter(|command| command.first() != Some(&b'x'))
+        .flatten()
+        .copied()
+        .collect();
+    String::from_utf8(commands).unwrap_or_default()
+}
+
+async fn draw_commands(commands: &str, size: (u8, u8), config: ConfigWrapper, state: StateWrapper) -> MatrixCanvas {
+    let assets = load_assets(commands.as_bytes(), config, state).await;
+    let mut canvas = MatrixCanvas::new(size);
+    canvas.interpret_all(commands.as_bytes(), &assets);
+    canvas
+}
+
+/// Devices take 4 bits per channel, so drop the rest to only send pixels that visibly change
+fn quantize(pixels: &[[u8; 3]]) -> Vec<[u8; 3]> {
+    pixels.iter().map(|pixel| pixel.map(|channel| channel & 0xF0)).collect()
+}
+
+/// Redraws each pixel that differs between two frames, as `l` lines across runs of the same colour and `q` for the rest
+fn pixel_changes(previous: &[[u8; 3]], next: &[[u8; 3]], width: u32) -> String {
+    let mut instructions = String::new();
+    let mut current_colour = None;
+    let width = width as usize;
+    // Positions are sent as two digits
+    for (y, row) in next.chunks(width).enumerate().take(100) {
+        let mut x = 0;
+        while x < row.len().min(100) {
+            let colour = row[x];
+            if previous[y * width + x] == colour {
+                x += 1;
+                continue;
+            }
+            let mut end = x;
+            while end + 1 < row.len().min(100) && row[end + 1] == colour && previous[y * width + end + 1] != colour {
+                end += 1;
+            }
+            let [r, g, b] = colour.map(|channel| channel >> 4);
+            if end == x {
+                instructions.push_str(&format!("q{:02}{:02}{:X}{:X}{:X}==", x, y, r, g, b));
+            } else {
+                if current_colour != Some(colour) {
+                    instructions.push_str(&format!("c{:X}{:X}{:X}======", r, g, b));
+                    current_colour = Some(colour);
+                }
+                instructions.push_str(&format!("l{:02}{:02}{:02}{:02}=", x, y, end, y));
+            }
+            x = end + 1;
+        }
+    }
+    instructions
+}
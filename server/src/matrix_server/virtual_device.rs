@@ -0,0 +1,61 @@
+use axum::extract::ws::{Message, WebSocket};
+use futures_util::{SinkExt, StreamExt};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+use crate::{config_manager::ConfigWrapper, state_manager::StateWrapper};
+
+use super::server::process_connection;
+
+/// Prefix for the device config keys of browser devices, physical devices are keyed by IP
+pub(crate) const VIRTUAL_DEVICE_PREFIX: &str = "virtual:";
+
+const BRIDGE_BUFFER_SIZE: usize = 64 * 1024;
+
+/// Virtual device names end up in device config keys, so keep them simple
+pub(crate) fn is_valid_virtual_device_name(name: &str) -> bool {
+    !name.is_empty() && name.len() <= 32 && name.chars().all(|x| x.is_ascii_alphanumeric() || x == '-' || x == '_')
+}
+
+/// Bridges a WebSocket onto the same connection handler TCP devices use. The browser sends the usual
+/// handshake lines and acks as WebSocket messages and receives the raw command stream as binary messages.
+pub(crate) async fn run_virtual_device(socket: WebSocket, name: String, config: ConfigWrapper, state: StateWrapper) {
+    let device_id = format!("{}{}", VIRTUAL_DEVICE_PREFIX, name);
+    let connection = format!("virtual device {}", &name);
+    let (device_stream, server_stream) = tokio::io::duplex(BRIDGE_BUFFER_SIZE);
+    let (mut device_reader, mut device_writer) = tokio::io::split(device_stream);
+    let (mut ws_sender, mut ws_receiver) = socket.split();
+    let session = tokio::spawn(process_connection(server_stream, device_id, connection, config, state));
+    let incoming = async {
+        while let Some(Ok(message)) = ws_receiver.next().await {
+            let written = match message {
+                Message::Text(text) => device_writer.write_all(text.as_bytes()).await,
+                Message::Binary(data) => device_writer.write_all(&data).await,
+                Message::Close(_) => break,
+                _ => Ok(()),
+            };
+            if written.is_err() {
+                break;
+            }
+        }
+    };
+    let outgoing = async {
+        let mut buffer = vec![0; BRIDGE_BUFFER_SIZE];
+        loop {
+            match device_reader.read(&mut buffer).await {
+                Ok(0) | Err(_) => break,
+                Ok(length) => {
+                    let message = Message::Binary(buffer[..length].to_vec().into());
+                    if ws_sender.send(message).await.is_err() {
+                        break;
+                    }
+                }
+            }
+        }
+        let _ = ws_sender.close().await;
+    };
+    tokio::select! {
+        _ = incoming => {},
+        _ = outgoing => {},
+    }
+    session.abort();
+}
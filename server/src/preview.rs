@@ -0,0 +1,95 @@
+use std::io::Cursor;
+
+use anyhow::Error;
+use chrono::{DateTime, Local};
+use image::{ImageFormat, Rgb, RgbImage};
+use shared::{
+    boards::BoardDefinition,
+    canvas::{LoadedAssets, MatrixCanvas, PixelImage},
+    device_config::DeviceConfig,
+};
+
+use crate::{
+    boards::BoardRender,
+    config_manager::ConfigWrapper,
+    font_manager::get_font,
+    image_conversion::ImageConversion,
+    image_manager::get_image_data,
+    state_manager::StateWrapper,
+};
+
+const PREVIEW_SCALE: u32 = 8;
+const PREVIEW_PIXEL_SPACING: u32 = 1;
+const PREVIEW_SPACING_COLOUR: Rgb<u8> = Rgb([24, 26, 27]);
+
+/// Renders a board the way a device would display it at `now` and encodes it as a PNG.
+/// Returns `None` if the device would skip the board at that time.
+pub(crate) async fn render_board_preview(
+    board: &BoardDefinition,
+    device_config: &DeviceConfig,
+    now: &DateTime<Local>,
+    config: ConfigWrapper,
+    state: StateWrapper,
+) -> Result<Option<Vec<u8>>, Error> {
+    let rendered_board = match board.render(device_config, config.clone(), state.clone(), now).await? {
+        Some(x) => x,
+        None => return Ok(None),
+    };
+    // Animated elements are previewed as their first frame
+    let rendered_board = rendered_board.commands;
+    let assets = load_assets(rendered_board.as_bytes(), config, state).await;
+    let mut canvas = MatrixCanvas::new(device_config.size);
+    canvas.interpret_all(rendered_board.as_bytes(), &assets);
+    Ok(Some(encode_png(&canvas)?))
+}
+
+/// Loads every font and image a rendered board refers to
+pub(crate) async fn load_assets(rendered_board: &[u8], config: ConfigWrapper, state: StateWrapper) -> LoadedAssets {
+    let mut assets = LoadedAssets::default();
+    let (fonts, images) = MatrixCanvas::referenced_assets(rendered_board);
+    for font_name in fonts {
+        match get_font(config.clone(), state.clone(), &font_name).await {
+            Ok(font) => {
+                assets.fonts.insert(font_name, (*font).clone());
+            }
+            Err(e) => tracing::warn!("{}", e),
+        }
+    }
+    for image_hash in images {
+        let Some(image_data) = get_image_data(config.clone(), state.clone(), &image_hash, ImageConversion::default()).await else {
+            continue;
+        };
+        match image::load_from_memory(&image_data) {
+            Ok(image) => {
+                let image = image.to_rgb8();
+                assets.images.insert(image_hash, PixelImage {
+                    width: image.width(),
+                    height: image.height(),
+                    pixels: image.pixels().map(|pixel| pixel.0).collect(),
+                });
+            }
+            Err(e) => tracing::warn!("Error loading image ({}):\n{}", image_hash, e),
+        }
+    }
+    assets
+}
+
+/// Scales the canvas up with a gap between each LED and encodes it as a PNG
+pub(crate) fn encode_png(canvas: &MatrixCanvas) -> Result<Vec<u8>, Error> {
+    if canvas.width == 0 || canvas.height == 0 {
+        return Err(anyhow::anyhow!("Cannot preview a {}x{} device", canvas.width, canvas.height));
+    }
+    let cell = PREVIEW_SCALE + PREVIEW_PIXEL_SPACING;
+    let width = canvas.width * cell - PREVIEW_PIXEL_SPACING;
+    let height = canvas.height * cell - PREVIEW_PIXEL_SPACING;
+    let pixels = canvas.displayed_pixels();
+    let image = RgbImage::from_fn(width, height, |x, y| {
+        if x % cell >= PREVIEW_SCALE || y % cell >= PREVIEW_SCALE {
+            return PREVIEW_SPACING_COLOUR;
+        }
+        Rgb(pixels[((y / cell) * canvas.width + x / cell) as usize])
+    });
+    let mut png = Cursor::new(Vec::new());
+    image.write_to(&mut png, ImageFormat::Png)?;
+    Ok(png.into_inner())
+}
@@ -0,0 +1,97 @@
+use std::{collections::{HashMap, HashSet}, sync::Arc};
+
+use shared::{board_variables::{BoardVariable, BoardVariables}, boards::BoardDefinition, expressions::variable_dependencies, templates::Template};
+use tokio::sync::Notify;
+
+use crate::state_manager::StateWrapper;
+
+/// Longest value that can be pushed to a variable, in bytes
+pub(crate) const MAX_PUSHED_VALUE_BYTES: usize = 64 * 1024;
+
+#[derive(Debug)]
+struct PushedValue {
+    value: String,
+    time_entered: i64,
+    /// `PushedValues::version` once it was pushed
+    version: u64,
+}
+
+/// Values pushed to `Pushed` variables through the API, by variable name
+#[derive(Default, Debug)]
+pub(crate) struct PushedValues {
+    values: HashMap<String, PushedValue>,
+    /// Counts pushes, so devices can tell whether anything they show was pushed since they drew it
+    version: u64,
+    /// Woken whenever a value is pushed, so devices showing the variable can redraw straight away
+    updated: Arc<Notify>,
+}
+
+impl PushedValues {
+    pub(crate) fn set(&mut self, variable_name: &str, value: String, now: i64) {
+        self.version += 1;
+        self.values.insert(variable_name.to_string(), PushedValue { value, time_entered: now, version: self.version });
+        self.updated.notify_waiters();
+    }
+
+    /// The pushed value, unless there isn't one or it's older than `ttl` seconds
+    pub(crate) fn get(&self, variable_name: &str, ttl: u32, now: i64) -> Option<&str> {
+        let pushed = self.values.get(variable_name)?;
+        if ttl > 0 && now - pushed.time_entered >= ttl as i64 {
+            return None;
+        }
+        Some(&pushed.value)
+    }
+
+    pub(crate) fn version(&self) -> u64 {
+        self.version
+    }
+
+    /// Whether any of the variables has had a value pushed after `version`
+    fn pushed_since(&self, variable_names: &HashSet<String>, version: u64) -> bool {
+        variable_names.iter().any(|name| self.values.get(name).is_some_and(|pushed| pushed.version > version))
+    }
+}
+
+/// Returns once a value is pushed to one of the variables after `version`, which never happens when there are none
+pub(crate) async fn wait_for_push(variable_names: &HashSet<String>, version: u64, state: StateWrapper) {
+    if variable_names.is_empty() {
+        return std::future::pending().await;
+    }
+    let updated = state.lock().await.pushed_values.updated.clone();
+    loop {
+        // Listening before checking, so a push in between isn't missed
+        let notified = updated.notified();
+        tokio::pin!(notified);
+        notified.as_mut().enable();
+        if state.lock().await.pushed_values.pushed_since(variable_names, version) {
+            return;
+        }
+        notified.await;
+    }
+}
+
+/// Pushed variables whose values end up on the board, directly or through expressions and countdowns
+pub(crate) fn pushed_variables_used(board: &BoardDefinition, variables: &BoardVariables) -> HashSet<String> {
+    let mut pending: Vec<String> = board.board_elements.iter()
+        .filter_map(|element| element.value.template())
+        .flat_map(|text| {
+            let template = Template::parse(text, |name| variables.contains_key(name));
+            template.variables().map(String::from).collect::<Vec<_>>()
+        })
+        .collect();
+    let mut visited = HashSet::new();
+    let mut pushed = HashSet::new();
+    while let Some(name) = pending.pop() {
+        if !visited.insert(name.clone()) {
+            continue;
+        }
+        match variables.get(&name) {
+            Some(BoardVariable::Pushed(_)) => {
+                pushed.insert(name);
+            }
+            Some(variable) => pending.extend(variable_dependencies(variable)),
+            None => {}
+        }
+    }
+    pushed
+}
@@ -1,14 +1,20 @@
 use std::{collections::HashMap, sync::Arc};
 use tokio::sync::Mutex;
 
-use crate::image_manager::HashedImages;
+use crate::{animation_manager::AnimationCache, font_manager::FontCache, image_conversion::ImageConversionCache, image_manager::HashedImages, pushed_values::PushedValues, variable_history::HistoryStore, variable_refresher::RefreshSchedule};
 
 pub(crate) type StateWrapper = Arc<Mutex<State>>;
 
     #[derive(Default, Debug)]
 pub(crate) struct State {
     pub(crate) board_variable_values: HashMap<String, VariableCache>,
+    pub(crate) variable_refreshes: RefreshSchedule,
+    pub(crate) pushed_values: PushedValues,
     pub(crate) image_hashes: HashedImages,
+    pub(crate) font_cache: FontCache,
+    pub(crate) variable_history: HistoryStore,
+    pub(crate) animation_cache: AnimationCache,
+    pub(crate) image_conversions: ImageConversionCache,
 }
 
 impl State {
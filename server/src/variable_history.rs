@@ -0,0 +1,102 @@
+use std::{collections::HashMap, path::PathBuf, time::Duration};
+
+use shared::{boards::BoardElementValue, graphs::{VariableHistory, MAX_GRAPH_HOURS}, indicators::parse_number};
+use tokio::fs;
+
+use crate::{board_variables::EvaluateBoardVariable, config_manager::{Boards, ConfigWrapper}, state_manager::StateWrapper};
+
+/// How often graphed variables are sampled
+const SAMPLE_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Recent values of every variable shown on a graph, saved to `history.json` next to the config
+#[derive(Default, Debug)]
+pub(crate) struct HistoryStore {
+    series: HashMap<String, VariableHistory>,
+}
+impl HistoryStore {
+    pub(crate) fn get(&self, variable_name: &str) -> Option<&VariableHistory> {
+        self.series.get(variable_name)
+    }
+
+    /// Adds a sample and drops any older than `retention` seconds
+    fn record(&mut self, variable_name: &str, timestamp: i64, value: f64, retention: i64) {
+        let series = self.series.entry(variable_name.to_string()).or_default();
+        series.push((timestamp, value));
+        series.retain(|(sample_time, _)| *sample_time > timestamp - retention);
+    }
+
+    /// Drops samples older than the longest history kept, forgetting variables that are no longer graphed once they run out
+    fn expire(&mut self, now: i64) {
+        for series in self.series.values_mut() {
+            series.retain(|(sample_time, _)| *sample_time > now - MAX_GRAPH_HOURS as i64 * 3600);
+        }
+        self.series.retain(|_, series| !series.is_empty());
+    }
+}
+
+/// Samples every variable drawn by a graph element once a minute, keeping as much history as the graphs show
+pub(crate) async fn run_history_sampler(config: ConfigWrapper, state: StateWrapper) {
+    let history_path = get_history_path(config.clone()).await;
+    match fs::read(&history_path).await {
+        Ok(data) => match serde_json::from_slice::<HashMap<String, VariableHistory>>(&data) {
+            Ok(series) => state.lock().await.variable_history = HistoryStore { series },
+            Err(e) => tracing::warn!("Unable to read variable history from '{}': {}", history_path.display(), e),
+        },
+        Err(_) => tracing::info!("No variable history found at '{}', starting fresh", history_path.display()),
+    }
+    let mut interval = tokio::time::interval(SAMPLE_INTERVAL);
+    loop {
+        interval.tick().await;
+        let now = chrono::Local::now();
+        let mut samples = Vec::new();
+        {
+            let config = config.read().await;
+            for (variable_name, hours) in graphed_variables(config.get_boards()) {
+                let Some(variable) = config.board_variables.get(&variable_name) else {
+                    continue;
+                };
+                let key_match = format!("__{}__", variable_name);
+                let value = variable.eval_variable(&key_match, &config, state.clone(), &now).await;
+                match parse_number(&value) {
+                    Some(number) => samples.push((variable_name, hours.min(MAX_GRAPH_HOURS as i64) * 3600, number)),
+                    None => tracing::warn!("Variable '{}' is graphed but its value ({}) is not a number", variable_name, value),
+                }
+            }
+        }
+        let serialized_history = {
+            let mut state = state.lock().await;
+            for (variable_name, retention, number) in samples {
+                state.variable_history.record(&variable_name, now.timestamp(), number, retention);
+            }
+            state.variable_history.expire(now.timestamp());
+            serde_json::to_vec(&state.variable_history.series)
+        };
+        match serialized_history {
+            Ok(data) => {
+                if let Err(e) = fs::write(&history_path, data).await {
+                    tracing::warn!("Unable to save variable history to '{}': {}", history_path.display(), e);
+                }
+            }
+            Err(e) => tracing::warn!("Unable to serialize variable history: {}", e),
+        }
+    }
+}
+
+/// Names of variables drawn by graphs, with the most hours of history any of their graphs show
+fn graphed_variables(boards: &Boards) -> HashMap<String, i64> {
+    let mut variables = HashMap::new();
+    for board in boards.values() {
+        for element in &board.board_elements {
+            if let BoardElementValue::Graph(variable_name, _, _, settings) = &element.value {
+                let hours = variables.entry(variable_name.clone()).or_insert(0);
+                *hours = (*hours).max(settings.hours as i64);
+            }
+        }
+    }
+    variables
+}
+
+async fn get_history_path(config: ConfigWrapper) -> PathBuf {
+    let config = config.read().await;
+    PathBuf::from(&config.config_path).with_file_name("history.json")
+}
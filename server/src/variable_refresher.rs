@@ -0,0 +1,313 @@
+use std::{collections::HashMap, sync::LazyLock, time::Duration};
+
+use chrono::{DateTime, Local};
+use reqwest::header::{HeaderMap, CACHE_CONTROL, RETRY_AFTER};
+use shared::board_variables::{BoardVariable, HttpAuth, HttpMethod, RequestBody, UrlRequest};
+use tokio::{sync::watch, time::Instant};
+
+use crate::{
+    board_variables::fill_template,
+    config_manager::{Config, ConfigWrapper, USER_AGENT},
+    state_manager::{StateWrapper, VariableCache},
+};
+
+/// How often the refresher looks for expired variables
+const REFRESH_TICK: Duration = Duration::from_secs(1);
+/// Shortest time between two fetches of the same variable, whatever its expiry
+const MIN_REFRESH_SECS: i64 = 5;
+/// Longest wait before retrying a failed request, the last good value is shown until then
+const FAILED_RETRY_SECS: i64 = 30;
+/// Wait before the first retry of a failed request, doubling for each one after
+const RETRY_BACKOFF: Duration = Duration::from_secs(1);
+/// Longest a `Retry-After` or `max-age` from a server can put a variable's next refresh off by
+const MAX_SERVER_DELAY_SECS: i64 = 24 * 3600;
+/// Time between requests to the same host
+const HOST_REQUEST_INTERVAL: Duration = Duration::from_secs(1);
+/// Fetches are skipped rather than queued once a host's queue is this long, or while it has asked us to back off
+const MAX_HOST_QUEUE: Duration = Duration::from_secs(10);
+/// Variables no board has read for this long stop being fetched until they are read again
+const IDLE_SECS: i64 = 600;
+
+static CLIENT: LazyLock<reqwest::Client> = LazyLock::new(|| {
+    reqwest::Client::builder()
+        .user_agent(USER_AGENT)
+        .build()
+        .expect("Failed to setup the HTTP client")
+});
+
+tokio::task_local! {
+    /// Set while a request's templates are being filled in. Variables read then never wait on their own
+    /// fetch, so requests whose templates use each other can't wait on each other forever.
+    static FILLING_TEMPLATES: ();
+}
+
+/// When URL variables were read, when they are next due and which are being fetched, all by var_id
+#[derive(Default, Debug)]
+pub(crate) struct RefreshSchedule {
+    last_read: HashMap<String, i64>,
+    next_refresh: HashMap<String, i64>,
+    /// Fetches under way, their receivers see `true` once the result is in the cache
+    in_flight: HashMap<String, watch::Receiver<bool>>,
+    /// Earliest time each host can be sent its next request
+    host_slots: HashMap<String, Instant>,
+}
+
+pub(crate) enum Refresh {
+    /// Another caller is already fetching the variable
+    Joined(watch::Receiver<bool>),
+    /// The caller has to fetch the variable with `start_fetch`, anyone else asking in the meantime joins in
+    Start(PendingFetch),
+    /// The variable was fetched recently, failed recently, or its host is rate limited
+    NotDue,
+}
+
+/// A fetch that has been given a place in its host's queue but not started yet
+pub(crate) struct PendingFetch {
+    var_id: String,
+    host: String,
+    slot: Instant,
+    done: watch::Sender<bool>,
+}
+
+impl RefreshSchedule {
+    /// Notes that a render wants this variable, so the refresher keeps it up to date
+    pub(crate) fn mark_read(&mut self, var_id: &str, now: i64) {
+        self.last_read.insert(var_id.to_string(), now);
+    }
+
+    /// Claims the fetch of a URL variable if it is due, or joins the fetch already under way
+    pub(crate) fn refresh(&mut self, request: &UrlRequest, now: i64) -> Refresh {
+        let var_id = request.var_id.to_string();
+        if let Some(fetch) = self.in_flight.get(&var_id) {
+            return Refresh::Joined(fetch.clone());
+        }
+        if self.next_refresh.get(&var_id).is_some_and(|next_refresh| *next_refresh > now) {
+            return Refresh::NotDue;
+        }
+        let host = reqwest::Url::parse(&request.url).ok().and_then(|url| url.host_str().map(|host| host.to_string())).unwrap_or_default();
+        let start = Instant::now();
+        let slot = self.host_slots.get(&host).copied().unwrap_or(start).max(start);
+        if slot > start + MAX_HOST_QUEUE {
+            return Refresh::NotDue;
+        }
+        self.host_slots.insert(host.clone(), slot + HOST_REQUEST_INTERVAL);
+        let (done, fetch) = watch::channel(false);
+        self.in_flight.insert(var_id.clone(), fetch);
+        Refresh::Start(PendingFetch { var_id, host, slot, done })
+    }
+
+    fn is_due(&self, var_id: &str, now: i64) -> bool {
+        let recently_read = self.last_read.get(var_id).is_some_and(|last_read| *last_read > now - IDLE_SECS);
+        let expired = self.next_refresh.get(var_id).is_none_or(|next_refresh| *next_refresh <= now);
+        recently_read && expired && !self.in_flight.contains_key(var_id)
+    }
+}
+
+impl PendingFetch {
+    pub(crate) fn subscribe(&self) -> watch::Receiver<bool> {
+        self.done.subscribe()
+    }
+}
+
+/// Whether a variable read right now may wait for its first fetch
+pub(crate) fn can_wait_for_fetch() -> bool {
+    FILLING_TEMPLATES.try_with(|_| ()).is_err()
+}
+
+/// Longest a fetch of the request can take, counting every retry
+pub(crate) fn longest_fetch(request: &UrlRequest) -> Duration {
+    let retries = request.retries.min(shared::board_variables::MAX_REQUEST_RETRIES) as u32;
+    Duration::from_secs(request.timeout as u64) * (retries + 1) + RETRY_BACKOFF * (2u32.pow(retries) - 1) + MAX_HOST_QUEUE
+}
+
+/// Fills in the request's templates from other variables, then fetches it in the background
+pub(crate) async fn start_fetch(pending: PendingFetch, request: &UrlRequest, variable_name: &str, config: &Config, state: StateWrapper, now: &DateTime<Local>) {
+    let request = FILLING_TEMPLATES.scope((), fill_request(request, config, state.clone(), now)).await;
+    tokio::spawn(fetch_variable(state, pending, variable_name.to_string(), request));
+}
+
+/// Fetches URL variables in the background as they expire, so rendering only waits on a variable that has never
+/// been fetched. A variable keeps its last value while it is being refetched or while its requests fail.
+pub(crate) async fn run_variable_refresher(config: ConfigWrapper, state: StateWrapper) {
+    let mut interval = tokio::time::interval(REFRESH_TICK);
+    interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+    loop {
+        interval.tick().await;
+        let now = chrono::Local::now();
+        let config = config.read().await;
+        for (variable_name, variable) in &config.board_variables {
+            let BoardVariable::URL(request) = variable else {
+                continue;
+            };
+            let refresh = {
+                let mut state = state.lock().await;
+                if !state.variable_refreshes.is_due(&request.var_id.to_string(), now.timestamp()) {
+                    continue;
+                }
+                state.variable_refreshes.refresh(request, now.timestamp())
+            };
+            if let Refresh::Start(pending) = refresh {
+                start_fetch(pending, request, variable_name, &config, state.clone(), &now).await;
+            }
+        }
+    }
+}
+
+/// A copy of the request with `__variable__` placeholders in its URL, query and body swapped for their values
+async fn fill_request(request: &UrlRequest, config: &Config, state: StateWrapper, now: &DateTime<Local>) -> UrlRequest {
+    let mut request = request.clone();
+    request.url = fill_template(&request.url, config, state.clone(), now).await;
+    for (_, value) in request.query.iter_mut() {
+        *value = fill_template(value, config, state.clone(), now).await;
+    }
+    match &mut request.body {
+        RequestBody::None => {}
+        RequestBody::Json(body) | RequestBody::Text(body) => *body = fill_template(body, config, state.clone(), now).await,
+        RequestBody::GraphQL { variables, .. } => *variables = fill_template(variables, config, state.clone(), now).await,
+    }
+    request
+}
+
+async fn fetch_variable(state: StateWrapper, pending: PendingFetch, variable_name: String, request: UrlRequest) {
+    tokio::time::sleep_until(pending.slot).await;
+    tracing::info!("Updating Variable Cache for URL variable '{}'", &variable_name);
+    let retries = request.retries.min(shared::board_variables::MAX_REQUEST_RETRIES) as u32;
+    let mut attempt = 0;
+    let response = loop {
+        let response = match build_request(&request) {
+            Ok(req) => req.send().await,
+            Err(e) => {
+                tracing::warn!("Unable to build request for URL variable '{}':\n{}", &variable_name, e);
+                break None;
+            }
+        };
+        let retryable = match &response {
+            Ok(resp) => (resp.status().is_server_error() || resp.status() == reqwest::StatusCode::TOO_MANY_REQUESTS) && !resp.headers().contains_key(RETRY_AFTER),
+            Err(e) => e.is_timeout() || e.is_connect() || e.is_request(),
+        };
+        if !retryable || attempt >= retries {
+            break Some(response);
+        }
+        match &response {
+            Ok(resp) => tracing::warn!("Error requesting url ({}), retrying:\n{}", &request.url, resp.status()),
+            Err(e) => tracing::warn!("Error requesting url ({}), retrying:\n{}", &request.url, e),
+        }
+        tokio::time::sleep(RETRY_BACKOFF * 2u32.pow(attempt)).await;
+        attempt += 1;
+    };
+    let now = chrono::Utc::now().timestamp();
+    let expiry = request.expiry.max(MIN_REFRESH_SECS);
+    let failed_retry = now + expiry.min(FAILED_RETRY_SECS);
+    let mut host_delay = None;
+    let (value, next_refresh) = match response {
+        Some(Ok(resp)) if resp.status().is_success() || request.cache_errors => {
+            if !resp.status().is_success() {
+                tracing::warn!("Error requesting url ({}), showing the response anyway:\n{}", &request.url, resp.status());
+            }
+            let delay = match resp.status().is_success() {
+                true => max_age(resp.headers()),
+                false => retry_after(resp.headers(), now),
+            };
+            match resp.text().await {
+                Ok(value) => (Some(value), now + expiry.max(delay.unwrap_or_default())),
+                Err(e) => {
+                    tracing::warn!("Error requesting url ({}):\n{}", &request.url, e);
+                    (None, failed_retry)
+                }
+            }
+        }
+        Some(Ok(resp)) => {
+            tracing::warn!("Error requesting url ({}):\n{}", &request.url, resp.status());
+            match retry_after(resp.headers(), now) {
+                Some(delay) => {
+                    host_delay = Some(delay);
+                    (None, now + delay.max(MIN_REFRESH_SECS))
+                }
+                None => (None, failed_retry),
+            }
+        }
+        Some(Err(e)) => {
+            tracing::warn!("Error requesting url ({}):\n{}", &request.url, e);
+            (None, failed_retry)
+        }
+        None => (None, failed_retry),
+    };
+    let mut state = state.lock().await;
+    if let Some(value) = value {
+        state.board_variable_values.insert(pending.var_id.clone(), VariableCache { time_entered: now, value });
+    }
+    let schedule = &mut state.variable_refreshes;
+    if let Some(delay) = host_delay {
+        // Usually a rate limit, which covers everything else fetched from the host too
+        let slot = Instant::now() + Duration::from_secs(delay as u64);
+        let host_slot = schedule.host_slots.entry(pending.host.clone()).or_insert(slot);
+        *host_slot = (*host_slot).max(slot);
+    }
+    schedule.next_refresh.insert(pending.var_id.clone(), next_refresh);
+    schedule.in_flight.remove(&pending.var_id);
+    let _ = pending.done.send(true);
+}
+
+fn build_request(request: &UrlRequest) -> Result<reqwest::RequestBuilder, serde_json::Error> {
+    let method = match (&request.body, request.method) {
+        (RequestBody::GraphQL { .. }, _) | (_, HttpMethod::Post) => reqwest::Method::POST,
+        (_, HttpMethod::Put) => reqwest::Method::PUT,
+        (_, HttpMethod::Get) => reqwest::Method::GET,
+    };
+    let mut req = CLIENT
+        .request(method, &request.url)
+        .timeout(Duration::from_secs(request.timeout.max(1) as u64));
+    if !request.query.is_empty() {
+        req = req.query(&request.query);
+    }
+    match &request.auth {
+        HttpAuth::None => {}
+        HttpAuth::Basic { username, password } => req = req.basic_auth(username, Some(password)),
+        HttpAuth::Bearer(token) => req = req.bearer_auth(token),
+    }
+    match &request.body {
+        RequestBody::None => {}
+        RequestBody::Json(body) => req = req.header(reqwest::header::CONTENT_TYPE, "application/json").body(body.clone()),
+        RequestBody::Text(body) => req = req.header(reqwest::header::CONTENT_TYPE, "text/plain").body(body.clone()),
+        RequestBody::GraphQL { query, variables } => {
+            let variables = match variables.trim() {
+                "" => serde_json::Value::Null,
+                variables => serde_json::from_str(variables)?,
+            };
+            let body = serde_json::json!({ "query": query, "variables": variables });
+            req = req.header(reqwest::header::CONTENT_TYPE, "application/json").body(body.to_string());
+        }
+    }
+    // Set last so they can override the content type
+    for (header, value) in &request.headers {
+        req = req.header(header, value);
+    }
+    Ok(req)
+}
+
+/// Seconds a response stays fresh for according to its `Cache-Control`, unless it says not to reuse it
+fn max_age(headers: &HeaderMap) -> Option<i64> {
+    let cache_control = headers.get(CACHE_CONTROL)?.to_str().ok()?;
+    let mut max_age = None;
+    for directive in cache_control.split(',').map(|directive| directive.trim()) {
+        if directive.eq_ignore_ascii_case("no-cache") || directive.eq_ignore_ascii_case("no-store") {
+            return None;
+        }
+        if let Some((name, value)) = directive.split_once('=') {
+            if name.trim().eq_ignore_ascii_case("max-age") {
+                max_age = value.trim().trim_matches('"').parse::<i64>().ok();
+            }
+        }
+    }
+    max_age.map(|max_age| max_age.clamp(0, MAX_SERVER_DELAY_SECS))
+}
+
+/// Seconds from `now` a `Retry-After` header asks us to wait, given either as seconds or as an HTTP date
+fn retry_after(headers: &HeaderMap, now: i64) -> Option<i64> {
+    let retry_after = headers.get(RETRY_AFTER)?.to_str().ok()?.trim();
+    let delay = match retry_after.parse::<i64>() {
+        Ok(seconds) => seconds,
+        Err(_) => chrono::DateTime::parse_from_rfc2822(retry_after).ok()?.timestamp() - now,
+    };
+    Some(delay.clamp(0, MAX_SERVER_DELAY_SECS))
+}
@@ -1,23 +1,35 @@
 use crate::{
+    board_variables::evaluate_expression,
     config_manager::{Boards, ConfigWrapper},
     font_manager,
-    image_manager::{get_image_list, get_image_path},
+    image_conversion::{parse_background, ImageConversion},
+    image_manager::{create_image_folder, delete_image, get_image_folders, get_image_list, get_image_source, is_image_file_name, rename_image, save_image, to_device_format, ImageFileError},
+    image_processing::{needs_processing, process_image},
+    json_path,
+    matrix_server::{asset_transfer::INBAND_ASSETS_PROTO_VERSION, virtual_device::{is_valid_virtual_device_name, run_virtual_device}},
+    preview::render_board_preview,
+    pushed_values::MAX_PUSHED_VALUE_BYTES,
     state_manager::StateWrapper,
 };
 use axum::{
     body::{Body, Bytes},
-    extract::Path,
-    http::{Response, StatusCode},
-    routing::{get, post},
+    extract::{ws::WebSocketUpgrade, DefaultBodyLimit, Multipart, Path, Query},
+    http::{header::CONTENT_TYPE, HeaderMap, Response, StatusCode},
+    routing::{delete, get, post, put},
     Extension, Json, Router,
 };
-use shared::{board_variables::BoardVariables, device_config::DeviceConfigs};
+use chrono::Local;
+use serde::Deserialize;
+use shared::{board_variables::{BoardVariable, BoardVariables}, device_config::DeviceConfigs, image_upload::{ImageFolder, ImageRename, ImageUploadOptions}};
 use tokio::fs;
 
 static FAVICON: &[u8] = include_bytes!("./favicon.ico");
 static WASM_BINARY: &[u8] = include_bytes!("../../../wasm_project/pkg/wasm_project_bg.wasm");
 static JS_LOADER: &str = include_str!("../../../wasm_project/pkg/wasm_project.js");
 
+/// Largest image file that can be uploaded
+const MAX_IMAGE_UPLOAD_BYTES: usize = 16 * 1024 * 1024;
+
 pub async fn run_web_server(config: ConfigWrapper, state: StateWrapper) {
     // build our application with a single route
     let app = Router::new()
@@ -32,11 +44,20 @@ pub async fn run_web_server(config: ConfigWrapper, state: StateWrapper) {
         .route("/api/update/boards", post(accept_boards_update))
         .route("/api/vars", get(serve_vars))
         .route("/api/update/vars", post(accept_vars_update))
+        .route("/api/vars/{name}/history", get(serve_var_history))
+        .route("/api/vars/evaluate", post(serve_expression_evaluation))
+        .route("/api/vars/{name}/value", put(accept_pushed_value).post(accept_pushed_value).layer(DefaultBodyLimit::max(MAX_PUSHED_VALUE_BYTES)))
+        .route("/api/date_names", get(serve_date_names))
         .route("/api/devices", get(serve_devices))
         .route("/api/update/devices", post(accept_device_update))
-        .route("/api/images", get(serve_image_index))
+        .route("/api/images", get(serve_image_index).post(accept_image_upload).layer(DefaultBodyLimit::max(MAX_IMAGE_UPLOAD_BYTES)))
+        .route("/api/images/{*path}", delete(accept_image_delete))
+        .route("/api/rename_image", post(accept_image_rename))
+        .route("/api/image_folders", get(serve_image_folders).post(accept_image_folder))
         .route("/api/image_list", get(serve_image_list))
         .route("/api/get_image/{image}", get(serve_image))
+        .route("/api/preview/{board}", get(serve_board_preview))
+        .route("/api/virtual_device/{name}", get(serve_virtual_device))
         .route("/config.json", get(serve_current_config))
         .fallback(serve_index)
         .layer(Extension(config.clone()))
@@ -174,6 +195,118 @@ async fn serve_vars(Extension(config): Extension<ConfigWrapper>) -> Response<Bod
         .unwrap()
 }
 
+async fn serve_var_history(
+    Extension(config): Extension<ConfigWrapper>,
+    Extension(state): Extension<StateWrapper>,
+    Path(name): Path<String>,
+) -> Response<Body> {
+    if !config.read().await.board_variables.contains_key(&name) {
+        return Response::builder()
+            .status(StatusCode::NOT_FOUND)
+            .header("Content-Type", "text/plain")
+            .body(Body::from(StatusCode::NOT_FOUND.to_string()))
+            .unwrap();
+    }
+    // Variables that are not graphed have no history yet
+    let history = state.lock().await.variable_history.get(&name).cloned().unwrap_or_default();
+    Response::builder()
+        .status(StatusCode::OK)
+        .header("Content-Type", "application/json")
+        .body(Body::from(serde_json::to_string(&history).unwrap()))
+        .unwrap()
+}
+
+#[derive(Deserialize)]
+struct PushQuery {
+    /// JSON path picking the value out of a JSON body, for webhooks that send a whole document
+    path: Option<String>,
+}
+
+/// Sets the value of a pushed variable. JSON bodies are shown like a JSON extractor's value, so strings lose their
+/// quotes, anything else is used as plain text.
+async fn accept_pushed_value(
+    Extension(config): Extension<ConfigWrapper>,
+    Extension(state): Extension<StateWrapper>,
+    Path(name): Path<String>,
+    Query(query): Query<PushQuery>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Response<Body> {
+    match config.read().await.board_variables.get(&name) {
+        Some(BoardVariable::Pushed(_)) => {}
+        Some(_) => return bad_request(format!("'{}' is not a pushed value variable", name)),
+        None => {
+            return Response::builder()
+                .status(StatusCode::NOT_FOUND)
+                .header("Content-Type", "text/plain")
+                .body(Body::from(StatusCode::NOT_FOUND.to_string()))
+                .unwrap();
+        }
+    }
+    let Ok(text) = String::from_utf8(body.to_vec()) else {
+        return bad_request(String::from("The value must be UTF-8 text"));
+    };
+    let is_json = headers.get(CONTENT_TYPE)
+        .and_then(|content_type| content_type.to_str().ok())
+        .is_some_and(|content_type| content_type.starts_with("application/json"));
+    let value = if is_json {
+        let json = match serde_json::from_str::<serde_json::Value>(&text) {
+            Ok(json) => json,
+            Err(e) => return bad_request(format!("Invalid JSON: {}", e)),
+        };
+        let json = match &query.path {
+            Some(path) => match json_path::query(&json, path) {
+                Ok(Some(value)) => value,
+                Ok(None) => return bad_request(format!("Nothing matches '{}'", path)),
+                Err(e) => return bad_request(e.to_string()),
+            },
+            None => json,
+        };
+        json_path::value_to_text(&json)
+    } else if query.path.is_some() {
+        return bad_request(String::from("A path can only be used with a JSON body"));
+    } else {
+        // Scripts piping a value in usually end it with a newline
+        text.trim_end_matches(['\r', '\n']).to_string()
+    };
+    state.lock().await.pushed_values.set(&name, value, Local::now().timestamp());
+    Response::builder()
+        .status(StatusCode::OK)
+        .header("Content-Type", "text/plain")
+        .body(Body::from(StatusCode::OK.to_string()))
+        .unwrap()
+}
+
+async fn serve_date_names(Extension(config): Extension<ConfigWrapper>) -> Response<Body> {
+    let config = config.read().await;
+    Response::builder()
+        .status(StatusCode::OK)
+        .header("Content-Type", "application/json")
+        .body(Body::from(serde_json::to_string(&config.date_names).unwrap()))
+        .unwrap()
+}
+
+/// Evaluates an expression the way an expression variable would, so the editor can show its value while it's written
+async fn serve_expression_evaluation(
+    Extension(config): Extension<ConfigWrapper>,
+    Extension(state): Extension<StateWrapper>,
+    Json(expression): Json<String>,
+) -> Response<Body> {
+    let config = config.read().await;
+    match evaluate_expression(&expression, &config, state, &Local::now()).await {
+        Ok(value) => Response::builder()
+            .status(StatusCode::OK)
+            .header("Content-Type", "text/plain")
+            .body(Body::from(value))
+            .unwrap(),
+        Err(e) => Response::builder()
+            .status(StatusCode::BAD_REQUEST)
+            .header("Content-Type", "text/plain")
+            .body(Body::from(e.to_string()))
+            .unwrap(),
+    }
+}
+
 async fn serve_devices(Extension(config): Extension<ConfigWrapper>) -> Response<Body> {
     let config = config.read().await;
     Response::builder()
@@ -247,25 +380,187 @@ async fn serve_image_list(
         .unwrap()
 }
 
-async fn serve_image(
+/// Saves a multipart upload: a `file` field plus the fields of `ImageUploadOptions`. Resized or quantised
+/// images are saved as PNGs, anything else is saved exactly as it was sent.
+async fn accept_image_upload(
     Extension(config): Extension<ConfigWrapper>,
     Extension(state): Extension<StateWrapper>,
-    Path(image): Path<String>,
+    mut multipart: Multipart,
 ) -> Response<Body> {
-    let images = get_image_list(config.clone(), state.clone(), false).await;
-    let img_path = images.get(&image);
-    if img_path.is_none() {
-        return Response::builder()
-            .status(StatusCode::NOT_FOUND)
+    let mut options = ImageUploadOptions::default();
+    let mut file = None;
+    loop {
+        let field = match multipart.next_field().await {
+            Ok(Some(field)) => field,
+            Ok(None) => break,
+            Err(e) => return bad_request(e.body_text()),
+        };
+        let field_name = field.name().unwrap_or_default().to_string();
+        if field_name == "file" {
+            // Browsers only send the name, but other clients may send a whole path
+            let file_name = field.file_name().and_then(|x| x.rsplit(['/', '\\']).next()).unwrap_or_default().to_string();
+            match field.bytes().await {
+                Ok(data) => file = Some((file_name, data)),
+                Err(e) => return bad_request(e.body_text()),
+            }
+            continue;
+        }
+        let value = match field.text().await {
+            Ok(value) => value,
+            Err(e) => return bad_request(e.body_text()),
+        };
+        if !options.set_form_field(&field_name, &value) {
+            return bad_request(format!("Invalid value for {}: {}", field_name, value));
+        }
+    }
+    let Some((file_name, data)) = file else {
+        return bad_request(String::from("No file was uploaded"));
+    };
+    if !is_image_file_name(&file_name) {
+        return image_file_error_response(ImageFileError::UnsupportedFormat(file_name));
+    }
+    let (file_name, data) = if needs_processing(&options) {
+        let processing_options = options.clone();
+        let processed = tokio::task::spawn_blocking(move || process_image(&data, &processing_options)).await;
+        match processed {
+            Ok(Ok(processed)) => {
+                let stem = file_name.rsplit_once('.').map(|(stem, _)| stem).unwrap_or(&file_name);
+                (format!("{}.png", stem), processed)
+            }
+            Ok(Err(e)) => return bad_request(format!("Unable to process {}: {}", file_name, e)),
+            Err(e) => return bad_request(format!("Unable to process {}: {}", file_name, e)),
+        }
+    } else {
+        if let Err(e) = image::load_from_memory(&data) {
+            return bad_request(format!("Unable to read {}: {}", file_name, e));
+        }
+        (file_name, data.to_vec())
+    };
+    match save_image(config, state, &options.folder, &file_name, &data, options.overwrite).await {
+        Ok(image_name) => Response::builder()
+            .status(StatusCode::CREATED)
+            .header("Content-Type", "application/json")
+            .body(Body::from(serde_json::json!({ "path": image_name }).to_string()))
+            .unwrap(),
+        Err(e) => image_file_error_response(e),
+    }
+}
+
+async fn accept_image_delete(
+    Extension(config): Extension<ConfigWrapper>,
+    Extension(state): Extension<StateWrapper>,
+    Path(path): Path<String>,
+) -> Response<Body> {
+    match delete_image(config, state, &path).await {
+        Ok(()) => Response::builder()
+            .status(StatusCode::OK)
             .header("Content-Type", "text/plain")
-            .body(Body::from(StatusCode::NOT_FOUND.to_string()))
-            .unwrap();
+            .body(Body::from(StatusCode::OK.to_string()))
+            .unwrap(),
+        Err(e) => image_file_error_response(e),
     }
-    let img_path = img_path.unwrap();
-    let img_path = get_image_path(config.clone(), Some(img_path)).await;
-    //
-    let file = fs::read(img_path).await;
-    if file.is_err() {
+}
+
+async fn accept_image_rename(
+    Extension(config): Extension<ConfigWrapper>,
+    Extension(state): Extension<StateWrapper>,
+    Json(rename): Json<ImageRename>,
+) -> Response<Body> {
+    match rename_image(config, state, &rename.from, &rename.to).await {
+        Ok(()) => Response::builder()
+            .status(StatusCode::OK)
+            .header("Content-Type", "text/plain")
+            .body(Body::from(StatusCode::OK.to_string()))
+            .unwrap(),
+        Err(e) => image_file_error_response(e),
+    }
+}
+
+async fn serve_image_folders(Extension(config): Extension<ConfigWrapper>) -> Response<Body> {
+    let folders = get_image_folders(config).await;
+    Response::builder()
+        .status(StatusCode::OK)
+        .header("Content-Type", "application/json")
+        .body(Body::from(serde_json::to_string(&folders).unwrap()))
+        .unwrap()
+}
+
+async fn accept_image_folder(
+    Extension(config): Extension<ConfigWrapper>,
+    Json(folder): Json<ImageFolder>,
+) -> Response<Body> {
+    match create_image_folder(config, &folder.path).await {
+        Ok(()) => Response::builder()
+            .status(StatusCode::CREATED)
+            .header("Content-Type", "text/plain")
+            .body(Body::from(StatusCode::CREATED.to_string()))
+            .unwrap(),
+        Err(e) => image_file_error_response(e),
+    }
+}
+
+fn bad_request(message: String) -> Response<Body> {
+    Response::builder()
+        .status(StatusCode::BAD_REQUEST)
+        .header("Content-Type", "text/plain")
+        .body(Body::from(message))
+        .unwrap()
+}
+
+fn image_file_error_response(e: ImageFileError) -> Response<Body> {
+    let status = match &e {
+        ImageFileError::InvalidPath(_) | ImageFileError::UnsupportedFormat(_) => StatusCode::BAD_REQUEST,
+        ImageFileError::NotFound(_) => StatusCode::NOT_FOUND,
+        ImageFileError::AlreadyExists(_) => StatusCode::CONFLICT,
+        ImageFileError::Io(_, io) if io.kind() == std::io::ErrorKind::DirectoryNotEmpty => StatusCode::CONFLICT,
+        ImageFileError::Io(..) => {
+            tracing::error!("{}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        }
+    };
+    Response::builder()
+        .status(status)
+        .header("Content-Type", "text/plain")
+        .body(Body::from(e.to_string()))
+        .unwrap()
+}
+
+#[derive(Deserialize)]
+struct ImageQuery {
+    /// `RRGGBB` colour that transparent pixels are blended onto, black by default
+    background: Option<String>,
+    /// `preserve` to keep the alpha channel in a 32 bit BMP instead
+    alpha: Option<String>,
+}
+
+/// Images are sent as BMPs, converted from other formats as devices need them. GIFs are sent as they are
+/// unless a conversion is asked for, so the web interface can play them; devices get their frames as separate images.
+async fn serve_image(
+    Extension(config): Extension<ConfigWrapper>,
+    Extension(state): Extension<StateWrapper>,
+    Path(image): Path<String>,
+    Query(query): Query<ImageQuery>,
+) -> Response<Body> {
+    let conversion = match (query.alpha.as_deref(), query.background.as_deref()) {
+        (Some("preserve"), _) => Some(ImageConversion::Preserve),
+        (_, Some(background)) => match parse_background(background) {
+            Some(background) => Some(ImageConversion::Flatten(background)),
+            None => {
+                return Response::builder()
+                    .status(StatusCode::BAD_REQUEST)
+                    .header("Content-Type", "text/plain")
+                    .body(Body::from("Invalid background colour"))
+                    .unwrap();
+            }
+        },
+        _ => None,
+    };
+    let file = match get_image_source(config.clone(), state.clone(), &image).await {
+        Some(source) if conversion.is_none() && matches!(image::guess_format(&source), Ok(image::ImageFormat::Gif)) => Some(source),
+        Some(source) => to_device_format(state.clone(), &image, source, conversion.unwrap_or_default()).await,
+        None => None,
+    };
+    if file.is_none() {
         return Response::builder()
             .status(StatusCode::NOT_FOUND)
             .header("Content-Type", "text/plain")
@@ -273,10 +568,13 @@ async fn serve_image(
             .unwrap();
     }
     let file = file.unwrap();
-    //
+    let content_type = match image::guess_format(&file) {
+        Ok(image::ImageFormat::Gif) => "image/gif",
+        _ => "image/bmp",
+    };
     Response::builder()
         .status(StatusCode::OK)
-        .header("Content-Type", "image/bmp")
+        .header("Content-Type", content_type)
         .body(Body::from(Bytes::from(file)))
         .unwrap()
 }
@@ -288,4 +586,95 @@ async fn serve_current_config(Extension(config): Extension<ConfigWrapper>) -> Re
         .header("Content-Type", "application/json")
         .body(Body::from(serde_json::to_string_pretty(&*config).unwrap()))
         .unwrap()
-}
\ No newline at end of file
+}
+
+#[derive(Deserialize)]
+struct PreviewQuery {
+    device: Option<String>,
+    at: Option<i64>,
+}
+
+/// Renders a board as a PNG the way a device (`?device=`, `default` otherwise) would show it at `?at=` or now.
+/// Text is always drawn with the BDF fonts, which devices that can't be sent fonts don't have, so their previews are
+/// marked with `X-Preview-Approximate: true`.
+async fn serve_board_preview(
+    Extension(config): Extension<ConfigWrapper>,
+    Extension(state): Extension<StateWrapper>,
+    Path(board): Path<String>,
+    Query(query): Query<PreviewQuery>,
+) -> Response<Body> {
+    let device_id = query.device.unwrap_or(String::from("default"));
+    let (board, device_config) = {
+        let config = config.read().await;
+        let board = config.get_boards().get(&board).cloned();
+        let device_config = config.device_configs.get(&device_id).cloned();
+        match (board, device_config) {
+            (Some(board), Some(device_config)) => (board, device_config),
+            _ => {
+                return Response::builder()
+                    .status(StatusCode::NOT_FOUND)
+                    .header("Content-Type", "text/plain")
+                    .body(Body::from(StatusCode::NOT_FOUND.to_string()))
+                    .unwrap();
+            }
+        }
+    };
+    let now = match query.at {
+        Some(timestamp) => match chrono::DateTime::from_timestamp(timestamp, 0) {
+            Some(x) => x.with_timezone(&chrono::Local),
+            None => {
+                return Response::builder()
+                    .status(StatusCode::BAD_REQUEST)
+                    .header("Content-Type", "text/plain")
+                    .body(Body::from("Invalid timestamp"))
+                    .unwrap();
+            }
+        },
+        None => chrono::Local::now(),
+    };
+    if board.size.0 > device_config.size.0 || board.size.1 > device_config.size.1 {
+        return Response::builder()
+            .status(StatusCode::BAD_REQUEST)
+            .header("Content-Type", "text/plain")
+            .body(Body::from(format!("Board [{}] is too large for device [{}] to display!", &board.name, &device_id)))
+            .unwrap();
+    }
+    // These draw text with their built-in mono fonts
+    let approximate = device_config.proto_version < INBAND_ASSETS_PROTO_VERSION;
+    match render_board_preview(&board, &device_config, &now, config.clone(), state.clone()).await {
+        Ok(Some(png)) => Response::builder()
+            .status(StatusCode::OK)
+            .header("Content-Type", "image/png")
+            .header("X-Preview-Approximate", approximate.to_string())
+            .body(Body::from(Bytes::from(png)))
+            .unwrap(),
+        Ok(None) => Response::builder()
+            .status(StatusCode::NO_CONTENT)
+            .body(Body::empty())
+            .unwrap(),
+        Err(e) => {
+            tracing::warn!("Error rendering preview of board [{}]:\n{:#}", &board.name, e);
+            Response::builder()
+                .status(StatusCode::INTERNAL_SERVER_ERROR)
+                .header("Content-Type", "text/plain")
+                .body(Body::from(format!("{:#}", e)))
+                .unwrap()
+        }
+    }
+}
+
+async fn serve_virtual_device(
+    Extension(config): Extension<ConfigWrapper>,
+    Extension(state): Extension<StateWrapper>,
+    Path(name): Path<String>,
+    ws: WebSocketUpgrade,
+) -> Response<Body> {
+    if !is_valid_virtual_device_name(&name) {
+        return Response::builder()
+            .status(StatusCode::BAD_REQUEST)
+            .header("Content-Type", "text/plain")
+            .body(Body::from("Virtual device names may only contain letters, numbers, '-' and '_'"))
+            .unwrap();
+    }
+    ws.on_upgrade(move |socket| run_virtual_device(socket, name, config, state))
+}
@@ -1,20 +1,24 @@
-use std::{collections::{hash_map, HashMap}, fmt::Display, str::FromStr};
+use std::{collections::HashMap, fmt::{Display, Write}};
 
+use chrono::{format::{Item, StrftimeItems}, DateTime, Days, Local, TimeZone, Utc};
 use rand::Rng;
+
+use crate::{countdowns::CountdownData, date_names::{DateNameTables, DateNames, DEFAULT_LOCALE}};
 use serde::{Deserialize, Serialize};
 
 pub type BoardVariables = HashMap<String, BoardVariable>;
 
 #[derive(Serialize, Deserialize, Clone)]
 pub enum BoardVariable {
-    URL(
-        u32, /*var_id*/
-        String, /*url*/
-        i64,    /*expiry-secs*/
-        HashMap<String, String>, /* headers */
-    ),
+    URL(UrlRequest),
     JsonURL(u32 /*URL var_id*/, String /*path*/, bool /* round_numbers */, Option<(u8, i16)> /*substring*/),
     Time(TimeData),
+    /// Computed from other variables, see `expressions`
+    Expression(String),
+    /// Time until or since a moment, see `countdowns`
+    Countdown(CountdownData),
+    /// Set from outside with `PUT /api/vars/{name}/value`, by CI pipelines, sensors or scripts
+    Pushed(PushedData),
 }
 
 impl BoardVariable {
@@ -23,35 +27,44 @@ impl BoardVariable {
             String::from("HTTP Request"),
             String::from("URL JSON Value Extractor"),
             String::from("DateTime"),
+            String::from("Expression"),
+            String::from("Countdown"),
+            String::from("Pushed Value"),
         ]
     }
     pub fn get_variable_type(&self) -> String {
         return match self {
-            BoardVariable::URL(_id, _url, _expiry, _headers) => String::from("HTTP Request"),
+            BoardVariable::URL(_request) => String::from("HTTP Request"),
             BoardVariable::JsonURL(_url_id, _json_path, _round_numbers, _substring) => String::from("URL JSON Value Extractor"),
             BoardVariable::Time(_time_data) => String::from("DateTime"),
+            BoardVariable::Expression(_expression) => String::from("Expression"),
+            BoardVariable::Countdown(_countdown) => String::from("Countdown"),
+            BoardVariable::Pushed(_pushed) => String::from("Pushed Value"),
         };
     }
     pub fn get_default_by_type(var_type: &str) -> BoardVariable {
         return match var_type {
-            "HTTP Request" => BoardVariable::URL(
-                get_rand(),
-                String::from("https://jsonplaceholder.typicode.com/todos/"),
-                30,
-                hash_map::HashMap::new(),
-            ),
+            "HTTP Request" => BoardVariable::URL(UrlRequest {
+                var_id: get_rand(),
+                url: String::from("https://jsonplaceholder.typicode.com/todos/"),
+                expiry: 30,
+                ..Default::default()
+            }),
             "URL JSON Value Extractor" => {
                 BoardVariable::JsonURL(get_rand(), String::from("0.title"), false, None)
             }
-            "DateTime" => BoardVariable::Time(TimeData::Time),
-            _ => BoardVariable::Time(TimeData::Time),
+            "DateTime" => BoardVariable::Time(TimeData::with_format(TIME_FORMAT_PRESETS[0].1)),
+            "Expression" => BoardVariable::Expression(String::from("round((20 + 273.15) * 9 / 5 - 459.67, 1)")),
+            "Countdown" => BoardVariable::Countdown(CountdownData::default()),
+            "Pushed Value" => BoardVariable::Pushed(PushedData::default()),
+            _ => BoardVariable::Time(TimeData::with_format(TIME_FORMAT_PRESETS[0].1)),
         };
     }
     pub fn get_url_if_id_matches_or_none(&self, check_id: &u32) -> Option<String> {
         return match self {
-            BoardVariable::URL(id, url, _timeout, _headers) => {
-                if id.eq(check_id) {
-                    Some(url.clone())
+            BoardVariable::URL(request) => {
+                if request.var_id.eq(check_id) {
+                    Some(request.url.clone())
                 } else {
                     None
                 }
@@ -61,40 +74,314 @@ impl BoardVariable {
     }
 }
 
-#[derive(Serialize, Deserialize, Clone, Debug)]
-pub enum TimeData {
-    Weekday(u8/* offset */, Option<(u8, i16)> /*substring*/),
-    Time,
-    Date,
+/// Default time allowed for a request, in seconds
+pub const DEFAULT_REQUEST_TIMEOUT: u16 = 10;
+/// Most times a failed request is retried before waiting for the next refresh
+pub const MAX_REQUEST_RETRIES: u8 = 5;
+
+/// The request behind an HTTP variable. Configs from before the named fields were added stored this as
+/// `[var_id, url, expiry, headers]`, which still loads, with everything after it left at its default.
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+pub struct UrlRequest {
+    /// What JSON extractors refer to this request by
+    pub var_id: u32,
+    /// May include `__variable__` placeholders, filled with other variables' values
+    pub url: String,
+    /// Seconds before the response is fetched again
+    pub expiry: i64,
+    pub headers: HashMap<String, String>,
+    #[serde(default)]
+    pub method: HttpMethod,
+    #[serde(default)]
+    pub body: RequestBody,
+    #[serde(default)]
+    pub auth: HttpAuth,
+    /// Added to the URL's query string, values may include `__variable__` placeholders
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub query: Vec<(String, String)>,
+    /// Extra attempts after a failed request, waiting twice as long before each one
+    #[serde(default)]
+    pub retries: u8,
+    /// Seconds
+    #[serde(default = "default_request_timeout")]
+    pub timeout: u16,
+    /// Show the body of error responses (anything but 2xx) instead of keeping the last good value
+    #[serde(default)]
+    pub cache_errors: bool,
+}
+impl Default for UrlRequest {
+    fn default() -> Self {
+        Self {
+            var_id: 0,
+            url: String::new(),
+            expiry: 30,
+            headers: HashMap::new(),
+            method: HttpMethod::Get,
+            body: RequestBody::None,
+            auth: HttpAuth::None,
+            query: Vec::new(),
+            retries: 0,
+            timeout: DEFAULT_REQUEST_TIMEOUT,
+            cache_errors: false,
+        }
+    }
+}
+fn default_request_timeout() -> u16 {
+    DEFAULT_REQUEST_TIMEOUT
 }
 
-impl Display for TimeData {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        let out = match self {
-            TimeData::Weekday(_, _) => String::from("Weekday"),
-            TimeData::Time => String::from("Time"),
-            TimeData::Date => String::from("Date"),
-        };
-        write!(f, "{}", &out)
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug, Default)]
+pub enum HttpMethod {
+    #[default]
+    Get,
+    Post,
+    Put,
+}
+impl HttpMethod {
+    pub fn get_option(&self) -> String {
+        match self {
+            HttpMethod::Get => HttpMethod::get_options()[0].clone(),
+            HttpMethod::Post => HttpMethod::get_options()[1].clone(),
+            HttpMethod::Put => HttpMethod::get_options()[2].clone(),
+        }
+    }
+    pub fn get_options() -> Vec<String> {
+        "GET;POST;PUT".split(';').map(|x|x.to_string()).collect()
+    }
+    pub fn from_option(type_str: &str) -> HttpMethod {
+        match type_str {
+            "POST" => HttpMethod::Post,
+            "PUT" => HttpMethod::Put,
+            _ => HttpMethod::Get,
+        }
     }
 }
 
-impl FromStr for TimeData {
-    type Err = ();
+/// What is sent as the body of a request. Text is filled in like the URL, `__variable__` placeholders and all.
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug, Default)]
+pub enum RequestBody {
+    #[default]
+    None,
+    /// Sent as `application/json`
+    Json(String),
+    /// Sent as `text/plain`
+    Text(String),
+    /// A GraphQL query and its variables (as JSON), always POSTed
+    GraphQL { query: String, variables: String },
+}
+impl RequestBody {
+    pub fn get_option(&self) -> String {
+        match self {
+            RequestBody::None => RequestBody::get_options()[0].clone(),
+            RequestBody::Json(_) => RequestBody::get_options()[1].clone(),
+            RequestBody::Text(_) => RequestBody::get_options()[2].clone(),
+            RequestBody::GraphQL { .. } => RequestBody::get_options()[3].clone(),
+        }
+    }
+    pub fn get_options() -> Vec<String> {
+        "None;JSON;Text;GraphQL".split(';').map(|x|x.to_string()).collect()
+    }
+    pub fn from_option(type_str: &str) -> RequestBody {
+        match type_str {
+            "JSON" => RequestBody::Json(String::from("{}")),
+            "Text" => RequestBody::Text(String::new()),
+            "GraphQL" => RequestBody::GraphQL { query: String::from("query {\n}"), variables: String::from("{}") },
+            _ => RequestBody::None,
+        }
+    }
+}
 
-    fn from_str(s: &str) -> Result<Self, Self::Err> {
-        Ok(match s {
-            "Weekday" => Self::Weekday(0, None),
-            "Time" => Self::Time,
-            "Date" => Self::Date,
-            _ => Self::Time,
-        })
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug, Default)]
+pub enum HttpAuth {
+    #[default]
+    None,
+    Basic { username: String, password: String },
+    Bearer(String /* token */),
+}
+impl HttpAuth {
+    pub fn get_option(&self) -> String {
+        match self {
+            HttpAuth::None => HttpAuth::get_options()[0].clone(),
+            HttpAuth::Basic { .. } => HttpAuth::get_options()[1].clone(),
+            HttpAuth::Bearer(_) => HttpAuth::get_options()[2].clone(),
+        }
     }
+    pub fn get_options() -> Vec<String> {
+        "None;Basic;Bearer Token".split(';').map(|x|x.to_string()).collect()
+    }
+    pub fn from_option(type_str: &str) -> HttpAuth {
+        match type_str {
+            "Basic" => HttpAuth::Basic { username: String::new(), password: String::new() },
+            "Bearer Token" => HttpAuth::Bearer(String::new()),
+            _ => HttpAuth::None,
+        }
+    }
+}
+
+/// A value pushed to the server rather than fetched by it
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug, Default)]
+pub struct PushedData {
+    /// Shown until a value is pushed, and again once a pushed value expires
+    #[serde(default)]
+    pub default: String,
+    /// Seconds a pushed value is shown for, forever when 0
+    #[serde(default)]
+    pub ttl: u32,
+}
+
+/// Formats offered by the DateTime editor, the first is used for new variables
+pub const TIME_FORMAT_PRESETS: [(&str, &str); 7] = [
+    ("12 Hour Time", "%I:%M %p"),
+    ("24 Hour Time", "%H:%M"),
+    ("24 Hour Time with Seconds", "%H:%M:%S"),
+    ("Date", "%b %-d %Y"),
+    ("Weekday", "%A"),
+    ("Short Weekday", "%a"),
+    ("ISO 8601", "%Y-%m-%dT%H:%M:%S%:z"),
+];
+
+/// The current date or time, formatted strftime style (`%H:%M`, `%a %-d %b`...). Configs from before formats were
+/// added stored `{"Weekday": [offset, substring]}`, `"Time"` or `"Date"`, which load as the equivalent format.
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+#[serde(from = "StoredTimeData")]
+pub struct TimeData {
+    pub format: String,
+    /// IANA name like `Europe/London`. When empty the device's time zone is used, then the server's.
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub time_zone: String,
+    /// Days added before formatting, e.g. 1 for tomorrow
+    #[serde(default)]
+    pub day_offset: i16,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub substring: Option<(u8, i16)>,
+    /// Language of weekday and month names, a built-in locale like `de` or the name of a custom table in the config.
+    /// When empty the device's locale is used, then English.
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub locale: String,
+}
+
+/// Settings of the device showing a DateTime variable, used for the ones the variable leaves empty
+#[derive(Clone, Default, Debug)]
+pub struct DeviceTimeSettings {
+    pub time_zone: String,
+    pub locale: String,
 }
 
 impl TimeData {
-    pub fn get_all_time_data_types() -> Vec<String> {
-        vec!["Weekday".to_string(), TimeData::Time.to_string(), TimeData::Date.to_string()]
+    pub fn with_format(format: &str) -> TimeData {
+        TimeData { format: format.to_string(), time_zone: String::new(), day_offset: 0, substring: None, locale: String::new() }
+    }
+
+    /// `time` in the variable's time zone and locale, or the device's where the variable has none. Without either
+    /// the local time zone and English names are used.
+    pub fn format(&self, time: &DateTime<Utc>, device: &DeviceTimeSettings, custom_names: &DateNameTables) -> Result<String, String> {
+        let locale = [self.locale.as_str(), device.locale.as_str()].into_iter().find(|locale| !locale.is_empty()).unwrap_or(DEFAULT_LOCALE);
+        let names = DateNames::for_locale(locale, custom_names)?;
+        let time_zone = if self.time_zone.is_empty() { &device.time_zone } else { &self.time_zone };
+        if time_zone.is_empty() {
+            return self.format_in(time.with_timezone(&Local), &names);
+        }
+        let time_zone = parse_time_zone(time_zone)?;
+        self.format_in(time.with_timezone(&time_zone), &names)
+    }
+
+    /// A representative (and deliberately wide) value, Wednesday 30th September 2026 at 12:34:56. Custom name tables
+    /// aren't available everywhere samples are shown, so variables using one show English names.
+    pub fn sample(&self) -> String {
+        let sample = Utc.with_ymd_and_hms(2026, 9, 30, 12, 34, 56).unwrap();
+        let names = DateNames::for_locale(&self.locale, &DateNameTables::new())
+            .or_else(|_| DateNames::for_locale(DEFAULT_LOCALE, &DateNameTables::new()))
+            .unwrap();
+        self.format_in(sample, &names).unwrap_or_else(|e| e)
+    }
+
+    fn format_in<Tz: TimeZone>(&self, time: DateTime<Tz>, names: &DateNames) -> Result<String, String>
+    where
+        Tz::Offset: Display,
+    {
+        let items = parse_time_format(&self.format)?;
+        let days = Days::new(self.day_offset.unsigned_abs() as u64);
+        let time = if self.day_offset < 0 { time.checked_sub_days(days) } else { time.checked_add_days(days) }
+            .ok_or_else(|| String::from("The day offset is out of range"))?;
+        let items = names.localize(items, &time);
+        let mut formatted = String::new();
+        write!(formatted, "{}", time.format_with_items(items.iter()))
+            .map_err(|_| format!("'{}' can't be used on this date", &self.format))?;
+        Ok(apply_substring(&formatted, &self.substring))
+    }
+}
+
+/// `format` split into its parts, or why it isn't a valid strftime format
+pub fn parse_time_format(format: &str) -> Result<Vec<Item<'_>>, String> {
+    let items: Vec<Item> = StrftimeItems::new(format).collect();
+    if items.contains(&Item::Error) {
+        return Err(format!("'{}' is not a valid format", format));
+    }
+    Ok(items)
+}
+
+pub fn parse_time_zone(name: &str) -> Result<chrono_tz::Tz, String> {
+    name.trim().parse::<chrono_tz::Tz>().map_err(|_| format!("Unknown time zone '{}'", name))
+}
+
+/// Every IANA time zone name, like `America/New_York`
+pub fn time_zone_names() -> impl Iterator<Item = &'static str> {
+    chrono_tz::TZ_VARIANTS.iter().map(|time_zone| time_zone.name())
+}
+
+/// Part of some text, from `start` to `end` characters in, where `end` counts back from the end when negative and 0 is
+/// the end. Counting characters keeps localized names like `miércoles` whole.
+pub fn apply_substring(data: &str, substring: &Option<(u8, i16)>) -> String {
+    let Some((start, end)) = substring else {
+        return data.to_string();
+    };
+    let length = data.chars().count();
+    let end = match *end {
+        0 => length,
+        end if end < 0 => length.saturating_sub(end.unsigned_abs() as usize),
+        end => (end as usize).min(length),
+    };
+    data.chars().skip(*start as usize).take(end.saturating_sub(*start as usize)).collect()
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum StoredTimeData {
+    Current {
+        format: String,
+        #[serde(default)]
+        time_zone: String,
+        #[serde(default)]
+        day_offset: i16,
+        #[serde(default)]
+        substring: Option<(u8, i16)>,
+        #[serde(default)]
+        locale: String,
+    },
+    Legacy(LegacyTimeData),
+}
+
+#[derive(Deserialize)]
+enum LegacyTimeData {
+    Weekday(u8/* offset */, Option<(u8, i16)> /*substring*/),
+    Time,
+    Date,
+}
+
+impl From<StoredTimeData> for TimeData {
+    fn from(stored: StoredTimeData) -> Self {
+        match stored {
+            StoredTimeData::Current { format, time_zone, day_offset, substring, locale } => {
+                TimeData { format, time_zone, day_offset, substring, locale }
+            }
+            StoredTimeData::Legacy(LegacyTimeData::Weekday(offset, substring)) => TimeData {
+                day_offset: offset as i16,
+                substring,
+                ..TimeData::with_format("%A")
+            },
+            StoredTimeData::Legacy(LegacyTimeData::Time) => TimeData::with_format("%I:%M %p"),
+            StoredTimeData::Legacy(LegacyTimeData::Date) => TimeData::with_format("%b %-d %Y"),
+        }
     }
 }
 
@@ -52,6 +52,13 @@ pub struct BoardElement {
     pub colour: ColourOption,
     pub font: Option<String>,
     pub value: BoardElementValue,
+    #[serde(default)]
+    pub horizontal_alignment: HorizontalAlignment,
+    #[serde(default)]
+    pub vertical_alignment: VerticalAlignment,
+    /// (width, height) of the area text is aligned within, starting at (x, y)
+    #[serde(default)]
+    pub text_box: Option<(u8, u8)>,
 }
 impl PartialEq for BoardElement {
     fn eq(&self, other: &Self) -> bool {
@@ -61,6 +68,9 @@ impl PartialEq for BoardElement {
             && self.colour == other.colour
             && self.font == other.font
             && self.value == other.value
+            && self.horizontal_alignment == other.horizontal_alignment
+            && self.vertical_alignment == other.vertical_alignment
+            && self.text_box == other.text_box
     }
     fn ne(&self, other: &Self) -> bool {
         !self.eq(other)
@@ -74,6 +84,17 @@ impl BoardElement {
         self.colour = new_values.colour;
         self.font = new_values.font;
         self.value = new_values.value;
+        self.horizontal_alignment = new_values.horizontal_alignment;
+        self.vertical_alignment = new_values.vertical_alignment;
+        self.text_box = new_values.text_box;
+    }
+    /// Resolves `Auto`: elements without an x position are centered, the rest are left aligned
+    pub fn get_horizontal_alignment(&self) -> HorizontalAlignment {
+        match self.horizontal_alignment {
+            HorizontalAlignment::Auto if self.x.is_none() => HorizontalAlignment::Center,
+            HorizontalAlignment::Auto => HorizontalAlignment::Left,
+            alignment => alignment,
+        }
     }
 }
 impl Default for BoardElement {
@@ -87,6 +108,9 @@ impl Default for BoardElement {
             colour: ColourOption::Default,
             font: None,
             value: BoardElementValue::default(),
+            horizontal_alignment: HorizontalAlignment::default(),
+            vertical_alignment: VerticalAlignment::default(),
+            text_box: None,
         }
     }
 }
@@ -95,6 +119,57 @@ fn get_rand() -> u32 {
     rng.gen()
 }
 
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, PartialOrd, Debug, Default)]
+pub enum HorizontalAlignment {
+    #[default]
+    Auto,
+    Left,
+    Center,
+    Right,
+}
+impl HorizontalAlignment {
+    pub fn get_option(&self) -> String {
+        format!("{:?}", self)
+    }
+    pub fn get_options() -> Vec<String> {
+        "Auto;Left;Center;Right".split(';').map(|x|x.to_string()).collect()
+    }
+    pub fn from_option(type_str: &str) -> HorizontalAlignment {
+        match type_str {
+            "Left" => HorizontalAlignment::Left,
+            "Center" => HorizontalAlignment::Center,
+            "Right" => HorizontalAlignment::Right,
+            _ => HorizontalAlignment::Auto,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, PartialOrd, Debug, Default)]
+pub enum VerticalAlignment {
+    #[default]
+    Top,
+    Middle,
+    Bottom,
+    /// `y` is the baseline of the text rather than the top of the box
+    Baseline,
+}
+impl VerticalAlignment {
+    pub fn get_option(&self) -> String {
+        format!("{:?}", self)
+    }
+    pub fn get_options() -> Vec<String> {
+        "Top;Middle;Bottom;Baseline".split(';').map(|x|x.to_string()).collect()
+    }
+    pub fn from_option(type_str: &str) -> VerticalAlignment {
+        match type_str {
+            "Middle" => VerticalAlignment::Middle,
+            "Bottom" => VerticalAlignment::Bottom,
+            "Baseline" => VerticalAlignment::Baseline,
+            _ => VerticalAlignment::Top,
+        }
+    }
+}
+
 #[derive(Serialize, Deserialize, Clone, Copy, PartialEq, PartialOrd, Debug)]
 pub enum ColourOption {
     Default,
@@ -163,6 +238,26 @@ pub enum BoardElementValue {
     Img(String, bool /* dynamic */),
     Pixel,
     Line(u8, u8, String),
+    /// Text that scrolls horizontally within the element's text box when it is too wide to fit
+    Marquee(String, MarqueeSettings),
+    /// Text wrapped onto as many lines as fit in the element's text box
+    TextBox(String, TextBoxSettings),
+    Rect(u8, u8 /* width, height */, ShapeStyle),
+    RoundedRect(u8, u8 /* width, height */, u8 /* corner radius */, ShapeStyle),
+    Circle(u8 /* diameter */, ShapeStyle),
+    Ellipse(u8, u8 /* width, height */, ShapeStyle),
+    /// The other two corners, the first one is at (x, y)
+    Triangle((u8, u8), (u8, u8), ShapeStyle),
+    /// Every point after the first one at (x, y)
+    Polyline(Vec<(u8, u8)>, ShapeStyle),
+    /// Filled in proportion to the number in the value
+    Bar(String /* value */, u8, u8 /* width, height */, BarDirection, IndicatorSettings),
+    /// A ring open at the bottom, filled clockwise in proportion to the number in the value
+    Gauge(String /* value */, u8 /* diameter */, u8 /* thickness */, IndicatorSettings),
+    /// Recent history of a numeric variable, which the server samples while a graph shows it
+    Graph(String /* variable name */, u8, u8 /* width, height */, GraphSettings),
+    /// Plays an animated GIF, or a horizontal sprite strip cut into equal frames
+    Animation(String /* image path */, AnimationSettings),
 }
 impl Default for BoardElementValue {
     fn default() -> Self {
@@ -177,6 +272,22 @@ impl BoardElementValue {
             BoardElementValue::Img(x, _) => (String::from("Image"), x.clone()),
             BoardElementValue::Pixel => (String::from("Pixel"), String::new()),
             BoardElementValue::Line(_, _, x) => (String::from("Line"), x.clone()),
+            BoardElementValue::Marquee(x, _) => (String::from("Marquee"), x.clone()),
+            BoardElementValue::TextBox(x, _) => (String::from("Text Box"), x.clone()),
+            BoardElementValue::Bar(x, ..) => (String::from("Bar"), x.clone()),
+            BoardElementValue::Gauge(x, ..) => (String::from("Gauge"), x.clone()),
+            BoardElementValue::Graph(x, ..) => (String::from("Graph"), x.clone()),
+            BoardElementValue::Animation(x, _) => (String::from("Animation"), x.clone()),
+            _ => (self.get_type(), String::new()),
+        }
+    }
+    /// Text with `__variable__` placeholders, for the element types whose value is filled in from variables
+    pub fn template(&self) -> Option<&str> {
+        match self {
+            BoardElementValue::Text(x) | BoardElementValue::Marquee(x, _) | BoardElementValue::TextBox(x, _)
+            | BoardElementValue::Bar(x, ..) | BoardElementValue::Gauge(x, ..) | BoardElementValue::Line(_, _, x)
+            | BoardElementValue::Img(x, true) => Some(x),
+            _ => None,
         }
     }
     pub fn get_type(&self) -> String {
@@ -185,10 +296,22 @@ impl BoardElementValue {
             BoardElementValue::Img(_, _) => String::from("Image"),
             BoardElementValue::Pixel => String::from("Pixel"),
             BoardElementValue::Line(_, _, _) => String::from("Line"),
+            BoardElementValue::Marquee(_, _) => String::from("Marquee"),
+            BoardElementValue::TextBox(_, _) => String::from("Text Box"),
+            BoardElementValue::Rect(..) => String::from("Rectangle"),
+            BoardElementValue::RoundedRect(..) => String::from("Rounded Rectangle"),
+            BoardElementValue::Circle(..) => String::from("Circle"),
+            BoardElementValue::Ellipse(..) => String::from("Ellipse"),
+            BoardElementValue::Triangle(..) => String::from("Triangle"),
+            BoardElementValue::Polyline(..) => String::from("Polyline"),
+            BoardElementValue::Bar(..) => String::from("Bar"),
+            BoardElementValue::Gauge(..) => String::from("Gauge"),
+            BoardElementValue::Graph(..) => String::from("Graph"),
+            BoardElementValue::Animation(..) => String::from("Animation"),
         }
     }
     pub fn get_types() -> Vec<String> {
-        "Text;Image;Pixel;Line".split(';').map(|x|x.to_string()).collect()
+        "Text;Image;Pixel;Line;Marquee;Text Box;Rectangle;Rounded Rectangle;Circle;Ellipse;Triangle;Polyline;Bar;Gauge;Graph;Animation".split(';').map(|x|x.to_string()).collect()
     }
     pub fn from_strings(type_string: &str, value: String, dynamic_img: bool) -> BoardElementValue {
         match type_string {
@@ -196,7 +319,259 @@ impl BoardElementValue {
             "Image" => BoardElementValue::Img(value, dynamic_img),
             "Pixel" => BoardElementValue::Pixel,
             "Line" => BoardElementValue::Line(0, 0, value),
+            "Marquee" => BoardElementValue::Marquee(value, MarqueeSettings::default()),
+            "Text Box" => BoardElementValue::TextBox(value, TextBoxSettings::default()),
+            "Rectangle" => BoardElementValue::Rect(16, 8, ShapeStyle::default()),
+            "Rounded Rectangle" => BoardElementValue::RoundedRect(16, 8, 3, ShapeStyle::default()),
+            "Circle" => BoardElementValue::Circle(8, ShapeStyle::default()),
+            "Ellipse" => BoardElementValue::Ellipse(16, 8, ShapeStyle::default()),
+            "Triangle" => BoardElementValue::Triangle((8, 8), (0, 8), ShapeStyle::default()),
+            "Polyline" => BoardElementValue::Polyline(vec![(8, 8)], ShapeStyle::default()),
+            "Bar" => BoardElementValue::Bar(value, 32, 4, BarDirection::default(), IndicatorSettings::default()),
+            "Gauge" => BoardElementValue::Gauge(value, 16, 2, IndicatorSettings::default()),
+            "Graph" => BoardElementValue::Graph(value, 32, 12, GraphSettings::default()),
+            "Animation" => BoardElementValue::Animation(value, AnimationSettings::default()),
             _ => BoardElementValue::Text(value),
         }
     }
 }
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, PartialOrd, Debug)]
+pub struct MarqueeSettings {
+    /// Pixels per second
+    pub speed: u8,
+    pub direction: ScrollDirection,
+    /// Milliseconds to hold the text still at each end of the scroll
+    pub pause: u16,
+    /// Number of times to scroll through the text before the board moves on
+    pub loops: u8,
+}
+impl Default for MarqueeSettings {
+    fn default() -> Self {
+        MarqueeSettings {
+            speed: 20,
+            direction: ScrollDirection::Left,
+            pause: 1000,
+            loops: 1,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, PartialOrd, Debug, Default)]
+pub enum ScrollDirection {
+    /// Text moves to the left, revealing the end of it
+    #[default]
+    Left,
+    /// Starts showing the end of the text and moves to the right to reveal the start
+    Right,
+}
+impl ScrollDirection {
+    pub fn get_option(&self) -> String {
+        format!("{:?}", self)
+    }
+    pub fn get_options() -> Vec<String> {
+        "Left;Right".split(';').map(|x|x.to_string()).collect()
+    }
+    pub fn from_option(type_str: &str) -> ScrollDirection {
+        match type_str {
+            "Right" => ScrollDirection::Right,
+            _ => ScrollDirection::Left,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, PartialOrd, Debug, Default)]
+pub struct TextBoxSettings {
+    /// Blank pixels between lines
+    pub line_spacing: u8,
+    pub overflow: TextOverflow,
+}
+
+/// What to do with wrapped text that has more lines than fit in the box
+#[derive(Serialize, Deserialize, Clone, PartialEq, PartialOrd, Debug, Default)]
+pub enum TextOverflow {
+    /// Cut the text off with "..." on the last line
+    #[default]
+    Ellipsis,
+    /// Switch to a smaller font, then cut the text off if it still does not fit
+    Shrink(String /* font */),
+    /// Show a box full of lines at a time, moving on every so many milliseconds
+    Page(u16 /* page time ms */),
+}
+impl TextOverflow {
+    pub fn get_option(&self) -> String {
+        match self {
+            TextOverflow::Ellipsis => TextOverflow::get_options()[0].clone(),
+            TextOverflow::Shrink(_) => TextOverflow::get_options()[1].clone(),
+            TextOverflow::Page(_) => TextOverflow::get_options()[2].clone(),
+        }
+    }
+    pub fn get_options() -> Vec<String> {
+        "Ellipsis;Shrink;Page".split(';').map(|x|x.to_string()).collect()
+    }
+    pub fn from_option(type_str: &str) -> TextOverflow {
+        match type_str {
+            "Shrink" => TextOverflow::Shrink(String::from("4x6")),
+            "Page" => TextOverflow::Page(3000),
+            _ => TextOverflow::Ellipsis,
+        }
+    }
+}
+
+/// How shape elements are drawn. The outline uses the element's colour and is drawn inside closed shapes.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, PartialOrd, Debug)]
+pub struct ShapeStyle {
+    /// Zero leaves the outline off
+    pub stroke_width: u8,
+    pub fill: Option<ElementColour>,
+}
+impl Default for ShapeStyle {
+    fn default() -> Self {
+        ShapeStyle {
+            stroke_width: 1,
+            fill: None,
+        }
+    }
+}
+
+/// Range and colours of bar and gauge elements
+#[derive(Serialize, Deserialize, Clone, PartialEq, PartialOrd, Debug)]
+pub struct IndicatorSettings {
+    pub min: f64,
+    pub max: f64,
+    /// Colours used from each value upwards. Values below every threshold use the element's colour.
+    pub thresholds: Vec<(f64, ElementColour)>,
+    /// Colour of the unfilled part
+    pub background: Option<ElementColour>,
+}
+impl Default for IndicatorSettings {
+    fn default() -> Self {
+        IndicatorSettings {
+            min: 0.,
+            max: 100.,
+            thresholds: Vec::new(),
+            background: Some(ElementColour { r: 0x30, g: 0x30, b: 0x30, a: 0xFF }),
+        }
+    }
+}
+
+/// Which way a bar fills up
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, PartialOrd, Debug, Default)]
+pub enum BarDirection {
+    #[default]
+    LeftToRight,
+    RightToLeft,
+    BottomToTop,
+    TopToBottom,
+}
+impl BarDirection {
+    pub fn get_option(&self) -> String {
+        match self {
+            BarDirection::LeftToRight => BarDirection::get_options()[0].clone(),
+            BarDirection::RightToLeft => BarDirection::get_options()[1].clone(),
+            BarDirection::BottomToTop => BarDirection::get_options()[2].clone(),
+            BarDirection::TopToBottom => BarDirection::get_options()[3].clone(),
+        }
+    }
+    pub fn get_options() -> Vec<String> {
+        "Left to Right;Right to Left;Bottom to Top;Top to Bottom".split(';').map(|x|x.to_string()).collect()
+    }
+    pub fn from_option(type_str: &str) -> BarDirection {
+        match type_str {
+            "Right to Left" => BarDirection::RightToLeft,
+            "Bottom to Top" => BarDirection::BottomToTop,
+            "Top to Bottom" => BarDirection::TopToBottom,
+            _ => BarDirection::LeftToRight,
+        }
+    }
+}
+
+/// How a graph element plots its variable's history
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, PartialOrd, Debug)]
+pub struct GraphSettings {
+    pub style: GraphStyle,
+    /// How many hours of history fit across the graph
+    pub hours: u16,
+    pub scale: GraphScale,
+}
+impl Default for GraphSettings {
+    fn default() -> Self {
+        GraphSettings {
+            style: GraphStyle::Line,
+            hours: 24,
+            scale: GraphScale::Auto,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, PartialOrd, Debug, Default)]
+pub enum GraphStyle {
+    #[default]
+    Line,
+    Bars,
+}
+impl GraphStyle {
+    pub fn get_option(&self) -> String {
+        match self {
+            GraphStyle::Line => GraphStyle::get_options()[0].clone(),
+            GraphStyle::Bars => GraphStyle::get_options()[1].clone(),
+        }
+    }
+    pub fn get_options() -> Vec<String> {
+        "Line;Bars".split(';').map(|x|x.to_string()).collect()
+    }
+    pub fn from_option(type_str: &str) -> GraphStyle {
+        match type_str {
+            "Bars" => GraphStyle::Bars,
+            _ => GraphStyle::Line,
+        }
+    }
+}
+
+/// The range of values between the bottom and top of a graph
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, PartialOrd, Debug, Default)]
+pub enum GraphScale {
+    /// Fit the lowest and highest values shown
+    #[default]
+    Auto,
+    Fixed(f64 /* min */, f64 /* max */),
+}
+impl GraphScale {
+    pub fn get_option(&self) -> String {
+        match self {
+            GraphScale::Auto => GraphScale::get_options()[0].clone(),
+            GraphScale::Fixed(..) => GraphScale::get_options()[1].clone(),
+        }
+    }
+    pub fn get_options() -> Vec<String> {
+        "Auto;Fixed".split(';').map(|x|x.to_string()).collect()
+    }
+    pub fn from_option(type_str: &str) -> GraphScale {
+        match type_str {
+            "Fixed" => GraphScale::Fixed(0., 100.),
+            _ => GraphScale::Auto,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, PartialOrd, Debug)]
+pub struct AnimationSettings {
+    /// Frames in a sprite strip, 0 for GIFs which carry their own frames
+    pub sprite_frames: u8,
+    /// Milliseconds each sprite strip frame is shown for
+    pub frame_time: u16,
+    /// Playback speed in percent
+    pub speed: u16,
+    /// Number of times to play through before the board moves on
+    pub loops: u8,
+}
+impl Default for AnimationSettings {
+    fn default() -> Self {
+        AnimationSettings {
+            sprite_frames: 0,
+            frame_time: 100,
+            speed: 100,
+            loops: 1,
+        }
+    }
+}
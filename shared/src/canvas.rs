@@ -0,0 +1,355 @@
+use std::collections::HashMap;
+
+use crate::{
+    shapes::{line_pixels, Shape},
+    text_layout::{glyph_pixels, LayoutBox},
+};
+
+#[cfg(target_arch = "wasm32")]
+use log::warn;
+
+#[cfg(not(target_arch = "wasm32"))]
+use tracing::warn;
+
+pub const COMMAND_LENGTH: usize = 10;
+
+/// Decoded RGB image data used when drawing `i` commands
+#[derive(Clone, Debug)]
+pub struct PixelImage {
+    pub width: u32,
+    pub height: u32,
+    pub pixels: Vec<[u8; 3]>,
+}
+
+/// Fonts and images referenced by a command stream
+pub trait CanvasAssets {
+    fn font(&self, font_name: &str) -> Option<&bdf2::Font>;
+    fn image(&self, image_id: &str) -> Option<&PixelImage>;
+}
+
+/// Simple asset store for callers that load everything up front
+#[derive(Default)]
+pub struct LoadedAssets {
+    pub fonts: HashMap<String, bdf2::Font>,
+    pub images: HashMap<String, PixelImage>,
+}
+impl CanvasAssets for LoadedAssets {
+    fn font(&self, font_name: &str) -> Option<&bdf2::Font> {
+        self.fonts.get(font_name)
+    }
+    fn image(&self, image_id: &str) -> Option<&PixelImage> {
+        self.images.get(image_id)
+    }
+}
+
+/// Software version of a matrix device, interprets the same commands a physical panel receives
+#[derive(Clone, Debug)]
+pub struct MatrixCanvas {
+    pub width: u32,
+    pub height: u32,
+    pub pixels: Vec<[u8; 3]>,
+    pub brightness: u8,
+    colour: [u8; 3],
+    font: String,
+    fill: Option<[u8; 3]>,
+    stroke_width: u32,
+    /// Points collected by `v` commands for the next triangle or polyline
+    vertices: Vec<(i32, i32)>,
+    /// (start, sweep) in degrees for the next arc, set by `u` commands
+    arc_angles: (i32, i32),
+}
+
+impl MatrixCanvas {
+    pub fn new(size: (u8, u8)) -> MatrixCanvas {
+        MatrixCanvas {
+            width: size.0 as u32,
+            height: size.1 as u32,
+            pixels: vec![[0; 3]; size.0 as usize * size.1 as usize],
+            brightness: 100,
+            colour: [0xFF; 3],
+            font: String::from("5x8"),
+            fill: None,
+            stroke_width: 1,
+            vertices: Vec::new(),
+            arc_angles: (0, 360),
+        }
+    }
+
+    /// Names of every font and image a command stream refers to as `(fonts, images)`
+    pub fn referenced_assets(commands: &[u8]) -> (Vec<String>, Vec<String>) {
+        let mut fonts = Vec::new();
+        let mut images = Vec::new();
+        for command in commands.chunks(COMMAND_LENGTH) {
+            match command.first() {
+                Some(b'f') => fonts.push(String::from_utf8_lossy(&command[1..]).trim_end_matches('=').to_string()),
+                Some(b'i') if command.len() == COMMAND_LENGTH => images.push(String::from_utf8_lossy(&command[5..]).trim_end_matches('=').to_string()),
+                _ => {}
+            }
+        }
+        fonts.sort();
+        fonts.dedup();
+        images.sort();
+        images.dedup();
+        (fonts, images)
+    }
+
+    /// Interprets a full command stream, skipping over any in-band asset payloads
+    pub fn interpret_all(&mut self, commands: &[u8], assets: &impl CanvasAssets) {
+        let mut pos = 0;
+        while pos + COMMAND_LENGTH <= commands.len() {
+            let command = String::from_utf8_lossy(&commands[pos..pos + COMMAND_LENGTH]).to_string();
+            pos += COMMAND_LENGTH;
+            if command.starts_with('a') {
+                // `a{kind}{length:08}`, then a 10 byte id and the payload itself
+                let length = command.get(2..).and_then(|length| length.parse::<usize>().ok()).unwrap_or(0);
+                pos += COMMAND_LENGTH + length;
+                continue;
+            }
+            self.interpret(&command, assets);
+        }
+    }
+
+    pub fn interpret(&mut self, command: &str, assets: &impl CanvasAssets) {
+        match command.chars().next() {
+            Some('b') => self.set_brightness(command),
+            Some('x') => self.clear(),
+            Some('c') => self.set_colour(command),
+            Some('l') => self.draw_line(command),
+            Some('p') => self.draw_pixel(command),
+            Some('q') => self.draw_coloured_pixel(command),
+            Some('f') => self.font = command.get(1..).unwrap_or_default().trim_end_matches('=').to_string(),
+            Some('t') | Some('j') => self.draw_character(command, assets),
+            Some('i') => self.draw_image(command, assets),
+            Some('d') => self.set_shape_style(command),
+            Some('r') | Some('R') | Some('o') | Some('e') => self.draw_closed_shape(command),
+            Some('v') => {
+                if let Some(pos) = parse_numbers::<2>(command) {
+                    self.vertices.push((pos[0], pos[1]));
+                }
+            }
+            Some('T') => {
+                let vertices = std::mem::take(&mut self.vertices);
+                if let [a, b, c, ..] = vertices[..] {
+                    self.draw_shape(&Shape::Triangle([a, b, c]));
+                }
+            }
+            Some('P') => {
+                let vertices = std::mem::take(&mut self.vertices);
+                self.draw_shape(&Shape::Polyline(vertices));
+            }
+            Some('u') => self.set_arc_angles(command),
+            Some('A') => match parse_numbers::<3>(command) {
+                Some(pos) => {
+                    let (start, sweep) = self.arc_angles;
+                    self.draw_shape(&Shape::Arc { x: pos[0], y: pos[1], diameter: pos[2] as u32, start, sweep });
+                }
+                None => warn!("Failed to parse arc: {}", command),
+            },
+            _ => {}
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.pixels.fill([0; 3]);
+    }
+
+    pub fn set_pixel(&mut self, x: i32, y: i32, colour: [u8; 3]) {
+        if x < 0 || y < 0 || x as u32 >= self.width || y as u32 >= self.height {
+            return;
+        }
+        self.pixels[(y as u32 * self.width + x as u32) as usize] = colour;
+    }
+
+    pub fn get_pixel(&self, x: u32, y: u32) -> [u8; 3] {
+        self.pixels[(y * self.width + x) as usize]
+    }
+
+    /// Pixels as they appear on the panel, with the current brightness applied
+    pub fn displayed_pixels(&self) -> Vec<[u8; 3]> {
+        let brightness = self.brightness.min(100) as u32;
+        self.pixels
+            .iter()
+            .map(|pixel| pixel.map(|channel| (channel as u32 * brightness / 100) as u8))
+            .collect()
+    }
+
+    pub fn draw_line_between(&mut self, start: (i32, i32), end: (i32, i32), colour: [u8; 3]) {
+        for (x, y) in line_pixels(start, end) {
+            self.set_pixel(x, y, colour);
+        }
+    }
+
+    /// Draws a shape with the current colour as its outline and the fill and stroke width from the last `d` command
+    pub fn draw_shape(&mut self, shape: &Shape) {
+        self.draw_shape_styled(shape, self.colour, self.stroke_width, self.fill);
+    }
+
+    pub fn draw_shape_styled(&mut self, shape: &Shape, stroke: [u8; 3], stroke_width: u32, fill: Option<[u8; 3]>) {
+        let pixels = shape.pixels(stroke_width);
+        if let Some(fill) = fill {
+            for (x, y) in pixels.fill {
+                self.set_pixel(x, y, fill);
+            }
+        }
+        for (x, y) in pixels.stroke {
+            self.set_pixel(x, y, stroke);
+        }
+    }
+
+    /// Draws a BDF glyph with the top of the font's bounding box at (x, y) and returns its width
+    pub fn draw_glyph(&mut self, font: &bdf2::Font, character: char, x: i32, y: i32, colour: [u8; 3]) -> u32 {
+        self.draw_glyph_clipped(font, character, x, y, colour, None)
+    }
+
+    /// Same as `draw_glyph`, leaving out any pixels outside of `clip`
+    pub fn draw_glyph_clipped(&mut self, font: &bdf2::Font, character: char, x: i32, y: i32, colour: [u8; 3], clip: Option<&LayoutBox>) -> u32 {
+        let Some(glyph) = font.glyphs().get(&character) else {
+            return 0;
+        };
+        for (pixel_x, pixel_y) in glyph_pixels(font, character, x, y) {
+            if clip.is_none_or(|clip| clip.contains(pixel_x, pixel_y)) {
+                self.set_pixel(pixel_x, pixel_y, colour);
+            }
+        }
+        glyph.width()
+    }
+
+    fn set_brightness(&mut self, command: &str) {
+        if let Some(Ok(brightness)) = command.get(1..4).map(|brightness| brightness.parse::<u8>()) {
+            self.brightness = brightness;
+        }
+    }
+
+    fn set_colour(&mut self, command: &str) {
+        match parse_colour(command, 1) {
+            Some(colour) => self.colour = colour,
+            None => warn!("Failed to parse colour: {}", command),
+        }
+    }
+
+    fn draw_line(&mut self, command: &str) {
+        let Some(pos) = parse_numbers::<4>(command) else {
+            return;
+        };
+        self.draw_line_between((pos[0], pos[1]), (pos[2], pos[3]), self.colour);
+    }
+
+    fn draw_pixel(&mut self, command: &str) {
+        let Some(pos) = parse_numbers::<2>(command) else {
+            return;
+        };
+        self.set_pixel(pos[0], pos[1], self.colour);
+    }
+
+    fn draw_coloured_pixel(&mut self, command: &str) {
+        let Some(pos) = parse_numbers::<2>(command) else {
+            return;
+        };
+        if let Some(colour) = parse_colour(command, 5) {
+            self.set_pixel(pos[0], pos[1], colour);
+        }
+    }
+
+    /// `d{fill colour}{filled}{stroke width:02}`
+    fn set_shape_style(&mut self, command: &str) {
+        let (Some(fill), Some(filled), Some(Ok(stroke_width))) =
+            (parse_colour(command, 1), command.get(4..5), command.get(5..7).map(|x| x.parse::<u32>()))
+        else {
+            warn!("Failed to parse shape style: {}", command);
+            return;
+        };
+        self.fill = (filled == "1").then_some(fill);
+        self.stroke_width = stroke_width;
+    }
+
+    /// `u{start:03}{sweep:03}`
+    fn set_arc_angles(&mut self, command: &str) {
+        let (Some(Ok(start)), Some(Ok(sweep))) =
+            (command.get(1..4).map(|x| x.parse::<i32>()), command.get(4..7).map(|x| x.parse::<i32>()))
+        else {
+            warn!("Failed to parse arc angles: {}", command);
+            return;
+        };
+        self.arc_angles = (start, sweep);
+    }
+
+    /// `r`/`e{x}{y}{width}{height}`, `R{x}{y}{width}{height}{radius:X}` and `o{x}{y}{diameter}`
+    fn draw_closed_shape(&mut self, command: &str) {
+        let shape = match command.chars().next() {
+            Some('o') => parse_numbers::<3>(command).map(|pos| Shape::Circle { x: pos[0], y: pos[1], diameter: pos[2] as u32 }),
+            Some(kind) => parse_numbers::<4>(command).and_then(|pos| {
+                let (x, y, width, height) = (pos[0], pos[1], pos[2] as u32, pos[3] as u32);
+                match kind {
+                    'r' => Some(Shape::Rect { x, y, width, height }),
+                    'e' => Some(Shape::Ellipse { x, y, width, height }),
+                    _ => {
+                        let radius = u32::from_str_radix(command.get(9..10)?, 16).ok()?;
+                        Some(Shape::RoundedRect { x, y, width, height, radius })
+                    }
+                }
+            }),
+            None => None,
+        };
+        match shape {
+            Some(shape) => self.draw_shape(&shape),
+            None => warn!("Failed to parse shape: {}", command),
+        }
+    }
+
+    fn draw_character(&mut self, command: &str, assets: &impl CanvasAssets) {
+        let Some(pos) = parse_numbers::<2>(command) else {
+            return;
+        };
+        let character = command.chars().nth(5).unwrap_or('_');
+        let character = if command.starts_with('j') {
+            match character {
+                '1' => '°',
+                _ => return,
+            }
+        } else {
+            character
+        };
+        let Some(font) = assets.font(&self.font) else {
+            warn!("Font ({}) is not available", &self.font);
+            return;
+        };
+        self.draw_glyph(font, character, pos[0], pos[1], self.colour);
+    }
+
+    fn draw_image(&mut self, command: &str, assets: &impl CanvasAssets) {
+        let Some(pos) = parse_numbers::<2>(command) else {
+            return;
+        };
+        let image_id = command.get(5..).unwrap_or_default().trim_end_matches('=');
+        let Some(image) = assets.image(image_id) else {
+            warn!("Image ({}) is not available", image_id);
+            return;
+        };
+        for y in 0..image.height {
+            for x in 0..image.width {
+                let colour = image.pixels[(y * image.width + x) as usize];
+                self.set_pixel(pos[0] + x as i32, pos[1] + y as i32, colour);
+            }
+        }
+    }
+}
+
+/// Parses the two digit numbers that follow the command character
+fn parse_numbers<const N: usize>(command: &str) -> Option<[i32; N]> {
+    let mut numbers = [0; N];
+    for (idx, number) in numbers.iter_mut().enumerate() {
+        let start = 1 + idx * 2;
+        *number = command.get(start..start + 2)?.parse::<i32>().ok()?;
+    }
+    Some(numbers)
+}
+
+/// Parses a 4 bit per channel colour (as sent by `c` and `q` commands) starting at `start`
+fn parse_colour(command: &str, start: usize) -> Option<[u8; 3]> {
+    let mut colour = [0; 3];
+    for (idx, channel) in colour.iter_mut().enumerate() {
+        let channel_str = command.get(start + idx..start + idx + 1)?;
+        *channel = u8::from_str_radix(channel_str, 16).ok()? * 0x10;
+    }
+    Some(colour)
+}
@@ -0,0 +1,234 @@
+//! Time left until a moment, or since it, for variables like "days until vacation", "time since the last deploy" or a
+//! countdown to the next meeting.
+
+use chrono::{DateTime, Datelike, Days, Local, NaiveDate, NaiveDateTime, NaiveTime, TimeZone, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::board_variables::parse_time_zone;
+
+/// How fixed targets and target variables' values can be written, for help text
+pub const COUNTDOWN_TARGET_HELP: &str = "2026-12-24T17:00:00+01:00, 2026-12-24 17:00 or 2026-12-24\n\
+    Times without an offset are in the countdown's time zone\n\
+    Unix timestamps in seconds or milliseconds work too";
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+pub struct CountdownData {
+    pub target: CountdownTarget,
+    pub units: CountdownUnits,
+    pub after_expiry: AfterExpiry,
+    /// IANA name used for targets without an offset, repeating targets and counting days. When empty the device's
+    /// time zone is used, then the server's.
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub time_zone: String,
+}
+
+impl Default for CountdownData {
+    fn default() -> Self {
+        CountdownData {
+            target: CountdownTarget::from_option("Fixed Time"),
+            units: CountdownUnits::Days,
+            after_expiry: AfterExpiry::Zero,
+            time_zone: String::new(),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+pub enum CountdownTarget {
+    /// A timestamp, see `COUNTDOWN_TARGET_HELP`
+    Fixed(String),
+    /// The next time of day (`17:00`) on one of the checked weekdays, Monday first. Once it passes the countdown
+    /// moves on to the next one, so it never expires.
+    Repeating { time: String, weekdays: [bool; 7] },
+    /// The value of another variable, like an ISO timestamp from a JSON extractor, read like a fixed target
+    Variable(String),
+}
+impl CountdownTarget {
+    pub fn get_option(&self) -> String {
+        match self {
+            CountdownTarget::Fixed(_) => CountdownTarget::get_options()[0].clone(),
+            CountdownTarget::Repeating { .. } => CountdownTarget::get_options()[1].clone(),
+            CountdownTarget::Variable(_) => CountdownTarget::get_options()[2].clone(),
+        }
+    }
+    pub fn get_options() -> Vec<String> {
+        "Fixed Time;Repeating;From Variable".split(';').map(|x|x.to_string()).collect()
+    }
+    pub fn from_option(type_str: &str) -> CountdownTarget {
+        match type_str {
+            "Repeating" => CountdownTarget::Repeating {
+                time: String::from("17:00"),
+                weekdays: [true, true, true, true, true, false, false],
+            },
+            "From Variable" => CountdownTarget::Variable(String::new()),
+            _ => CountdownTarget::Fixed(format!("{}-12-25", Utc::now().year())),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug, Default)]
+pub enum CountdownUnits {
+    /// Calendar days, so a target tomorrow is 1 day away whatever the time
+    #[default]
+    Days,
+    /// Hours and minutes, `53:07`
+    HoursMinutes,
+    /// Minutes and seconds, `05:42`
+    MinutesSeconds,
+    /// The largest whole unit, `3 days`, `5 hours` or `42 seconds`
+    Humanized,
+}
+impl CountdownUnits {
+    pub fn get_option(&self) -> String {
+        match self {
+            CountdownUnits::Days => CountdownUnits::get_options()[0].clone(),
+            CountdownUnits::HoursMinutes => CountdownUnits::get_options()[1].clone(),
+            CountdownUnits::MinutesSeconds => CountdownUnits::get_options()[2].clone(),
+            CountdownUnits::Humanized => CountdownUnits::get_options()[3].clone(),
+        }
+    }
+    pub fn get_options() -> Vec<String> {
+        "Days;h:mm;mm:ss;Humanized".split(';').map(|x|x.to_string()).collect()
+    }
+    pub fn from_option(type_str: &str) -> CountdownUnits {
+        match type_str {
+            "h:mm" => CountdownUnits::HoursMinutes,
+            "mm:ss" => CountdownUnits::MinutesSeconds,
+            "Humanized" => CountdownUnits::Humanized,
+            _ => CountdownUnits::Days,
+        }
+    }
+
+    /// `seconds` and `days` are how far apart the target and now are, never negative
+    fn format(&self, seconds: i64, days: i64) -> String {
+        match self {
+            CountdownUnits::Days => days.to_string(),
+            CountdownUnits::HoursMinutes => format!("{}:{:02}", seconds / 3600, seconds % 3600 / 60),
+            CountdownUnits::MinutesSeconds => format!("{:02}:{:02}", seconds / 60, seconds % 60),
+            CountdownUnits::Humanized => {
+                let (count, unit) = match seconds {
+                    s if s >= 86400 => (s / 86400, "day"),
+                    s if s >= 3600 => (s / 3600, "hour"),
+                    s if s >= 60 => (s / 60, "minute"),
+                    s => (s, "second"),
+                };
+                format!("{} {}{}", count, unit, if count == 1 { "" } else { "s" })
+            }
+        }
+    }
+}
+
+/// What a countdown shows once its target has passed
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug, Default)]
+pub enum AfterExpiry {
+    /// Shows zero in the countdown's units
+    #[default]
+    Zero,
+    /// Counts the time since the target, e.g. since the last deploy
+    CountUp,
+    /// Shows some text instead, like "Now!", or nothing when empty
+    Text(String),
+}
+impl AfterExpiry {
+    pub fn get_option(&self) -> String {
+        match self {
+            AfterExpiry::Zero => AfterExpiry::get_options()[0].clone(),
+            AfterExpiry::CountUp => AfterExpiry::get_options()[1].clone(),
+            AfterExpiry::Text(_) => AfterExpiry::get_options()[2].clone(),
+        }
+    }
+    pub fn get_options() -> Vec<String> {
+        "Show Zero;Count Up;Show Text".split(';').map(|x|x.to_string()).collect()
+    }
+    pub fn from_option(type_str: &str) -> AfterExpiry {
+        match type_str {
+            "Count Up" => AfterExpiry::CountUp,
+            "Show Text" => AfterExpiry::Text(String::from("Now!")),
+            _ => AfterExpiry::Zero,
+        }
+    }
+}
+
+impl CountdownData {
+    /// The countdown at `now` in its time zone, or `device_time_zone` when it has none. `target_value` is the value of
+    /// the target variable, for targets that use one.
+    pub fn format(&self, now: &DateTime<Utc>, device_time_zone: &str, target_value: Option<&str>) -> Result<String, String> {
+        let time_zone = if self.time_zone.is_empty() { device_time_zone } else { &self.time_zone };
+        if time_zone.is_empty() {
+            return self.format_in(now.with_timezone(&Local), target_value);
+        }
+        let time_zone = parse_time_zone(time_zone)?;
+        self.format_in(now.with_timezone(&time_zone), target_value)
+    }
+
+    /// A representative (and deliberately wide) value for the units
+    pub fn sample(&self) -> String {
+        match self.units {
+            CountdownUnits::Days => String::from("128"),
+            CountdownUnits::HoursMinutes => String::from("53:07"),
+            CountdownUnits::MinutesSeconds => String::from("42:15"),
+            CountdownUnits::Humanized => String::from("12 minutes"),
+        }
+    }
+
+    fn format_in<Tz: TimeZone>(&self, now: DateTime<Tz>, target_value: Option<&str>) -> Result<String, String> {
+        let target = match &self.target {
+            CountdownTarget::Fixed(text) => parse_timestamp(text, &now.timezone())?,
+            CountdownTarget::Repeating { time, weekdays } => next_occurrence(&now, time, weekdays)?,
+            CountdownTarget::Variable(name) => {
+                let value = target_value.ok_or_else(|| format!("No value for the target variable '{}'", name))?;
+                parse_timestamp(value, &now.timezone())?
+            }
+        };
+        let seconds = target.clone().signed_duration_since(now.clone()).num_seconds();
+        let days = (target.date_naive() - now.date_naive()).num_days();
+        if seconds >= 0 {
+            return Ok(self.units.format(seconds, days));
+        }
+        Ok(match &self.after_expiry {
+            AfterExpiry::Zero => self.units.format(0, 0),
+            AfterExpiry::CountUp => self.units.format(-seconds, -days),
+            AfterExpiry::Text(text) => text.clone(),
+        })
+    }
+}
+
+/// A moment written as described in `COUNTDOWN_TARGET_HELP`, with times without an offset taken to be in `time_zone`
+pub fn parse_timestamp<Tz: TimeZone>(text: &str, time_zone: &Tz) -> Result<DateTime<Tz>, String> {
+    let text = text.trim().trim_matches('"');
+    if let Ok(time) = DateTime::parse_from_rfc3339(text).or_else(|_| DateTime::parse_from_str(text, "%Y-%m-%dT%H:%M:%S%.f%z")) {
+        return Ok(time.with_timezone(time_zone));
+    }
+    if let Ok(timestamp) = text.parse::<i64>() {
+        // Anything past the year 5138 in seconds is more likely to be milliseconds
+        let time = if timestamp.abs() >= 100_000_000_000 {
+            DateTime::from_timestamp_millis(timestamp)
+        } else {
+            DateTime::from_timestamp(timestamp, 0)
+        };
+        return time.map(|time| time.with_timezone(time_zone)).ok_or_else(|| format!("'{}' is out of range", text));
+    }
+    let local = ["%Y-%m-%dT%H:%M:%S%.f", "%Y-%m-%d %H:%M:%S%.f", "%Y-%m-%dT%H:%M", "%Y-%m-%d %H:%M"]
+        .iter()
+        .find_map(|format| NaiveDateTime::parse_from_str(text, format).ok())
+        .or_else(|| NaiveDate::parse_from_str(text, "%Y-%m-%d").ok().and_then(|date| date.and_hms_opt(0, 0, 0)))
+        .ok_or_else(|| format!("'{}' is not a date or time", text))?;
+    time_zone.from_local_datetime(&local).earliest().ok_or_else(|| format!("'{}' doesn't exist in this time zone", text))
+}
+
+/// The first `time` on one of `weekdays` after `now`
+fn next_occurrence<Tz: TimeZone>(now: &DateTime<Tz>, time: &str, weekdays: &[bool; 7]) -> Result<DateTime<Tz>, String> {
+    let time = NaiveTime::parse_from_str(time.trim(), "%H:%M")
+        .or_else(|_| NaiveTime::parse_from_str(time.trim(), "%H:%M:%S"))
+        .map_err(|_| format!("'{}' is not a time like 17:00", time))?;
+    if !weekdays.contains(&true) {
+        return Err(String::from("A repeating target needs at least one day"));
+    }
+    // A week and a day ahead covers today's time having passed on the only checked day
+    (0..=7)
+        .filter_map(|offset| now.date_naive().checked_add_days(Days::new(offset)))
+        .filter(|date| weekdays[date.weekday().num_days_from_monday() as usize])
+        .filter_map(|date| now.timezone().from_local_datetime(&date.and_time(time)).earliest())
+        .find(|target| target > now)
+        .ok_or_else(|| String::from("No upcoming time for the repeating target"))
+}
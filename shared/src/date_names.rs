@@ -0,0 +1,174 @@
+//! Weekday, month and AM/PM names for DateTime variables, in a handful of built-in languages or from tables in the
+//! config. Custom tables are handy for small fonts, which need hand-tuned abbreviations or names without accents.
+
+use std::collections::HashMap;
+
+use chrono::{format::{Fixed, Item}, DateTime, Datelike, TimeZone, Timelike};
+use serde::{Deserialize, Serialize};
+
+/// Locale used when neither a variable nor its device has one
+pub const DEFAULT_LOCALE: &str = "en";
+
+/// Custom name tables from the config, by the locale name variables and devices use to pick them
+pub type DateNameTables = HashMap<String, DateNames>;
+
+/// Names in weekday order from Monday and month order from January. In custom tables any list can be left empty to
+/// use the names of the `base` locale.
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug, Default)]
+pub struct DateNames {
+    /// Built-in locale the table starts from, English when empty
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub base: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub weekdays: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub short_weekdays: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub months: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub short_months: Vec<String>,
+    /// Shown for `%p`, morning first
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub am_pm: Vec<String>,
+}
+
+struct BuiltInNames {
+    name: &'static str,
+    weekdays: [&'static str; 7],
+    short_weekdays: [&'static str; 7],
+    months: [&'static str; 12],
+    short_months: [&'static str; 12],
+    am_pm: [&'static str; 2],
+}
+
+const BUILT_IN_LOCALES: [(&str, BuiltInNames); 7] = [
+    ("en", BuiltInNames {
+        name: "English",
+        weekdays: ["Monday", "Tuesday", "Wednesday", "Thursday", "Friday", "Saturday", "Sunday"],
+        short_weekdays: ["Mon", "Tue", "Wed", "Thu", "Fri", "Sat", "Sun"],
+        months: ["January", "February", "March", "April", "May", "June", "July", "August", "September", "October", "November", "December"],
+        short_months: ["Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec"],
+        am_pm: ["AM", "PM"],
+    }),
+    ("de", BuiltInNames {
+        name: "Deutsch",
+        weekdays: ["Montag", "Dienstag", "Mittwoch", "Donnerstag", "Freitag", "Samstag", "Sonntag"],
+        short_weekdays: ["Mo", "Di", "Mi", "Do", "Fr", "Sa", "So"],
+        months: ["Januar", "Februar", "März", "April", "Mai", "Juni", "Juli", "August", "September", "Oktober", "November", "Dezember"],
+        short_months: ["Jan", "Feb", "Mär", "Apr", "Mai", "Jun", "Jul", "Aug", "Sep", "Okt", "Nov", "Dez"],
+        am_pm: ["AM", "PM"],
+    }),
+    ("es", BuiltInNames {
+        name: "Español",
+        weekdays: ["lunes", "martes", "miércoles", "jueves", "viernes", "sábado", "domingo"],
+        short_weekdays: ["lun", "mar", "mié", "jue", "vie", "sáb", "dom"],
+        months: ["enero", "febrero", "marzo", "abril", "mayo", "junio", "julio", "agosto", "septiembre", "octubre", "noviembre", "diciembre"],
+        short_months: ["ene", "feb", "mar", "abr", "may", "jun", "jul", "ago", "sept", "oct", "nov", "dic"],
+        am_pm: ["a. m.", "p. m."],
+    }),
+    ("fr", BuiltInNames {
+        name: "Français",
+        weekdays: ["lundi", "mardi", "mercredi", "jeudi", "vendredi", "samedi", "dimanche"],
+        short_weekdays: ["lun.", "mar.", "mer.", "jeu.", "ven.", "sam.", "dim."],
+        months: ["janvier", "février", "mars", "avril", "mai", "juin", "juillet", "août", "septembre", "octobre", "novembre", "décembre"],
+        short_months: ["janv.", "févr.", "mars", "avr.", "mai", "juin", "juil.", "août", "sept.", "oct.", "nov.", "déc."],
+        am_pm: ["AM", "PM"],
+    }),
+    ("it", BuiltInNames {
+        name: "Italiano",
+        weekdays: ["lunedì", "martedì", "mercoledì", "giovedì", "venerdì", "sabato", "domenica"],
+        short_weekdays: ["lun", "mar", "mer", "gio", "ven", "sab", "dom"],
+        months: ["gennaio", "febbraio", "marzo", "aprile", "maggio", "giugno", "luglio", "agosto", "settembre", "ottobre", "novembre", "dicembre"],
+        short_months: ["gen", "feb", "mar", "apr", "mag", "giu", "lug", "ago", "set", "ott", "nov", "dic"],
+        am_pm: ["AM", "PM"],
+    }),
+    ("nl", BuiltInNames {
+        name: "Nederlands",
+        weekdays: ["maandag", "dinsdag", "woensdag", "donderdag", "vrijdag", "zaterdag", "zondag"],
+        short_weekdays: ["ma", "di", "wo", "do", "vr", "za", "zo"],
+        months: ["januari", "februari", "maart", "april", "mei", "juni", "juli", "augustus", "september", "oktober", "november", "december"],
+        short_months: ["jan", "feb", "mrt", "apr", "mei", "jun", "jul", "aug", "sep", "okt", "nov", "dec"],
+        am_pm: ["a.m.", "p.m."],
+    }),
+    ("pt", BuiltInNames {
+        name: "Português",
+        weekdays: ["segunda-feira", "terça-feira", "quarta-feira", "quinta-feira", "sexta-feira", "sábado", "domingo"],
+        short_weekdays: ["seg", "ter", "qua", "qui", "sex", "sáb", "dom"],
+        months: ["janeiro", "fevereiro", "março", "abril", "maio", "junho", "julho", "agosto", "setembro", "outubro", "novembro", "dezembro"],
+        short_months: ["jan", "fev", "mar", "abr", "mai", "jun", "jul", "ago", "set", "out", "nov", "dez"],
+        am_pm: ["AM", "PM"],
+    }),
+];
+
+/// Codes and names of the built-in locales, like `("de", "Deutsch")`
+pub fn built_in_locales() -> impl Iterator<Item = (&'static str, &'static str)> {
+    BUILT_IN_LOCALES.iter().map(|(code, names)| (*code, names.name))
+}
+
+/// A built-in locale by its code, ignoring any region, so `de_AT` and `es-MX` work
+fn built_in(locale: &str) -> Option<&'static BuiltInNames> {
+    let language = locale.split(['-', '_']).next().unwrap_or_default().to_lowercase();
+    BUILT_IN_LOCALES.iter().find(|(code, _)| *code == language).map(|(_, names)| names)
+}
+
+impl DateNames {
+    /// The full set of names for `locale`, a custom table's name or a built-in locale code
+    pub fn for_locale(locale: &str, custom: &DateNameTables) -> Result<DateNames, String> {
+        let Some(table) = custom.get(locale) else {
+            return built_in(locale).map(DateNames::from).ok_or_else(|| format!("Unknown locale '{}'", locale));
+        };
+        let base = if table.base.is_empty() { DEFAULT_LOCALE } else { &table.base };
+        let mut names = built_in(base).map(DateNames::from)
+            .ok_or_else(|| format!("Unknown base locale '{}' for names '{}'", base, locale))?;
+        let lists = [
+            (&table.weekdays, &mut names.weekdays, "weekdays"),
+            (&table.short_weekdays, &mut names.short_weekdays, "short_weekdays"),
+            (&table.months, &mut names.months, "months"),
+            (&table.short_months, &mut names.short_months, "short_months"),
+            (&table.am_pm, &mut names.am_pm, "am_pm"),
+        ];
+        for (custom, names, list) in lists {
+            if custom.is_empty() {
+                continue;
+            }
+            if custom.len() != names.len() {
+                return Err(format!("'{}' in names '{}' needs {} names, not {}", list, locale, names.len(), custom.len()));
+            }
+            names.clone_from(custom);
+        }
+        Ok(names)
+    }
+
+    /// Replaces the name parts of a format (`%A`, `%b`, `%p`...) with this table's names for `time`
+    pub fn localize<'a, Tz: TimeZone>(&self, items: Vec<Item<'a>>, time: &DateTime<Tz>) -> Vec<Item<'a>> {
+        let weekday = time.weekday().num_days_from_monday() as usize;
+        let month = time.month0() as usize;
+        let pm = time.hour() >= 12;
+        items.into_iter().map(|item| {
+            let name = match &item {
+                Item::Fixed(Fixed::LongWeekdayName) => self.weekdays[weekday].clone(),
+                Item::Fixed(Fixed::ShortWeekdayName) => self.short_weekdays[weekday].clone(),
+                Item::Fixed(Fixed::LongMonthName) => self.months[month].clone(),
+                Item::Fixed(Fixed::ShortMonthName) => self.short_months[month].clone(),
+                Item::Fixed(Fixed::UpperAmPm) => self.am_pm[pm as usize].clone(),
+                Item::Fixed(Fixed::LowerAmPm) => self.am_pm[pm as usize].to_lowercase(),
+                _ => return item,
+            };
+            Item::OwnedLiteral(name.into_boxed_str())
+        }).collect()
+    }
+}
+
+impl From<&BuiltInNames> for DateNames {
+    fn from(names: &BuiltInNames) -> Self {
+        let owned = |names: &[&str]| names.iter().map(|name| name.to_string()).collect();
+        DateNames {
+            base: String::new(),
+            weekdays: owned(&names.weekdays),
+            short_weekdays: owned(&names.short_weekdays),
+            months: owned(&names.months),
+            short_months: owned(&names.short_months),
+            am_pm: owned(&names.am_pm),
+        }
+    }
+}
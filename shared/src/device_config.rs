@@ -1,6 +1,6 @@
 use std::collections::HashMap;
 
-use chrono::Timelike;
+use chrono::{DateTime, Local, Timelike};
 use serde::{Deserialize, Serialize};
 
 #[cfg(target_arch = "wasm32")]
@@ -9,7 +9,7 @@ use log::warn;
 #[cfg(not(target_arch = "wasm32"))]
 use tracing::warn;
 
-use crate::boards::ElementColour;
+use crate::{boards::ElementColour, transitions::BoardTransition};
 
 pub type DeviceConfigs = HashMap<String, DeviceConfig>;
 pub type Brightnesses = Vec<Brightness>;
@@ -24,6 +24,18 @@ pub struct DeviceConfig {
     #[serde(alias="picture_of_the_day_brightness_threshold")]
     pub skip_brightness_threshold: u8,
     pub proto_version: u64,
+    /// How every board in the rotation is switched to
+    #[serde(default)]
+    pub transition: BoardTransition,
+    /// Transitions used instead of `transition` when switching to particular boards, by board name
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub board_transitions: HashMap<String, BoardTransition>,
+    /// IANA name like `Europe/London` for DateTime variables shown on this device, the server's when empty
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub time_zone: String,
+    /// Language of weekday and month names for DateTime variables shown on this device, English when empty
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub locale: String,
 }
 impl Default for DeviceConfig {
     fn default() -> Self {
@@ -35,9 +47,24 @@ impl Default for DeviceConfig {
             brightness: Vec::new(),
             skip_brightness_threshold: 25,
             proto_version: 0,
+            transition: BoardTransition::default(),
+            board_transitions: HashMap::new(),
+            time_zone: String::new(),
+            locale: String::new(),
         }
     }
 }
+impl DeviceConfig {
+    /// The transition used when switching to `board_name`
+    pub fn transition_to(&self, board_name: &str) -> BoardTransition {
+        self.board_transitions.get(board_name).copied().unwrap_or(self.transition)
+    }
+
+    /// Whether switching to any board plays a transition
+    pub fn has_transitions(&self) -> bool {
+        self.transition.is_active() || self.board_transitions.values().any(|x| x.is_active())
+    }
+}
 
 #[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct Brightness {
@@ -54,7 +81,11 @@ impl Default for Brightness {
 }
 
 pub fn get_current_brightness(brightnesses: &Brightnesses) -> u8 {
-    let current_time = cur_time_ms();
+    get_brightness_at(brightnesses, &chrono::Local::now())
+}
+
+pub fn get_brightness_at(brightnesses: &Brightnesses, datetime: &DateTime<Local>) -> u8 {
+    let current_time = time_ms(datetime);
     for brightness in brightnesses {
         let time = parse_time_string(&brightness.time);
         if current_time < time {
@@ -152,6 +183,9 @@ pub fn parse_time_string(time: &str) -> u32 {
 }
 
 pub fn cur_time_ms() -> u32 {
-    let datetime = chrono::Local::now();
+    time_ms(&chrono::Local::now())
+}
+
+fn time_ms(datetime: &DateTime<Local>) -> u32 {
     (datetime.hour() * 3600000) + (datetime.minute() * 60000) + (datetime.second() * 1000)
 }
@@ -0,0 +1,616 @@
+//! Expressions computed from other variables' values, like `round((temp_k - 273.15) * 9 / 5 + 32, 1)`
+//! or `if(battery < 20, "LOW", battery & "%")`.
+//!
+//! Variables are referred to by name, or as `{name}` when the name has characters other than letters, digits
+//! and `_`. Their values are numbers when they look like one, and text otherwise.
+
+use std::{collections::HashSet, fmt};
+
+use crate::{board_variables::{BoardVariable, BoardVariables}, countdowns::{CountdownData, CountdownTarget}, indicators::parse_number};
+
+/// Operators and functions expressions can use, for help text
+pub const EXPRESSION_HELP: &str = "Arithmetic: + - * / % ^ (+ joins text, & always joins)
+Comparisons: == != < <= > >=, logic: && || !, conditionals: a ? b : c
+Text in quotes: \"text\" or 'text', other variables by name or as {name}
+Functions: abs, round(x[, decimals]), floor, ceil, sqrt, pow(x, y), min, max, sum, avg,
+clamp(x, low, high), if(condition, then, else), len, upper, lower, contains(text, part),
+number(text) (first number in text), now() (unix seconds), timestamp(date) (unix seconds)";
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct ExpressionError(pub String);
+
+impl fmt::Display for ExpressionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for ExpressionError {}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Value {
+    Number(f64),
+    Text(String),
+    Bool(bool),
+}
+
+impl Value {
+    /// Variable values are text, anything that reads as a number is used as one
+    fn from_variable(value: String) -> Value {
+        match value.trim().parse::<f64>() {
+            Ok(number) if number.is_finite() => Value::Number(number),
+            _ => Value::Text(value),
+        }
+    }
+
+    fn number(&self) -> Result<f64, ExpressionError> {
+        match self {
+            Value::Number(number) => Ok(*number),
+            Value::Bool(value) => Ok(if *value { 1. } else { 0. }),
+            Value::Text(text) => Err(ExpressionError(format!("'{}' is not a number", text))),
+        }
+    }
+
+    fn truthy(&self) -> bool {
+        match self {
+            Value::Number(number) => *number != 0.,
+            Value::Bool(value) => *value,
+            Value::Text(text) => !text.is_empty(),
+        }
+    }
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Value::Number(number) => f.write_str(&format_number(*number)),
+            Value::Text(text) => f.write_str(text),
+            Value::Bool(value) => write!(f, "{}", value),
+        }
+    }
+}
+
+/// Whole numbers without a decimal point, others without floating point noise like 0.30000000000000004
+fn format_number(number: f64) -> String {
+    if number.fract() == 0. && number.abs() < 1e15 {
+        return format!("{}", number as i64);
+    }
+    let text = format!("{:.10}", number);
+    text.trim_end_matches('0').trim_end_matches('.').to_string()
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Operator {
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+    Remainder,
+    Power,
+    Join,
+    Equal,
+    NotEqual,
+    Less,
+    LessOrEqual,
+    Greater,
+    GreaterOrEqual,
+    And,
+    Or,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Node {
+    Literal(Value),
+    Variable(String),
+    Negate(Box<Node>),
+    Not(Box<Node>),
+    Binary(Operator, Box<Node>, Box<Node>),
+    Conditional(Box<Node>, Box<Node>, Box<Node>),
+    Call(String, Vec<Node>),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Expression {
+    root: Node,
+}
+
+impl Expression {
+    pub fn parse(text: &str) -> Result<Expression, ExpressionError> {
+        let mut parser = Parser { chars: text.chars().collect(), pos: 0 };
+        let root = parser.conditional()?;
+        parser.skip_whitespace();
+        if parser.pos < parser.chars.len() {
+            return Err(parser.error("Unexpected text"));
+        }
+        Ok(Expression { root })
+    }
+
+    /// Names of the variables the expression uses
+    pub fn variables(&self) -> Vec<String> {
+        let mut names = Vec::new();
+        collect_variables(&self.root, &mut names);
+        names
+    }
+
+    /// `lookup` gives the value of a variable, or `None` when there is no such variable.
+    /// `now` is the unix timestamp `now()` returns.
+    pub fn evaluate(&self, now: i64, lookup: &impl Fn(&str) -> Option<String>) -> Result<Value, ExpressionError> {
+        evaluate(&self.root, now, lookup)
+    }
+}
+
+/// A chain of variables the expression uses, through expressions and countdown targets, that leads back round to
+/// itself, e.g. `["a", "b", "a"]`
+pub fn find_cycle(expression: &Expression, variables: &BoardVariables) -> Option<Vec<String>> {
+    let mut finished = HashSet::new();
+    expression.variables().iter().find_map(|name| visit_dependencies(name, variables, &mut Vec::new(), &mut finished))
+}
+
+/// Like `find_cycle`, for a variable that uses `name`, like a countdown whose target comes from another variable
+pub fn find_variable_cycle(name: &str, variables: &BoardVariables) -> Option<Vec<String>> {
+    visit_dependencies(name, variables, &mut Vec::new(), &mut HashSet::new())
+}
+
+/// Variables that evaluating a variable evaluates first
+pub fn variable_dependencies(variable: &BoardVariable) -> Vec<String> {
+    match variable {
+        BoardVariable::Expression(text) => Expression::parse(text).map(|expression| expression.variables()).unwrap_or_default(),
+        BoardVariable::Countdown(CountdownData { target: CountdownTarget::Variable(name), .. }) => vec![name.clone()],
+        _ => Vec::new(),
+    }
+}
+
+fn visit_dependencies(name: &str, variables: &BoardVariables, path: &mut Vec<String>, finished: &mut HashSet<String>) -> Option<Vec<String>> {
+    if let Some(start) = path.iter().position(|visited| visited == name) {
+        let mut cycle = path[start..].to_vec();
+        cycle.push(name.to_string());
+        return Some(cycle);
+    }
+    if finished.contains(name) {
+        return None;
+    }
+    let variable = variables.get(name)?;
+    path.push(name.to_string());
+    for dependency in variable_dependencies(variable) {
+        if let Some(cycle) = visit_dependencies(&dependency, variables, path, finished) {
+            return Some(cycle);
+        }
+    }
+    path.pop();
+    finished.insert(name.to_string());
+    None
+}
+
+fn collect_variables(node: &Node, names: &mut Vec<String>) {
+    match node {
+        Node::Literal(_) => {}
+        Node::Variable(name) => {
+            if !names.contains(name) {
+                names.push(name.clone());
+            }
+        }
+        Node::Negate(inner) | Node::Not(inner) => collect_variables(inner, names),
+        Node::Binary(_, left, right) => {
+            collect_variables(left, names);
+            collect_variables(right, names);
+        }
+        Node::Conditional(condition, then, otherwise) => {
+            collect_variables(condition, names);
+            collect_variables(then, names);
+            collect_variables(otherwise, names);
+        }
+        Node::Call(_, args) => args.iter().for_each(|arg| collect_variables(arg, names)),
+    }
+}
+
+struct Parser {
+    chars: Vec<char>,
+    pos: usize,
+}
+
+impl Parser {
+    fn error(&self, message: &str) -> ExpressionError {
+        match self.chars.get(self.pos) {
+            Some(c) => ExpressionError(format!("{} '{}' at character {}", message, c, self.pos + 1)),
+            None => ExpressionError(format!("{} at the end", message)),
+        }
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self.chars.get(self.pos).is_some_and(|c| c.is_whitespace()) {
+            self.pos += 1;
+        }
+    }
+
+    /// Consumes `token` if it comes next, ignoring whitespace before it
+    fn eat(&mut self, token: &str) -> bool {
+        self.skip_whitespace();
+        let matches = token.chars().enumerate().all(|(i, c)| self.chars.get(self.pos + i) == Some(&c));
+        if matches {
+            self.pos += token.chars().count();
+        }
+        matches
+    }
+
+    /// Consumes `token` unless it is the start of one of the longer tokens in `unless`, like `&` in `&&`
+    fn eat_only(&mut self, token: &str, unless: &[&str]) -> bool {
+        let start = self.pos;
+        if unless.iter().any(|longer| self.eat(longer)) {
+            self.pos = start;
+            return false;
+        }
+        self.eat(token)
+    }
+
+    fn expect(&mut self, token: &str) -> Result<(), ExpressionError> {
+        if self.eat(token) { Ok(()) } else { Err(self.error(&format!("Expected '{}', found", token))) }
+    }
+
+    fn conditional(&mut self) -> Result<Node, ExpressionError> {
+        let condition = self.or()?;
+        if !self.eat("?") {
+            return Ok(condition);
+        }
+        let then = self.conditional()?;
+        self.expect(":")?;
+        let otherwise = self.conditional()?;
+        Ok(Node::Conditional(Box::new(condition), Box::new(then), Box::new(otherwise)))
+    }
+
+    /// Parses a left associative chain of operators that bind tighter than `next`
+    fn binary(
+        &mut self,
+        operators: &[(&str, &[&str], Operator)],
+        next: fn(&mut Self) -> Result<Node, ExpressionError>,
+    ) -> Result<Node, ExpressionError> {
+        let mut left = next(self)?;
+        'chain: loop {
+            for (token, unless, operator) in operators {
+                if self.eat_only(token, unless) {
+                    let right = next(self)?;
+                    left = Node::Binary(*operator, Box::new(left), Box::new(right));
+                    continue 'chain;
+                }
+            }
+            return Ok(left);
+        }
+    }
+
+    fn or(&mut self) -> Result<Node, ExpressionError> {
+        self.binary(&[("||", &[], Operator::Or)], Self::and)
+    }
+
+    fn and(&mut self) -> Result<Node, ExpressionError> {
+        self.binary(&[("&&", &[], Operator::And)], Self::equality)
+    }
+
+    fn equality(&mut self) -> Result<Node, ExpressionError> {
+        self.binary(&[("==", &[], Operator::Equal), ("!=", &[], Operator::NotEqual)], Self::comparison)
+    }
+
+    fn comparison(&mut self) -> Result<Node, ExpressionError> {
+        self.binary(&[
+            ("<=", &[], Operator::LessOrEqual),
+            (">=", &[], Operator::GreaterOrEqual),
+            ("<", &[], Operator::Less),
+            (">", &[], Operator::Greater),
+        ], Self::join)
+    }
+
+    fn join(&mut self) -> Result<Node, ExpressionError> {
+        self.binary(&[("&", &["&&"], Operator::Join)], Self::additive)
+    }
+
+    fn additive(&mut self) -> Result<Node, ExpressionError> {
+        self.binary(&[("+", &[], Operator::Add), ("-", &[], Operator::Subtract)], Self::multiplicative)
+    }
+
+    fn multiplicative(&mut self) -> Result<Node, ExpressionError> {
+        self.binary(&[
+            ("*", &[], Operator::Multiply),
+            ("/", &[], Operator::Divide),
+            ("%", &[], Operator::Remainder),
+        ], Self::unary)
+    }
+
+    fn unary(&mut self) -> Result<Node, ExpressionError> {
+        if self.eat("-") {
+            return Ok(Node::Negate(Box::new(self.unary()?)));
+        }
+        if self.eat_only("!", &["!="]) {
+            return Ok(Node::Not(Box::new(self.unary()?)));
+        }
+        self.power()
+    }
+
+    /// `^` is right associative and binds tighter than a leading minus, so `-2 ^ 2` is -4
+    fn power(&mut self) -> Result<Node, ExpressionError> {
+        let base = self.primary()?;
+        if self.eat("^") {
+            let exponent = self.unary()?;
+            return Ok(Node::Binary(Operator::Power, Box::new(base), Box::new(exponent)));
+        }
+        Ok(base)
+    }
+
+    fn primary(&mut self) -> Result<Node, ExpressionError> {
+        self.skip_whitespace();
+        let Some(&c) = self.chars.get(self.pos) else {
+            return Err(self.error("Expected a value"));
+        };
+        if self.eat("(") {
+            let inner = self.conditional()?;
+            self.expect(")")?;
+            return Ok(inner);
+        }
+        if c == '"' || c == '\'' {
+            return self.text(c);
+        }
+        if c == '{' {
+            self.pos += 1;
+            let start = self.pos;
+            while self.chars.get(self.pos).is_some_and(|c| *c != '}') {
+                self.pos += 1;
+            }
+            let name: String = self.chars[start..self.pos].iter().collect();
+            self.expect("}")?;
+            return Ok(Node::Variable(name.trim().to_string()));
+        }
+        if c.is_ascii_digit() || c == '.' {
+            let start = self.pos;
+            while self.chars.get(self.pos).is_some_and(|c| c.is_ascii_digit() || *c == '.') {
+                self.pos += 1;
+            }
+            let number: String = self.chars[start..self.pos].iter().collect();
+            return match number.parse::<f64>() {
+                Ok(number) => Ok(Node::Literal(Value::Number(number))),
+                Err(_) => {
+                    self.pos = start;
+                    Err(self.error("Invalid number"))
+                }
+            };
+        }
+        if c.is_alphabetic() || c == '_' {
+            let start = self.pos;
+            while self.chars.get(self.pos).is_some_and(|c| c.is_alphanumeric() || *c == '_') {
+                self.pos += 1;
+            }
+            let name: String = self.chars[start..self.pos].iter().collect();
+            if self.eat("(") {
+                let mut args = Vec::new();
+                if !self.eat(")") {
+                    loop {
+                        args.push(self.conditional()?);
+                        if self.eat(")") {
+                            break;
+                        }
+                        self.expect(",")?;
+                    }
+                }
+                return Ok(Node::Call(name, args));
+            }
+            return Ok(match name.as_str() {
+                "true" => Node::Literal(Value::Bool(true)),
+                "false" => Node::Literal(Value::Bool(false)),
+                _ => Node::Variable(name),
+            });
+        }
+        Err(self.error("Unexpected"))
+    }
+
+    /// Quoted text, where `\` makes the next character literal
+    fn text(&mut self, quote: char) -> Result<Node, ExpressionError> {
+        let start = self.pos;
+        self.pos += 1;
+        let mut text = String::new();
+        loop {
+            match self.chars.get(self.pos) {
+                None => {
+                    self.pos = start;
+                    return Err(self.error("Unclosed text starting with"));
+                }
+                Some('\\') if self.pos + 1 < self.chars.len() => {
+                    text.push(self.chars[self.pos + 1]);
+                    self.pos += 2;
+                }
+                Some(c) if *c == quote => {
+                    self.pos += 1;
+                    return Ok(Node::Literal(Value::Text(text)));
+                }
+                Some(c) => {
+                    text.push(*c);
+                    self.pos += 1;
+                }
+            }
+        }
+    }
+}
+
+fn evaluate(node: &Node, now: i64, lookup: &impl Fn(&str) -> Option<String>) -> Result<Value, ExpressionError> {
+    match node {
+        Node::Literal(value) => Ok(value.clone()),
+        Node::Variable(name) => match lookup(name) {
+            Some(value) => Ok(Value::from_variable(value)),
+            None => Err(ExpressionError(format!("No variable named '{}'", name))),
+        },
+        Node::Negate(inner) => Ok(Value::Number(-evaluate(inner, now, lookup)?.number()?)),
+        Node::Not(inner) => Ok(Value::Bool(!evaluate(inner, now, lookup)?.truthy())),
+        Node::Conditional(condition, then, otherwise) => {
+            if evaluate(condition, now, lookup)?.truthy() {
+                evaluate(then, now, lookup)
+            } else {
+                evaluate(otherwise, now, lookup)
+            }
+        }
+        Node::Binary(Operator::And, left, right) => {
+            Ok(Value::Bool(evaluate(left, now, lookup)?.truthy() && evaluate(right, now, lookup)?.truthy()))
+        }
+        Node::Binary(Operator::Or, left, right) => {
+            Ok(Value::Bool(evaluate(left, now, lookup)?.truthy() || evaluate(right, now, lookup)?.truthy()))
+        }
+        Node::Binary(operator, left, right) => {
+            binary(*operator, evaluate(left, now, lookup)?, evaluate(right, now, lookup)?)
+        }
+        Node::Call(name, args) if name == "if" => {
+            let [condition, then, otherwise] = args.as_slice() else {
+                return Err(ExpressionError(String::from("'if' takes a condition, a value when it's true and one when it's not")));
+            };
+            if evaluate(condition, now, lookup)?.truthy() {
+                evaluate(then, now, lookup)
+            } else {
+                evaluate(otherwise, now, lookup)
+            }
+        }
+        Node::Call(name, args) => {
+            let args = args.iter().map(|arg| evaluate(arg, now, lookup)).collect::<Result<Vec<_>, _>>()?;
+            call(name, args, now)
+        }
+    }
+}
+
+fn binary(operator: Operator, left: Value, right: Value) -> Result<Value, ExpressionError> {
+    let number = |value: f64| {
+        if value.is_finite() { Ok(Value::Number(value)) } else { Err(ExpressionError(String::from("The result is not a number"))) }
+    };
+    match operator {
+        Operator::Add => match (&left, &right) {
+            (Value::Text(_), _) | (_, Value::Text(_)) => Ok(Value::Text(format!("{}{}", left, right))),
+            _ => number(left.number()? + right.number()?),
+        },
+        Operator::Subtract => number(left.number()? - right.number()?),
+        Operator::Multiply => number(left.number()? * right.number()?),
+        Operator::Divide => match right.number()? {
+            0. => Err(ExpressionError(String::from("Division by zero"))),
+            divisor => number(left.number()? / divisor),
+        },
+        Operator::Remainder => match right.number()? {
+            0. => Err(ExpressionError(String::from("Division by zero"))),
+            divisor => number(left.number()? % divisor),
+        },
+        Operator::Power => number(left.number()?.powf(right.number()?)),
+        Operator::Join => Ok(Value::Text(format!("{}{}", left, right))),
+        Operator::Equal => Ok(Value::Bool(compare(&left, &right).is_eq())),
+        Operator::NotEqual => Ok(Value::Bool(compare(&left, &right).is_ne())),
+        Operator::Less => Ok(Value::Bool(compare(&left, &right).is_lt())),
+        Operator::LessOrEqual => Ok(Value::Bool(compare(&left, &right).is_le())),
+        Operator::Greater => Ok(Value::Bool(compare(&left, &right).is_gt())),
+        Operator::GreaterOrEqual => Ok(Value::Bool(compare(&left, &right).is_ge())),
+        Operator::And | Operator::Or => unreachable!("short circuited in evaluate"),
+    }
+}
+
+/// Numbers compare as numbers, anything else by its text
+fn compare(left: &Value, right: &Value) -> std::cmp::Ordering {
+    match (left, right) {
+        (Value::Number(left), Value::Number(right)) => left.total_cmp(right),
+        _ => left.to_string().cmp(&right.to_string()),
+    }
+}
+
+fn call(name: &str, args: Vec<Value>, now: i64) -> Result<Value, ExpressionError> {
+    let arity = |expected: usize| -> Result<(), ExpressionError> {
+        if args.len() == expected {
+            Ok(())
+        } else {
+            Err(ExpressionError(format!("'{}' takes {} argument(s), not {}", name, expected, args.len())))
+        }
+    };
+    let numbers = || -> Result<Vec<f64>, ExpressionError> {
+        if args.is_empty() {
+            return Err(ExpressionError(format!("'{}' needs at least one argument", name)));
+        }
+        args.iter().map(Value::number).collect()
+    };
+    let unary = |function: fn(f64) -> f64| -> Result<Value, ExpressionError> {
+        arity(1)?;
+        Ok(Value::Number(function(args[0].number()?)))
+    };
+    match name {
+        "abs" => unary(f64::abs),
+        "floor" => unary(f64::floor),
+        "ceil" => unary(f64::ceil),
+        "sqrt" => {
+            arity(1)?;
+            match args[0].number()? {
+                value if value < 0. => Err(ExpressionError(String::from("'sqrt' of a negative number"))),
+                value => Ok(Value::Number(value.sqrt())),
+            }
+        }
+        "round" => {
+            let decimals = match args.len() {
+                1 => 0,
+                2 => args[1].number()?.clamp(0., 10.) as i32,
+                _ => return Err(ExpressionError(String::from("'round' takes a number and optionally a number of decimals"))),
+            };
+            let scale = 10f64.powi(decimals);
+            Ok(Value::Number((args[0].number()? * scale).round() / scale))
+        }
+        "pow" => {
+            arity(2)?;
+            binary(Operator::Power, args[0].clone(), args[1].clone())
+        }
+        "min" => Ok(Value::Number(numbers()?.into_iter().fold(f64::INFINITY, f64::min))),
+        "max" => Ok(Value::Number(numbers()?.into_iter().fold(f64::NEG_INFINITY, f64::max))),
+        "sum" => Ok(Value::Number(numbers()?.into_iter().sum())),
+        "avg" => {
+            let numbers = numbers()?;
+            Ok(Value::Number(numbers.iter().sum::<f64>() / numbers.len() as f64))
+        }
+        "clamp" => {
+            arity(3)?;
+            let (value, low, high) = (args[0].number()?, args[1].number()?, args[2].number()?);
+            Ok(Value::Number(value.max(low).min(high)))
+        }
+        "len" => {
+            arity(1)?;
+            Ok(Value::Number(args[0].to_string().chars().count() as f64))
+        }
+        "upper" => {
+            arity(1)?;
+            Ok(Value::Text(args[0].to_string().to_uppercase()))
+        }
+        "lower" => {
+            arity(1)?;
+            Ok(Value::Text(args[0].to_string().to_lowercase()))
+        }
+        "contains" => {
+            arity(2)?;
+            Ok(Value::Bool(args[0].to_string().contains(&args[1].to_string())))
+        }
+        "text" => {
+            arity(1)?;
+            Ok(Value::Text(args[0].to_string()))
+        }
+        "number" => {
+            arity(1)?;
+            match &args[0] {
+                Value::Text(text) => parse_number(text)
+                    .map(Value::Number)
+                    .ok_or_else(|| ExpressionError(format!("There is no number in '{}'", text))),
+                value => Ok(Value::Number(value.number()?)),
+            }
+        }
+        "now" => {
+            arity(0)?;
+            Ok(Value::Number(now as f64))
+        }
+        "timestamp" => {
+            arity(1)?;
+            parse_timestamp(&args[0]).map(|timestamp| Value::Number(timestamp as f64))
+        }
+        _ => Err(ExpressionError(format!("Unknown function '{}'", name))),
+    }
+}
+
+/// Unix timestamps pass through, dates can be RFC 3339 (`2024-09-30T12:34:56Z`) or `2024-09-30 12:34:56` in UTC
+fn parse_timestamp(value: &Value) -> Result<i64, ExpressionError> {
+    if let Value::Number(timestamp) = value {
+        return Ok(*timestamp as i64);
+    }
+    let text = value.to_string();
+    let text = text.trim();
+    chrono::DateTime::parse_from_rfc3339(text)
+        .map(|date| date.timestamp())
+        .or_else(|_| chrono::NaiveDateTime::parse_from_str(text, "%Y-%m-%d %H:%M:%S").map(|date| date.and_utc().timestamp()))
+        .map_err(|_| ExpressionError(format!("'{}' is not a date", text)))
+}
@@ -0,0 +1,92 @@
+use crate::{
+    boards::{BoardElement, BoardElementValue, ElementColour, GraphScale, GraphStyle},
+    device_config::TemperatureColours,
+    indicators::value_colour,
+};
+
+/// The most history the server keeps for a graph, a week
+pub const MAX_GRAPH_HOURS: u16 = 24 * 7;
+
+/// Samples of a variable as (unix timestamp in seconds, value), oldest first
+pub type VariableHistory = Vec<(i64, f64)>;
+
+/// A line of a graph, drawn with the device's line command
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct GraphSegment {
+    pub start: (i32, i32),
+    pub end: (i32, i32),
+    pub colour: ElementColour,
+}
+
+/// Averages the samples from the last `window` seconds before `now` into `columns` evenly spaced buckets,
+/// `None` where no samples were taken
+pub fn bucket_samples(samples: &[(i64, f64)], now: i64, window: i64, columns: u32) -> Vec<Option<f64>> {
+    let mut totals = vec![(0., 0); columns as usize];
+    if columns == 0 || window <= 0 {
+        return Vec::new();
+    }
+    let start = now - window;
+    for (timestamp, value) in samples {
+        if *timestamp <= start || *timestamp > now {
+            continue;
+        }
+        let column = (((*timestamp - start) as i128 * columns as i128 - 1) / window as i128) as usize;
+        let total = &mut totals[column.min(columns as usize - 1)];
+        total.0 += value;
+        total.1 += 1;
+    }
+    totals.into_iter().map(|(sum, count)| (count > 0).then(|| sum / count as f64)).collect()
+}
+
+/// The values at the bottom and top of the graph
+pub fn graph_range(values: &[Option<f64>], scale: &GraphScale) -> (f64, f64) {
+    match scale {
+        GraphScale::Fixed(min, max) => (*min, *max),
+        GraphScale::Auto => {
+            let values = values.iter().flatten();
+            let min = values.clone().copied().fold(f64::INFINITY, f64::min);
+            let max = values.copied().fold(f64::NEG_INFINITY, f64::max);
+            if !min.is_finite() {
+                (0., 1.)
+            } else if min == max {
+                // Keep flat lines in the middle of the graph
+                (min - 1., max + 1.)
+            } else {
+                (min, max)
+            }
+        }
+    }
+}
+
+/// The lines that draw a graph element from its variable's history, `None` for other elements.
+/// Each column of the graph covers an equal slice of the element's time window.
+pub fn element_graph(element: &BoardElement, samples: &[(i64, f64)], now: i64, temperature_colours: &TemperatureColours) -> Option<Vec<GraphSegment>> {
+    let BoardElementValue::Graph(_, width, height, settings) = &element.value else {
+        return None;
+    };
+    let (x, y) = (element.x.unwrap_or(0) as i32, element.y as i32);
+    let (width, height) = (*width as u32, *height as u32);
+    let values = bucket_samples(samples, now, settings.hours as i64 * 3600, width);
+    let (min, max) = graph_range(&values, &settings.scale);
+    let bottom = y + height as i32 - 1;
+    let value_y = |value: f64| {
+        let fraction = if max == min { 0. } else { ((value - min) / (max - min)).clamp(0., 1.) };
+        bottom - (fraction * height.saturating_sub(1) as f64).round() as i32
+    };
+    let mut segments = Vec::new();
+    let mut previous: Option<(i32, i32)> = None;
+    for (column, value) in values.iter().enumerate() {
+        let Some(value) = value else {
+            previous = None;
+            continue;
+        };
+        let colour = value_colour(*value, &element.colour, temperature_colours);
+        let point = (x + column as i32, value_y(*value));
+        match settings.style {
+            GraphStyle::Line => segments.push(GraphSegment { start: previous.unwrap_or(point), end: point, colour }),
+            GraphStyle::Bars => segments.push(GraphSegment { start: (point.0, bottom), end: point, colour }),
+        }
+        previous = Some(point);
+    }
+    Some(segments)
+}
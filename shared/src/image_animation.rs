@@ -0,0 +1,74 @@
+use std::time::Duration;
+
+use crate::{boards::AnimationSettings, canvas::PixelImage};
+
+/// GIFs that ask for frames faster than this get the usual 100ms instead, the same as browsers do
+const MIN_FRAME_DELAY: Duration = Duration::from_millis(20);
+const DEFAULT_FRAME_DELAY: Duration = Duration::from_millis(100);
+
+/// Cuts a horizontal sprite strip into `frames` equal frames, dropping any leftover columns on the right
+pub fn split_sprite_strip(image: &PixelImage, frames: u8) -> Vec<PixelImage> {
+    let frame_width = image.width / frames.max(1) as u32;
+    if frame_width == 0 {
+        return vec![image.clone()];
+    }
+    (0..frames.max(1) as u32)
+        .map(|frame| {
+            let mut pixels = Vec::with_capacity((frame_width * image.height) as usize);
+            for y in 0..image.height {
+                let start = (y * image.width + frame * frame_width) as usize;
+                pixels.extend_from_slice(&image.pixels[start..start + frame_width as usize]);
+            }
+            PixelImage { width: frame_width, height: image.height, pixels }
+        })
+        .collect()
+}
+
+/// How long a GIF frame is shown for, fixing up the zero delays some encoders write
+pub fn gif_frame_delay(delay_ms: u32) -> Duration {
+    let delay = Duration::from_millis(delay_ms as u64);
+    if delay < MIN_FRAME_DELAY {
+        DEFAULT_FRAME_DELAY
+    } else {
+        delay
+    }
+}
+
+/// Delays of every frame once the element's speed is applied
+fn scaled_delays<'a>(delays: &'a [Duration], settings: &'a AnimationSettings) -> impl Iterator<Item = Duration> + 'a {
+    let speed = settings.speed.max(1) as u32;
+    delays.iter().map(move |delay| (*delay * 100 / speed).max(MIN_FRAME_DELAY))
+}
+
+/// How long the animation plays for, every loop included. Zero for a single frame, which never changes.
+pub fn animation_duration(delays: &[Duration], settings: &AnimationSettings) -> Duration {
+    if delays.len() < 2 {
+        return Duration::ZERO;
+    }
+    scaled_delays(delays, settings).sum::<Duration>() * settings.loops.max(1) as u32
+}
+
+/// The shortest time between frame changes
+pub fn animation_frame_interval(delays: &[Duration], settings: &AnimationSettings) -> Duration {
+    scaled_delays(delays, settings).min().unwrap_or(DEFAULT_FRAME_DELAY)
+}
+
+/// Which frame is showing `elapsed` after the animation started, holding the last frame once every loop has played
+pub fn animation_frame_at(delays: &[Duration], settings: &AnimationSettings, elapsed: Duration) -> usize {
+    let duration = animation_duration(delays, settings);
+    if duration.is_zero() {
+        return 0;
+    }
+    if elapsed >= duration {
+        return delays.len() - 1;
+    }
+    let loop_length = duration / settings.loops.max(1) as u32;
+    let mut position = Duration::from_nanos((elapsed.as_nanos() % loop_length.as_nanos()) as u64);
+    for (frame, delay) in scaled_delays(delays, settings).enumerate() {
+        if position < delay {
+            return frame;
+        }
+        position -= delay;
+    }
+    delays.len() - 1
+}
@@ -0,0 +1,143 @@
+use serde::{Deserialize, Serialize};
+
+/// Largest image an upload can be resized to, the biggest panels are chained up to this many LEDs across
+pub const MAX_UPLOAD_SIZE: u8 = 128;
+
+/// How an uploaded image is fitted into the requested width and height
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, PartialOrd, Debug, Default)]
+pub enum ImageFit {
+    /// Keep the image's own size
+    #[default]
+    Original,
+    /// Scale the whole image down (or up) to fit inside the size, keeping its aspect ratio
+    Contain,
+    /// Scale the image to cover the size and crop off whatever hangs over the edges
+    Crop,
+}
+impl ImageFit {
+    pub fn get_option(&self) -> String {
+        match self {
+            ImageFit::Original => ImageFit::get_options()[0].clone(),
+            ImageFit::Contain => ImageFit::get_options()[1].clone(),
+            ImageFit::Crop => ImageFit::get_options()[2].clone(),
+        }
+    }
+    pub fn get_options() -> Vec<String> {
+        "Original;Contain;Crop".split(';').map(|x|x.to_string()).collect()
+    }
+    pub fn from_option(type_str: &str) -> ImageFit {
+        match type_str {
+            "Contain" => ImageFit::Contain,
+            "Crop" => ImageFit::Crop,
+            _ => ImageFit::Original,
+        }
+    }
+}
+
+/// How colours between the quantisation levels are made up
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, PartialOrd, Debug, Default)]
+pub enum Dithering {
+    /// Round every pixel to the nearest level
+    #[default]
+    None,
+    /// Spread each pixel's rounding error onto its neighbours, best for photos
+    FloydSteinberg,
+    /// A fixed 4x4 pattern, which stays still when an image is redrawn and suits flat artwork
+    Ordered,
+}
+impl Dithering {
+    pub fn get_option(&self) -> String {
+        match self {
+            Dithering::None => Dithering::get_options()[0].clone(),
+            Dithering::FloydSteinberg => Dithering::get_options()[1].clone(),
+            Dithering::Ordered => Dithering::get_options()[2].clone(),
+        }
+    }
+    pub fn get_options() -> Vec<String> {
+        "None;Floyd-Steinberg;Ordered".split(';').map(|x|x.to_string()).collect()
+    }
+    pub fn from_option(type_str: &str) -> Dithering {
+        match type_str {
+            "Floyd-Steinberg" => Dithering::FloydSteinberg,
+            "Ordered" => Dithering::Ordered,
+            _ => Dithering::None,
+        }
+    }
+}
+
+/// Everything sent with an uploaded image besides the file itself, as multipart form fields
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+pub struct ImageUploadOptions {
+    /// Folder under the images folder to save into, empty for the top level
+    pub folder: String,
+    pub fit: ImageFit,
+    pub width: u8,
+    pub height: u8,
+    /// Brightness levels per colour channel, 0 to keep every colour. Panels show 16 levels for drawn colours.
+    pub levels: u8,
+    pub dithering: Dithering,
+    /// Replace an image with the same name instead of refusing the upload
+    pub overwrite: bool,
+}
+impl Default for ImageUploadOptions {
+    fn default() -> Self {
+        Self {
+            folder: String::new(),
+            fit: ImageFit::Original,
+            width: 64,
+            height: 32,
+            levels: 0,
+            dithering: Dithering::None,
+            overwrite: false,
+        }
+    }
+}
+impl ImageUploadOptions {
+    /// The options as (field name, value) pairs for a multipart form
+    pub fn form_fields(&self) -> Vec<(&'static str, String)> {
+        vec![
+            ("folder", self.folder.clone()),
+            ("fit", self.fit.get_option()),
+            ("width", self.width.to_string()),
+            ("height", self.height.to_string()),
+            ("levels", self.levels.to_string()),
+            ("dithering", self.dithering.get_option()),
+            ("overwrite", self.overwrite.to_string()),
+        ]
+    }
+
+    /// Reads back a field written by `form_fields`, returning `false` for unknown fields or bad values
+    pub fn set_form_field(&mut self, name: &str, value: &str) -> bool {
+        match name {
+            "folder" => self.folder = value.to_string(),
+            "fit" => self.fit = ImageFit::from_option(value),
+            "dithering" => self.dithering = Dithering::from_option(value),
+            "width" | "height" | "levels" => {
+                let Ok(number) = value.parse::<u8>() else {
+                    return false;
+                };
+                match name {
+                    "width" => self.width = number.clamp(1, MAX_UPLOAD_SIZE),
+                    "height" => self.height = number.clamp(1, MAX_UPLOAD_SIZE),
+                    _ => self.levels = if number < 2 { 0 } else { number },
+                }
+            }
+            "overwrite" => self.overwrite = value == "true",
+            _ => return false,
+        }
+        true
+    }
+}
+
+/// Body of a request to rename or move an image or folder, both paths relative to the images folder
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+pub struct ImageRename {
+    pub from: String,
+    pub to: String,
+}
+
+/// Body of a request to create a folder in the images folder
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+pub struct ImageFolder {
+    pub path: String,
+}
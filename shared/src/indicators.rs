@@ -0,0 +1,110 @@
+use crate::{
+    boards::{BarDirection, BoardElement, BoardElementValue, ColourOption, ElementColour, IndicatorSettings},
+    device_config::TemperatureColours,
+    shapes::Shape,
+};
+
+/// Where the track of a gauge starts, in degrees clockwise from 3 o'clock
+pub const GAUGE_START_ANGLE: i32 = 135;
+/// How far round a full gauge goes, leaving the bottom open
+pub const GAUGE_SWEEP: i32 = 270;
+
+/// One piece of a bar or gauge. Shapes are filled when `stroke_width` is 0, otherwise only their outline is drawn.
+#[derive(Clone, Debug, PartialEq)]
+pub struct IndicatorPart {
+    pub shape: Shape,
+    pub stroke_width: u32,
+    pub colour: ElementColour,
+}
+
+/// The first number in some text, so values like "72°F" or "-3.5 kW" can drive an indicator
+pub fn parse_number(text: &str) -> Option<f64> {
+    let start = text.find(|c: char| c.is_ascii_digit())?;
+    let negative = text[..start].ends_with('-');
+    let number: String = text[start..].chars().take_while(|c| c.is_ascii_digit() || *c == '.').collect();
+    let number = number.trim_end_matches('.').parse::<f64>().ok()?;
+    Some(if negative { -number } else { number })
+}
+
+/// How full an indicator is, between 0 and 1
+pub fn indicator_fraction(value: f64, settings: &IndicatorSettings) -> f64 {
+    let range = settings.max - settings.min;
+    if range == 0. {
+        return if value >= settings.max { 1. } else { 0. };
+    }
+    ((value - settings.min) / range).clamp(0., 1.)
+}
+
+/// The colour of the highest threshold the value has reached, or the element's own colour below all of them
+pub fn indicator_colour(value: f64, colour: &ColourOption, settings: &IndicatorSettings, temperature_colours: &TemperatureColours) -> ElementColour {
+    let threshold = settings.thresholds.iter()
+        .filter(|(threshold, _)| value >= *threshold)
+        .max_by(|a, b| a.0.total_cmp(&b.0));
+    match threshold {
+        Some((_, colour)) => *colour,
+        None => value_colour(value, colour, temperature_colours),
+    }
+}
+
+/// The colour an element's colour option gives a number, using the temperature colours when asked to
+pub fn value_colour(value: f64, colour: &ColourOption, temperature_colours: &TemperatureColours) -> ElementColour {
+    match colour {
+        ColourOption::Default => ElementColour::default(),
+        ColourOption::Specific(colour) => *colour,
+        ColourOption::ParseTemperature => temperature_colours.get_colour(value.round() as i32),
+    }
+}
+
+/// The parts of a bar or gauge element showing `value_text`, background first. `None` for other elements.
+/// Text without a number shows as an empty indicator.
+pub fn element_indicator(element: &BoardElement, value_text: &str, temperature_colours: &TemperatureColours) -> Option<Vec<IndicatorPart>> {
+    let (x, y) = (element.x.unwrap_or(0) as i32, element.y as i32);
+    let settings = match &element.value {
+        BoardElementValue::Bar(_, _, _, _, settings) | BoardElementValue::Gauge(_, _, _, settings) => settings,
+        _ => return None,
+    };
+    let value = parse_number(value_text).unwrap_or(settings.min);
+    let fraction = indicator_fraction(value, settings);
+    let colour = indicator_colour(value, &element.colour, settings, temperature_colours);
+    let mut parts = Vec::new();
+    match &element.value {
+        BoardElementValue::Bar(_, width, height, direction, _) => {
+            let (width, height) = (*width as u32, *height as u32);
+            let horizontal = matches!(direction, BarDirection::LeftToRight | BarDirection::RightToLeft);
+            let length = if horizontal { width } else { height };
+            let filled = (fraction * length as f64).round() as u32;
+            let empty = length - filled;
+            // (offset along the bar, length) of the filled and empty parts
+            let (filled_span, empty_span) = match direction {
+                BarDirection::LeftToRight | BarDirection::TopToBottom => ((0, filled), (filled, empty)),
+                BarDirection::RightToLeft | BarDirection::BottomToTop => ((empty, filled), (0, empty)),
+            };
+            let span_rect = |(offset, span_length): (u32, u32)| {
+                if horizontal {
+                    Shape::Rect { x: x + offset as i32, y, width: span_length, height }
+                } else {
+                    Shape::Rect { x, y: y + offset as i32, width, height: span_length }
+                }
+            };
+            if let (Some(background), true) = (settings.background, empty > 0) {
+                parts.push(IndicatorPart { shape: span_rect(empty_span), stroke_width: 0, colour: background });
+            }
+            if filled > 0 {
+                parts.push(IndicatorPart { shape: span_rect(filled_span), stroke_width: 0, colour });
+            }
+        }
+        BoardElementValue::Gauge(_, diameter, thickness, _) => {
+            let (diameter, stroke_width) = (*diameter as u32, (*thickness).max(1) as u32);
+            let sweep = (fraction * GAUGE_SWEEP as f64).round() as i32;
+            let arc = |start: i32, sweep: i32| Shape::Arc { x, y, diameter, start, sweep };
+            if let (Some(background), true) = (settings.background, sweep < GAUGE_SWEEP) {
+                parts.push(IndicatorPart { shape: arc(GAUGE_START_ANGLE + sweep, GAUGE_SWEEP - sweep), stroke_width, colour: background });
+            }
+            if sweep > 0 {
+                parts.push(IndicatorPart { shape: arc(GAUGE_START_ANGLE, sweep), stroke_width, colour });
+            }
+        }
+        _ => {}
+    }
+    Some(parts)
+}
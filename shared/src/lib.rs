@@ -1,3 +1,15 @@
 pub mod boards;
 pub mod board_variables;
+pub mod canvas;
+pub mod countdowns;
+pub mod date_names;
+pub mod expressions;
+pub mod graphs;
+pub mod image_animation;
+pub mod image_upload;
+pub mod indicators;
+pub mod shapes;
+pub mod templates;
+pub mod text_layout;
+pub mod transitions;
 pub mod device_config;
\ No newline at end of file
@@ -0,0 +1,242 @@
+use crate::boards::{BoardElement, BoardElementValue, ShapeStyle};
+
+/// Geometry of a shape element in board pixels
+#[derive(Clone, Debug, PartialEq)]
+pub enum Shape {
+    Rect { x: i32, y: i32, width: u32, height: u32 },
+    RoundedRect { x: i32, y: i32, width: u32, height: u32, radius: u32 },
+    Circle { x: i32, y: i32, diameter: u32 },
+    Ellipse { x: i32, y: i32, width: u32, height: u32 },
+    Triangle([(i32, i32); 3]),
+    Polyline(Vec<(i32, i32)>),
+    /// Part of a circle's outline, angles are in degrees clockwise from 3 o'clock
+    Arc { x: i32, y: i32, diameter: u32, start: i32, sweep: i32 },
+}
+
+/// Pixels covered by a shape, split into the parts drawn in the fill and outline colours
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ShapePixels {
+    pub fill: Vec<(i32, i32)>,
+    pub stroke: Vec<(i32, i32)>,
+}
+
+/// The shape an element draws and how, `None` for elements that are not shapes
+pub fn element_shape(element: &BoardElement) -> Option<(Shape, ShapeStyle)> {
+    let (x, y) = (element.x.unwrap_or(0) as i32, element.y as i32);
+    let shape = match &element.value {
+        BoardElementValue::Rect(width, height, style) => {
+            (Shape::Rect { x, y, width: *width as u32, height: *height as u32 }, *style)
+        }
+        BoardElementValue::RoundedRect(width, height, radius, style) => {
+            (Shape::RoundedRect { x, y, width: *width as u32, height: *height as u32, radius: *radius as u32 }, *style)
+        }
+        BoardElementValue::Circle(diameter, style) => (Shape::Circle { x, y, diameter: *diameter as u32 }, *style),
+        BoardElementValue::Ellipse(width, height, style) => {
+            (Shape::Ellipse { x, y, width: *width as u32, height: *height as u32 }, *style)
+        }
+        BoardElementValue::Triangle((x2, y2), (x3, y3), style) => {
+            (Shape::Triangle([(x, y), (*x2 as i32, *y2 as i32), (*x3 as i32, *y3 as i32)]), *style)
+        }
+        BoardElementValue::Polyline(points, style) => {
+            let mut all_points = vec![(x, y)];
+            all_points.extend(points.iter().map(|(x, y)| (*x as i32, *y as i32)));
+            (Shape::Polyline(all_points), *style)
+        }
+        _ => return None,
+    };
+    Some(shape)
+}
+
+impl Shape {
+    /// (x, y, width, height) of the area the shape covers
+    pub fn bounds(&self) -> (i32, i32, u32, u32) {
+        match self {
+            Shape::Rect { x, y, width, height }
+            | Shape::RoundedRect { x, y, width, height, .. }
+            | Shape::Ellipse { x, y, width, height } => (*x, *y, *width, *height),
+            Shape::Circle { x, y, diameter } | Shape::Arc { x, y, diameter, .. } => (*x, *y, *diameter, *diameter),
+            Shape::Triangle(points) => points_bounds(points),
+            Shape::Polyline(points) => points_bounds(points),
+        }
+    }
+
+    /// Pixels lit by the shape. Outlines of closed shapes are drawn inside the shape, the same way devices draw them.
+    pub fn pixels(&self, stroke_width: u32) -> ShapePixels {
+        let mut pixels = ShapePixels::default();
+        if let Shape::Polyline(points) = self {
+            pixels.stroke = polyline_pixels(points, stroke_width);
+            return pixels;
+        }
+        if let Shape::Arc { x, y, diameter, start, sweep } = self {
+            pixels.stroke = arc_pixels(*x, *y, *diameter, *start, *sweep, stroke_width);
+            return pixels;
+        }
+        let (x, y, width, height) = self.bounds();
+        for pixel_y in y..y + height as i32 + 1 {
+            for pixel_x in x..x + width as i32 + 1 {
+                if !self.contains(pixel_x, pixel_y, 0.) {
+                    continue;
+                }
+                if self.contains(pixel_x, pixel_y, stroke_width as f32) {
+                    pixels.fill.push((pixel_x, pixel_y));
+                } else {
+                    pixels.stroke.push((pixel_x, pixel_y));
+                }
+            }
+        }
+        // Thin triangles can fall between pixel centres, so make sure the edges are always there
+        if let Shape::Triangle(points) = self {
+            if stroke_width > 0 {
+                for idx in 0..3 {
+                    pixels.stroke.extend(line_pixels(points[idx], points[(idx + 1) % 3]));
+                }
+                pixels.fill.retain(|pixel| !pixels.stroke.contains(pixel));
+            }
+        }
+        pixels
+    }
+
+    /// Whether the centre of a pixel is inside the shape once it is shrunk by `inset` on every side
+    fn contains(&self, pixel_x: i32, pixel_y: i32, inset: f32) -> bool {
+        let (px, py) = (pixel_x as f32 + 0.5, pixel_y as f32 + 0.5);
+        match self {
+            Shape::Rect { x, y, width, height } => {
+                px >= *x as f32 + inset
+                    && px < (*x + *width as i32) as f32 - inset
+                    && py >= *y as f32 + inset
+                    && py < (*y + *height as i32) as f32 - inset
+            }
+            Shape::RoundedRect { x, y, width, height, radius } => {
+                let (left, top) = (*x as f32, *y as f32);
+                let (right, bottom) = (left + *width as f32, top + *height as f32);
+                if px < left + inset || px >= right - inset || py < top + inset || py >= bottom - inset {
+                    return false;
+                }
+                let radius = (*radius as f32).min(*width as f32 / 2.).min(*height as f32 / 2.);
+                let corner_x = if px < left + radius { left + radius } else if px > right - radius { right - radius } else { px };
+                let corner_y = if py < top + radius { top + radius } else if py > bottom - radius { bottom - radius } else { py };
+                let corner_radius = radius - inset;
+                (px == corner_x || py == corner_y)
+                    || (corner_radius > 0. && (px - corner_x).powi(2) + (py - corner_y).powi(2) <= corner_radius.powi(2))
+            }
+            Shape::Circle { x, y, diameter } => {
+                let radius = *diameter as f32 / 2.;
+                ellipse_contains(px - (*x as f32 + radius), py - (*y as f32 + radius), radius - inset, radius - inset)
+            }
+            Shape::Ellipse { x, y, width, height } => {
+                let (radius_x, radius_y) = (*width as f32 / 2., *height as f32 / 2.);
+                ellipse_contains(px - (*x as f32 + radius_x), py - (*y as f32 + radius_y), radius_x - inset, radius_y - inset)
+            }
+            Shape::Triangle(points) => {
+                let corners = points.map(|(x, y)| (x as f32 + 0.5, y as f32 + 0.5));
+                let sides = [0, 1, 2].map(|idx| edge_side(corners[idx], corners[(idx + 1) % 3], (px, py)));
+                let inside = sides.iter().all(|side| *side >= 0.) || sides.iter().all(|side| *side <= 0.);
+                inside
+                    && (0..3).all(|idx| segment_distance(corners[idx], corners[(idx + 1) % 3], (px, py)) >= inset)
+            }
+            Shape::Polyline(_) | Shape::Arc { .. } => false,
+        }
+    }
+}
+
+fn points_bounds(points: &[(i32, i32)]) -> (i32, i32, u32, u32) {
+    let min_x = points.iter().map(|x| x.0).min().unwrap_or(0);
+    let max_x = points.iter().map(|x| x.0).max().unwrap_or(0);
+    let min_y = points.iter().map(|x| x.1).min().unwrap_or(0);
+    let max_y = points.iter().map(|x| x.1).max().unwrap_or(0);
+    (min_x, min_y, (max_x - min_x + 1) as u32, (max_y - min_y + 1) as u32)
+}
+
+fn ellipse_contains(dx: f32, dy: f32, radius_x: f32, radius_y: f32) -> bool {
+    radius_x > 0. && radius_y > 0. && (dx / radius_x).powi(2) + (dy / radius_y).powi(2) <= 1.
+}
+
+/// Which side of the line from `start` to `end` a point is on
+fn edge_side(start: (f32, f32), end: (f32, f32), point: (f32, f32)) -> f32 {
+    (end.0 - start.0) * (point.1 - start.1) - (end.1 - start.1) * (point.0 - start.0)
+}
+
+fn segment_distance(start: (f32, f32), end: (f32, f32), point: (f32, f32)) -> f32 {
+    let (dx, dy) = (end.0 - start.0, end.1 - start.1);
+    let length = dx * dx + dy * dy;
+    let t = if length == 0. { 0. } else { (((point.0 - start.0) * dx + (point.1 - start.1) * dy) / length).clamp(0., 1.) };
+    ((point.0 - start.0 - t * dx).powi(2) + (point.1 - start.1 - t * dy).powi(2)).sqrt()
+}
+
+/// Pixels along a line, including both ends
+pub fn line_pixels(start: (i32, i32), end: (i32, i32)) -> Vec<(i32, i32)> {
+    let mut pixels = Vec::new();
+    let (mut x, mut y) = start;
+    let dx = (end.0 - x).abs();
+    let dy = -(end.1 - y).abs();
+    let step_x = if x < end.0 { 1 } else { -1 };
+    let step_y = if y < end.1 { 1 } else { -1 };
+    let mut error = dx + dy;
+    loop {
+        pixels.push((x, y));
+        if x == end.0 && y == end.1 {
+            break;
+        }
+        let doubled_error = 2 * error;
+        if doubled_error >= dy {
+            error += dy;
+            x += step_x;
+        }
+        if doubled_error <= dx {
+            error += dx;
+            y += step_y;
+        }
+    }
+    pixels
+}
+
+/// The outline of a circle `stroke_width` thick, limited to the pixels whose centres are within the sweep
+fn arc_pixels(x: i32, y: i32, diameter: u32, start: i32, sweep: i32, stroke_width: u32) -> Vec<(i32, i32)> {
+    let ring = Shape::Circle { x, y, diameter };
+    let radius = diameter as f32 / 2.;
+    let (centre_x, centre_y) = (x as f32 + radius, y as f32 + radius);
+    let (start, sweep) = if sweep < 0 { (start + sweep, -sweep) } else { (start, sweep) };
+    let mut pixels = Vec::new();
+    for pixel_y in y..y + diameter as i32 {
+        for pixel_x in x..x + diameter as i32 {
+            if !ring.contains(pixel_x, pixel_y, 0.) || ring.contains(pixel_x, pixel_y, stroke_width as f32) {
+                continue;
+            }
+            let angle = (pixel_y as f32 + 0.5 - centre_y).atan2(pixel_x as f32 + 0.5 - centre_x).to_degrees();
+            if sweep >= 360 || (angle - start as f32).rem_euclid(360.) <= sweep as f32 {
+                pixels.push((pixel_x, pixel_y));
+            }
+        }
+    }
+    pixels
+}
+
+/// Lines one pixel wide follow the pixel grid, wider ones cover everything within half the width of each segment
+fn polyline_pixels(points: &[(i32, i32)], stroke_width: u32) -> Vec<(i32, i32)> {
+    let mut pixels = Vec::new();
+    if stroke_width == 0 {
+        return pixels;
+    }
+    for segment in points.windows(2) {
+        if stroke_width == 1 {
+            pixels.extend(line_pixels(segment[0], segment[1]));
+            continue;
+        }
+        let half_width = stroke_width as f32 / 2.;
+        let reach = stroke_width as i32;
+        let (x, y, width, height) = points_bounds(segment);
+        let (start, end) = (
+            (segment[0].0 as f32 + 0.5, segment[0].1 as f32 + 0.5),
+            (segment[1].0 as f32 + 0.5, segment[1].1 as f32 + 0.5),
+        );
+        for pixel_y in y - reach..y + height as i32 + reach {
+            for pixel_x in x - reach..x + width as i32 + reach {
+                let centre = (pixel_x as f32 + 0.5, pixel_y as f32 + 0.5);
+                if segment_distance(start, end, centre) < half_width && !pixels.contains(&(pixel_x, pixel_y)) {
+                    pixels.push((pixel_x, pixel_y));
+                }
+            }
+        }
+    }
+    pixels
+}
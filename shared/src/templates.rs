@@ -0,0 +1,318 @@
+//! Text with `__name__` placeholders for board variables, optionally run through filters:
+//! `__temp|round:1__`, `__title|upper|truncate:10__`, `__x|default:--__`.
+//!
+//! Filter arguments are separated by `:`, and filters by `|`. A `\` makes the character after it literal,
+//! so `\|`, `\:`, `\_` and `\\` can be used in arguments. Outside placeholders `\__` writes a literal `__`.
+
+use std::fmt;
+
+/// The filters a placeholder can use, for help text
+pub const TEMPLATE_FILTER_HELP: &str = "Placeholders look like __name__ or __name|filter|filter:argument__
+upper, lower, capitalize, trim
+truncate:length[:suffix]   e.g. truncate:10:...
+round:decimals             3.14159 -> round:1 -> 3.1
+num:decimals               1234.5 -> num:2 -> 1,234.50
+default:text               used when the value is empty
+pad_left:width[:char], pad_right:width[:char]
+Use \\ before | : or _ to include them in an argument";
+
+/// A problem with one placeholder. Its text is shown as written instead of being substituted.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TemplateError {
+    /// Character offset of the problem in the template
+    pub position: usize,
+    pub message: String,
+}
+
+impl fmt::Display for TemplateError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} (at character {})", self.message, self.position + 1)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum TemplateFilter {
+    Upper,
+    Lower,
+    Capitalize,
+    Trim,
+    Truncate { length: usize, suffix: String },
+    /// Round a number, dropping trailing zeros
+    Round(usize),
+    /// A fixed number of decimals, with thousands separators
+    Num(usize),
+    Default(String),
+    PadLeft(usize, char),
+    PadRight(usize, char),
+}
+
+impl TemplateFilter {
+    pub fn apply(&self, value: String) -> String {
+        match self {
+            TemplateFilter::Upper => value.to_uppercase(),
+            TemplateFilter::Lower => value.to_lowercase(),
+            TemplateFilter::Capitalize => {
+                let mut chars = value.chars();
+                match chars.next() {
+                    Some(first) => first.to_uppercase().chain(chars).collect(),
+                    None => value,
+                }
+            }
+            TemplateFilter::Trim => value.trim().to_string(),
+            TemplateFilter::Truncate { length, suffix } => {
+                if value.chars().count() <= *length {
+                    return value;
+                }
+                let kept = length.saturating_sub(suffix.chars().count());
+                value.chars().take(kept).chain(suffix.chars()).take(*length).collect()
+            }
+            TemplateFilter::Round(decimals) => match parse_value(&value) {
+                Some(number) => {
+                    let rounded = format!("{:.*}", decimals, round_to(number, *decimals));
+                    let rounded = if rounded.contains('.') { rounded.trim_end_matches('0').trim_end_matches('.') } else { &rounded };
+                    if rounded == "-0" { String::from("0") } else { rounded.to_string() }
+                }
+                None => value,
+            },
+            TemplateFilter::Num(decimals) => match parse_value(&value) {
+                Some(number) => group_thousands(&format!("{:.*}", decimals, round_to(number, *decimals))),
+                None => value,
+            },
+            TemplateFilter::Default(text) => if value.trim().is_empty() { text.clone() } else { value },
+            TemplateFilter::PadLeft(width, fill) => {
+                let padding = width.saturating_sub(value.chars().count());
+                std::iter::repeat_n(*fill, padding).chain(value.chars()).collect()
+            }
+            TemplateFilter::PadRight(width, fill) => {
+                let padding = width.saturating_sub(value.chars().count());
+                value.chars().chain(std::iter::repeat_n(*fill, padding)).collect()
+            }
+        }
+    }
+
+    fn parse(name: &str, args: Vec<String>, position: usize) -> Result<TemplateFilter, TemplateError> {
+        let error = |message: String| TemplateError { position, message };
+        let count = |arg: Option<&String>, required: bool| -> Result<Option<usize>, TemplateError> {
+            match arg {
+                Some(arg) => arg.trim().parse().map(Some).map_err(|_| error(format!("'{}' expects a whole number, not '{}'", name, arg))),
+                None if required => Err(error(format!("'{}' needs a number", name))),
+                None => Ok(None),
+            }
+        };
+        let fill = |arg: Option<&String>| -> Result<char, TemplateError> {
+            match arg.map(|arg| arg.chars().collect::<Vec<_>>()).as_deref() {
+                None => Ok(' '),
+                Some([fill]) => Ok(*fill),
+                Some(_) => Err(error(format!("'{}' pads with a single character", name))),
+            }
+        };
+        let max_args = match name {
+            "upper" | "lower" | "capitalize" | "trim" => 0,
+            "round" | "num" => 1,
+            "truncate" | "pad_left" | "pad_right" => 2,
+            "default" => usize::MAX,
+            _ => return Err(error(format!("Unknown filter '{}'", name))),
+        };
+        if args.len() > max_args {
+            return Err(error(format!("'{}' takes at most {} argument(s)", name, max_args)));
+        }
+        Ok(match name {
+            "upper" => TemplateFilter::Upper,
+            "lower" => TemplateFilter::Lower,
+            "capitalize" => TemplateFilter::Capitalize,
+            "trim" => TemplateFilter::Trim,
+            "round" => TemplateFilter::Round(count(args.first(), false)?.unwrap_or(0)),
+            "num" => TemplateFilter::Num(count(args.first(), false)?.unwrap_or(0)),
+            "truncate" => TemplateFilter::Truncate {
+                length: count(args.first(), true)?.unwrap_or_default(),
+                suffix: args.get(1).cloned().unwrap_or_default(),
+            },
+            "pad_left" => TemplateFilter::PadLeft(count(args.first(), true)?.unwrap_or_default(), fill(args.get(1))?),
+            "pad_right" => TemplateFilter::PadRight(count(args.first(), true)?.unwrap_or_default(), fill(args.get(1))?),
+            // Defaults are free text, so a time like 12:00 doesn't need escaping
+            _ => TemplateFilter::Default(args.join(":")),
+        })
+    }
+}
+
+fn parse_value(value: &str) -> Option<f64> {
+    value.trim().parse::<f64>().ok().filter(|number| number.is_finite())
+}
+
+/// Rounds halves away from zero like the expression `round()` does, where formatting alone would round them to even
+fn round_to(number: f64, decimals: usize) -> f64 {
+    let factor = 10f64.powi(decimals as i32);
+    (number * factor).round() / factor
+}
+
+fn group_thousands(number: &str) -> String {
+    let (sign, number) = number.strip_prefix('-').map(|rest| ("-", rest)).unwrap_or(("", number));
+    let (whole, fraction) = number.split_once('.').map(|(whole, fraction)| (whole, Some(fraction))).unwrap_or((number, None));
+    let mut grouped = String::from(sign);
+    for (i, digit) in whole.chars().enumerate() {
+        if i > 0 && (whole.len() - i) % 3 == 0 {
+            grouped.push(',');
+        }
+        grouped.push(digit);
+    }
+    if let Some(fraction) = fraction {
+        grouped.push('.');
+        grouped.push_str(fraction);
+    }
+    grouped
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum TemplatePart {
+    Text(String),
+    Placeholder { variable: String, filters: Vec<TemplateFilter> },
+}
+
+/// Text split into literal parts and variable placeholders
+#[derive(Clone, Debug, PartialEq)]
+pub struct Template {
+    parts: Vec<TemplatePart>,
+    errors: Vec<TemplateError>,
+}
+
+impl Template {
+    /// Only names `is_variable` accepts become placeholders, so other text between double underscores is left alone.
+    /// Where a name could end in more than one place the shortest variable name wins.
+    pub fn parse(text: &str, is_variable: impl Fn(&str) -> bool) -> Template {
+        let chars: Vec<char> = text.chars().collect();
+        let mut template = Template { parts: Vec::new(), errors: Vec::new() };
+        let mut literal = String::new();
+        let mut i = 0;
+        while i < chars.len() {
+            if chars[i] == '\\' && starts_with_marker(&chars, i + 1) {
+                literal.push_str("__");
+                i += 3;
+                continue;
+            }
+            if !starts_with_marker(&chars, i) {
+                literal.push(chars[i]);
+                i += 1;
+                continue;
+            }
+            let Some((name_end, has_filters)) = find_variable(&chars, i + 2, &is_variable) else {
+                literal.push(chars[i]);
+                i += 1;
+                continue;
+            };
+            let variable: String = chars[i + 2..name_end].iter().collect();
+            let (filters, end) = if has_filters {
+                match parse_filters(&chars, name_end + 1) {
+                    Ok(parsed) => parsed,
+                    Err(error) => {
+                        // Skip past the broken placeholder so its text is shown as written
+                        let end = find_close(&chars, name_end + 1).map(|close| close + 2).unwrap_or(chars.len());
+                        literal.extend(&chars[i..end]);
+                        template.errors.push(error);
+                        i = end;
+                        continue;
+                    }
+                }
+            } else {
+                (Vec::new(), name_end + 2)
+            };
+            if !literal.is_empty() {
+                template.parts.push(TemplatePart::Text(std::mem::take(&mut literal)));
+            }
+            template.parts.push(TemplatePart::Placeholder { variable, filters });
+            i = end;
+        }
+        if !literal.is_empty() {
+            template.parts.push(TemplatePart::Text(literal));
+        }
+        template
+    }
+
+    pub fn errors(&self) -> &[TemplateError] {
+        &self.errors
+    }
+
+    /// Variables the template uses, in order, possibly repeated
+    pub fn variables(&self) -> impl Iterator<Item = &str> {
+        self.parts.iter().filter_map(|part| match part {
+            TemplatePart::Placeholder { variable, .. } => Some(variable.as_str()),
+            TemplatePart::Text(_) => None,
+        })
+    }
+
+    pub fn render(&self, value: impl Fn(&str) -> String) -> String {
+        self.parts.iter().map(|part| match part {
+            TemplatePart::Text(text) => text.clone(),
+            TemplatePart::Placeholder { variable, filters } => {
+                filters.iter().fold(value(variable), |value, filter| filter.apply(value))
+            }
+        }).collect()
+    }
+}
+
+fn starts_with_marker(chars: &[char], i: usize) -> bool {
+    chars.get(i) == Some(&'_') && chars.get(i + 1) == Some(&'_')
+}
+
+/// The end of a variable name starting at `start`, and whether filters follow it
+fn find_variable(chars: &[char], start: usize, is_variable: &impl Fn(&str) -> bool) -> Option<(usize, bool)> {
+    (start + 1..chars.len()).find_map(|end| {
+        let has_filters = chars[end] == '|';
+        if !has_filters && !starts_with_marker(chars, end) {
+            return None;
+        }
+        let name: String = chars[start..end].iter().collect();
+        is_variable(&name).then_some((end, has_filters))
+    })
+}
+
+/// Where the unescaped `__` closing a placeholder starts
+fn find_close(chars: &[char], start: usize) -> Option<usize> {
+    let mut i = start;
+    while i < chars.len() {
+        if chars[i] == '\\' {
+            i += 2;
+        } else if starts_with_marker(chars, i) {
+            return Some(i);
+        } else {
+            i += 1;
+        }
+    }
+    None
+}
+
+/// Filters from `start` up to the closing `__`, and the position just after it
+fn parse_filters(chars: &[char], start: usize) -> Result<(Vec<TemplateFilter>, usize), TemplateError> {
+    let close = find_close(chars, start).ok_or_else(|| TemplateError {
+        position: start.saturating_sub(1),
+        message: String::from("Placeholder is missing its closing '__'"),
+    })?;
+    let mut filters = Vec::new();
+    let mut i = start;
+    loop {
+        let filter_start = i;
+        // The filter's name followed by its arguments
+        let mut pieces = vec![String::new()];
+        while i < close && chars[i] != '|' {
+            match chars[i] {
+                '\\' if i + 1 < close => {
+                    pieces.last_mut().unwrap().push(chars[i + 1]);
+                    i += 2;
+                    continue;
+                }
+                ':' => pieces.push(String::new()),
+                c => pieces.last_mut().unwrap().push(c),
+            }
+            i += 1;
+        }
+        let name = pieces.remove(0);
+        let name = name.trim();
+        if name.is_empty() {
+            return Err(TemplateError { position: filter_start, message: String::from("Missing filter name") });
+        }
+        filters.push(TemplateFilter::parse(name, pieces, filter_start)?);
+        if i >= close {
+            return Ok((filters, close + 2));
+        }
+        i += 1;
+    }
+}
@@ -0,0 +1,336 @@
+use std::time::Duration;
+
+use bdf2::BoundingBox;
+
+use crate::boards::{BoardElement, HorizontalAlignment, MarqueeSettings, ScrollDirection, TextBoxSettings, TextOverflow, VerticalAlignment};
+
+// Frames faster than this are more than devices can keep up with
+const MIN_MARQUEE_FRAME_INTERVAL: Duration = Duration::from_millis(20);
+const ELLIPSIS: &str = "...";
+
+/// Measurements text layout needs from a font
+pub trait FontMetrics {
+    fn has_glyph(&self, character: char) -> bool;
+    /// Horizontal distance from a character's origin to the next one
+    fn advance(&self, character: char) -> u32;
+    fn glyph_bounds(&self, character: char) -> Option<BoundingBox>;
+    /// Distance from the top of the font's bounding box down to the baseline
+    fn ascent(&self) -> i32;
+    /// Distance from the baseline down to the bottom of the font's bounding box
+    fn descent(&self) -> i32;
+    fn line_height(&self) -> u32;
+}
+
+impl FontMetrics for bdf2::Font {
+    fn has_glyph(&self, character: char) -> bool {
+        self.glyphs().contains_key(&character)
+    }
+    /// Characters the font does not have take up the width of the font's bounding box
+    fn advance(&self, character: char) -> u32 {
+        match self.glyphs().get(&character) {
+            Some(glyph) => glyph.device_width().map(|(x, _)| *x).unwrap_or(glyph.width()),
+            None => self.bounds().width,
+        }
+    }
+    fn glyph_bounds(&self, character: char) -> Option<BoundingBox> {
+        self.glyphs().get(&character).map(|glyph| *glyph.bounds())
+    }
+    fn ascent(&self) -> i32 {
+        self.bounds().height as i32 + self.bounds().y
+    }
+    fn descent(&self) -> i32 {
+        -self.bounds().y
+    }
+    fn line_height(&self) -> u32 {
+        self.bounds().height
+    }
+}
+
+/// Area text is aligned within, in board pixels
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct LayoutBox {
+    pub x: i32,
+    pub y: i32,
+    pub width: u32,
+    pub height: u32,
+}
+impl LayoutBox {
+    pub fn contains(&self, x: i32, y: i32) -> bool {
+        x >= self.x && x < self.x + self.width as i32 && y >= self.y && y < self.y + self.height as i32
+    }
+}
+
+/// A character and where it goes, `y` is the top of the font's bounding box (as `t` commands expect)
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PositionedGlyph {
+    pub character: char,
+    pub x: i32,
+    pub y: i32,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct TextLayout {
+    pub glyphs: Vec<PositionedGlyph>,
+    pub x: i32,
+    pub y: i32,
+    pub width: u32,
+    pub height: u32,
+}
+
+pub fn measure_text(font: &impl FontMetrics, text: &str) -> u32 {
+    text.chars().map(|character| font.advance(character)).sum()
+}
+
+/// Lays a single line of text out within `layout_box`. Text wider than the box starts at its left edge.
+pub fn layout_text(
+    font: &impl FontMetrics,
+    text: &str,
+    layout_box: &LayoutBox,
+    horizontal: HorizontalAlignment,
+    vertical: VerticalAlignment,
+) -> TextLayout {
+    let width = measure_text(font, text);
+    let height = font.line_height();
+    let spare_width = layout_box.width.saturating_sub(width) as i32;
+    let x = layout_box.x
+        + match horizontal {
+            HorizontalAlignment::Auto | HorizontalAlignment::Left => 0,
+            HorizontalAlignment::Center => spare_width / 2,
+            HorizontalAlignment::Right => spare_width,
+        };
+    let spare_height = layout_box.height as i32 - height as i32;
+    let y = match vertical {
+        VerticalAlignment::Top => layout_box.y,
+        VerticalAlignment::Middle => layout_box.y + spare_height.div_euclid(2),
+        VerticalAlignment::Bottom => layout_box.y + spare_height,
+        VerticalAlignment::Baseline => layout_box.y - font.ascent(),
+    };
+    let mut glyphs = Vec::new();
+    let mut pen_x = x;
+    for character in text.chars() {
+        glyphs.push(PositionedGlyph { character, x: pen_x, y });
+        pen_x += font.advance(character) as i32;
+    }
+    TextLayout { glyphs, x, y, width, height }
+}
+
+/// The box an element's text is laid out in. Without a text box the text is aligned between `x` and the right
+/// edge of the board on a single line.
+pub fn element_text_box(element: &BoardElement, board_size: (u8, u8), font: &impl FontMetrics) -> LayoutBox {
+    let x = element.x.unwrap_or(0) as i32;
+    let (width, height) = match element.text_box {
+        Some((width, height)) => (width as u32, height as u32),
+        None => ((board_size.0 as i32 - x).max(0) as u32, font.line_height()),
+    };
+    LayoutBox { x, y: element.y as i32, width, height }
+}
+
+/// Lays out the text of a board element using its position, box and alignment settings
+pub fn layout_element_text(element: &BoardElement, board_size: (u8, u8), font: &impl FontMetrics, text: &str) -> TextLayout {
+    let layout_box = element_text_box(element, board_size, font);
+    layout_text(font, text, &layout_box, element.get_horizontal_alignment(), element.vertical_alignment)
+}
+
+/// Lays out a marquee `elapsed` into its scroll. Text that fits in the box is laid out like any other text,
+/// anything wider starts at the left edge and is shifted along by the scroll.
+pub fn layout_marquee(
+    element: &BoardElement,
+    board_size: (u8, u8),
+    font: &impl FontMetrics,
+    text: &str,
+    settings: &MarqueeSettings,
+    elapsed: Duration,
+) -> TextLayout {
+    let layout_box = element_text_box(element, board_size, font);
+    let overflow = measure_text(font, text).saturating_sub(layout_box.width);
+    if overflow == 0 {
+        return layout_text(font, text, &layout_box, element.get_horizontal_alignment(), element.vertical_alignment);
+    }
+    let mut layout = layout_text(font, text, &layout_box, HorizontalAlignment::Left, element.vertical_alignment);
+    let offset = marquee_offset(settings, overflow, elapsed) as i32;
+    layout.x -= offset;
+    for glyph in &mut layout.glyphs {
+        glyph.x -= offset;
+    }
+    layout
+}
+
+/// How far the text has scrolled `elapsed` into the animation. Each loop holds at the start, scrolls through
+/// the `overflow` pixels that do not fit and holds at the end.
+pub fn marquee_offset(settings: &MarqueeSettings, overflow: u32, elapsed: Duration) -> u32 {
+    let pause = Duration::from_millis(settings.pause as u64);
+    let scroll = marquee_scroll_time(settings, overflow);
+    let loop_time = pause * 2 + scroll;
+    let offset = if elapsed >= marquee_duration(settings, overflow) || loop_time.is_zero() {
+        overflow
+    } else {
+        let into_loop = Duration::from_nanos((elapsed.as_nanos() % loop_time.as_nanos()) as u64);
+        let scrolled = into_loop.saturating_sub(pause).min(scroll);
+        (scrolled.as_millis() as u64 * get_speed(settings) as u64 / 1000) as u32
+    };
+    match settings.direction {
+        ScrollDirection::Left => offset.min(overflow),
+        ScrollDirection::Right => overflow - offset.min(overflow),
+    }
+}
+
+/// How long every loop of the marquee takes, zero when the text fits and nothing needs to scroll
+pub fn marquee_duration(settings: &MarqueeSettings, overflow: u32) -> Duration {
+    if overflow == 0 {
+        return Duration::ZERO;
+    }
+    let loop_time = Duration::from_millis(settings.pause as u64) * 2 + marquee_scroll_time(settings, overflow);
+    loop_time * settings.loops.max(1) as u32
+}
+
+/// Time between frames so the text moves a pixel at a time
+pub fn marquee_frame_interval(settings: &MarqueeSettings) -> Duration {
+    (Duration::from_secs(1) / get_speed(settings) as u32).max(MIN_MARQUEE_FRAME_INTERVAL)
+}
+
+fn marquee_scroll_time(settings: &MarqueeSettings, overflow: u32) -> Duration {
+    Duration::from_millis(overflow as u64 * 1000 / get_speed(settings) as u64)
+}
+
+fn get_speed(settings: &MarqueeSettings) -> u8 {
+    settings.speed.max(1)
+}
+
+/// The box a text box element wraps its text within: its text box, or the rest of the board from (x, y)
+pub fn element_wrap_box(element: &BoardElement, board_size: (u8, u8)) -> LayoutBox {
+    let x = element.x.unwrap_or(0) as i32;
+    let (width, height) = match element.text_box {
+        Some((width, height)) => (width as u32, height as u32),
+        None => ((board_size.0 as i32 - x).max(0) as u32, (board_size.1 as i32 - element.y as i32).max(0) as u32),
+    };
+    LayoutBox { x, y: element.y as i32, width, height }
+}
+
+/// Splits text into lines no wider than `width`, breaking between words where possible. Words too long for a
+/// line of their own are broken wherever they reach the edge.
+pub fn wrap_text(font: &impl FontMetrics, text: &str, width: u32) -> Vec<String> {
+    let mut lines = Vec::new();
+    for paragraph in text.lines() {
+        let mut line = String::new();
+        for word in paragraph.split_whitespace() {
+            let candidate = if line.is_empty() { word.to_string() } else { format!("{} {}", line, word) };
+            if measure_text(font, &candidate) <= width {
+                line = candidate;
+                continue;
+            }
+            if !line.is_empty() {
+                lines.push(std::mem::take(&mut line));
+            }
+            for character in word.chars() {
+                if !line.is_empty() && measure_text(font, &line) + font.advance(character) > width {
+                    lines.push(std::mem::take(&mut line));
+                }
+                line.push(character);
+            }
+        }
+        lines.push(line);
+    }
+    lines
+}
+
+/// How many lines fit in `height`, always at least one
+pub fn lines_per_page(font: &impl FontMetrics, height: u32, line_spacing: u8) -> usize {
+    let pitch = font.line_height() + line_spacing as u32;
+    (((height + line_spacing as u32) / pitch.max(1)) as usize).max(1)
+}
+
+/// Whether wrapped text fits in the box without overflowing
+pub fn text_fits(font: &impl FontMetrics, text: &str, layout_box: &LayoutBox, line_spacing: u8) -> bool {
+    wrap_text(font, text, layout_box.width).len() <= lines_per_page(font, layout_box.height, line_spacing)
+}
+
+/// Wraps text and splits it into what is shown at once. Only paging gives more than one page, otherwise the
+/// text is cut off with an ellipsis.
+pub fn paginate_text(font: &impl FontMetrics, text: &str, layout_box: &LayoutBox, settings: &TextBoxSettings) -> Vec<Vec<String>> {
+    let lines = wrap_text(font, text, layout_box.width);
+    let page_length = lines_per_page(font, layout_box.height, settings.line_spacing);
+    if let TextOverflow::Page(_) = settings.overflow {
+        return lines.chunks(page_length).map(|x| x.to_vec()).collect();
+    }
+    if lines.len() <= page_length {
+        return vec![lines];
+    }
+    let mut page = lines[..page_length].to_vec();
+    if let Some(last_line) = page.last_mut() {
+        while !last_line.is_empty() && measure_text(font, &format!("{}{}", last_line, ELLIPSIS)) > layout_box.width {
+            last_line.pop();
+        }
+        *last_line = format!("{}{}", last_line.trim_end(), ELLIPSIS);
+    }
+    vec![page]
+}
+
+/// Index of the page shown `elapsed` after the board was drawn, the last page stays up once every page is shown
+pub fn page_at(settings: &TextBoxSettings, pages: usize, elapsed: Duration) -> usize {
+    match settings.overflow {
+        TextOverflow::Page(page_time) if pages > 1 && page_time > 0 => {
+            ((elapsed.as_millis() / page_time as u128) as usize).min(pages - 1)
+        }
+        _ => 0,
+    }
+}
+
+/// How long it takes to show every page once, zero when there is only one
+pub fn text_box_duration(settings: &TextBoxSettings, pages: usize) -> Duration {
+    match settings.overflow {
+        TextOverflow::Page(page_time) if pages > 1 => Duration::from_millis(page_time as u64) * pages as u32,
+        _ => Duration::ZERO,
+    }
+}
+
+/// Lays out several lines as a block aligned within `layout_box`, each line aligned on its own
+pub fn layout_lines(
+    font: &impl FontMetrics,
+    lines: &[String],
+    layout_box: &LayoutBox,
+    horizontal: HorizontalAlignment,
+    vertical: VerticalAlignment,
+    line_spacing: u8,
+) -> TextLayout {
+    let pitch = font.line_height() as i32 + line_spacing as i32;
+    let block_height = (pitch * lines.len() as i32 - line_spacing as i32).max(0);
+    let spare_height = layout_box.height as i32 - block_height;
+    let y = match vertical {
+        VerticalAlignment::Top => layout_box.y,
+        VerticalAlignment::Middle => layout_box.y + spare_height.div_euclid(2),
+        VerticalAlignment::Bottom => layout_box.y + spare_height,
+        VerticalAlignment::Baseline => layout_box.y - font.ascent(),
+    };
+    let mut layout = TextLayout { glyphs: Vec::new(), x: layout_box.x, y, width: 0, height: block_height as u32 };
+    let mut left = i32::MAX;
+    for (idx, line) in lines.iter().enumerate() {
+        let line_box = LayoutBox { x: layout_box.x, y: y + pitch * idx as i32, width: layout_box.width, height: font.line_height() };
+        let line_layout = layout_text(font, line, &line_box, horizontal, VerticalAlignment::Top);
+        left = left.min(line_layout.x);
+        layout.width = layout.width.max((line_layout.x + line_layout.width as i32 - layout_box.x) as u32);
+        layout.glyphs.extend(line_layout.glyphs);
+    }
+    if left != i32::MAX {
+        layout.width -= (left - layout_box.x) as u32;
+        layout.x = left;
+    }
+    layout
+}
+
+/// Board pixels lit by a glyph drawn with the top of the font's bounding box at (x, y)
+pub fn glyph_pixels(font: &bdf2::Font, character: char, x: i32, y: i32) -> Vec<(i32, i32)> {
+    let Some(glyph) = font.glyphs().get(&character) else {
+        return Vec::new();
+    };
+    let baseline = y + font.ascent();
+    let origin_x = x + glyph.bounds().x;
+    let origin_y = baseline - (glyph.bounds().height as i32 + glyph.bounds().y);
+    // Plain loop on purpose, bdf2's `size_hint` for pixel iterators overflows
+    let mut pixels = Vec::new();
+    for ((glyph_x, glyph_y), lit) in glyph.pixels() {
+        if lit {
+            pixels.push((origin_x + glyph_x as i32, origin_y + glyph_y as i32));
+        }
+    }
+    pixels
+}
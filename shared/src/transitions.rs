@@ -0,0 +1,128 @@
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+/// Time between transition frames, 25 frames a second
+pub const TRANSITION_FRAME_INTERVAL: Duration = Duration::from_millis(40);
+/// Longest transition a device can be set to
+pub const MAX_TRANSITION_TIME: u16 = 5000;
+
+/// How the next board replaces the one on screen
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, PartialOrd, Debug, Default)]
+pub enum TransitionEffect {
+    /// Clear and redraw straight away
+    #[default]
+    None,
+    CrossFade,
+    /// The next board pushes the current one off to the left
+    SlideLeft,
+    /// The next board pushes the current one off the top
+    SlideUp,
+    /// The next board is uncovered from left to right
+    Wipe,
+    /// Pixels switch over one at a time in a scattered order
+    Dissolve,
+}
+impl TransitionEffect {
+    pub fn get_option(&self) -> String {
+        match self {
+            TransitionEffect::None => TransitionEffect::get_options()[0].clone(),
+            TransitionEffect::CrossFade => TransitionEffect::get_options()[1].clone(),
+            TransitionEffect::SlideLeft => TransitionEffect::get_options()[2].clone(),
+            TransitionEffect::SlideUp => TransitionEffect::get_options()[3].clone(),
+            TransitionEffect::Wipe => TransitionEffect::get_options()[4].clone(),
+            TransitionEffect::Dissolve => TransitionEffect::get_options()[5].clone(),
+        }
+    }
+    pub fn get_options() -> Vec<String> {
+        "None;Cross Fade;Slide Left;Slide Up;Wipe;Dissolve".split(';').map(|x|x.to_string()).collect()
+    }
+    pub fn from_option(type_str: &str) -> TransitionEffect {
+        match type_str {
+            "Cross Fade" => TransitionEffect::CrossFade,
+            "Slide Left" => TransitionEffect::SlideLeft,
+            "Slide Up" => TransitionEffect::SlideUp,
+            "Wipe" => TransitionEffect::Wipe,
+            "Dissolve" => TransitionEffect::Dissolve,
+            _ => TransitionEffect::None,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, PartialOrd, Debug)]
+pub struct BoardTransition {
+    pub effect: TransitionEffect,
+    /// Milliseconds
+    pub duration: u16,
+}
+impl Default for BoardTransition {
+    fn default() -> Self {
+        Self {
+            effect: TransitionEffect::None,
+            duration: 500,
+        }
+    }
+}
+impl BoardTransition {
+    /// Whether the transition has any frames to show
+    pub fn is_active(&self) -> bool {
+        self.effect != TransitionEffect::None && self.duration > 0
+    }
+
+    pub fn duration(&self) -> Duration {
+        Duration::from_millis(self.duration.min(MAX_TRANSITION_TIME) as u64)
+    }
+
+    /// How many frames are sent between the two boards, the last of which is the next board
+    pub fn frame_count(&self) -> u32 {
+        (self.duration().as_millis() / TRANSITION_FRAME_INTERVAL.as_millis()).max(1) as u32
+    }
+}
+
+/// The pixels shown `progress` (0 to 1) of the way from `from` to `to`. Both are `width` by `height`, row by row.
+pub fn transition_frame(from: &[[u8; 3]], to: &[[u8; 3]], width: u32, height: u32, effect: TransitionEffect, progress: f64) -> Vec<[u8; 3]> {
+    let progress = progress.clamp(0., 1.);
+    let (width, height) = (width as usize, height as usize);
+    let mut pixels = Vec::with_capacity(width * height);
+    for y in 0..height {
+        for x in 0..width {
+            let idx = y * width + x;
+            let pixel = match effect {
+                TransitionEffect::None => to[idx],
+                TransitionEffect::CrossFade => {
+                    let mut pixel = [0; 3];
+                    for channel in 0..3 {
+                        let (start, end) = (from[idx][channel] as f64, to[idx][channel] as f64);
+                        pixel[channel] = (start + (end - start) * progress).round() as u8;
+                    }
+                    pixel
+                }
+                TransitionEffect::SlideLeft => {
+                    let offset = (progress * width as f64).round() as usize;
+                    if x + offset < width { from[idx + offset] } else { to[idx + offset - width] }
+                }
+                TransitionEffect::SlideUp => {
+                    let offset = (progress * height as f64).round() as usize;
+                    if y + offset < height { from[idx + offset * width] } else { to[(y + offset - height) * width + x] }
+                }
+                TransitionEffect::Wipe => {
+                    if x < (progress * width as f64).round() as usize { to[idx] } else { from[idx] }
+                }
+                TransitionEffect::Dissolve => {
+                    if dissolve_order(idx) < progress { to[idx] } else { from[idx] }
+                }
+            };
+            pixels.push(pixel);
+        }
+    }
+    pixels
+}
+
+/// When a pixel switches over during a dissolve, from 0 up to (but not including) 1. Scattered, but the same every time.
+fn dissolve_order(idx: usize) -> f64 {
+    let mut hash = (idx as u32).wrapping_add(1).wrapping_mul(0x9E37_79B9);
+    hash ^= hash >> 15;
+    hash = hash.wrapping_mul(0x85EB_CA6B);
+    hash ^= hash >> 13;
+    hash as f64 / (u32::MAX as f64 + 1.)
+}
@@ -7,6 +7,7 @@ use eframe::egui;
 use shared::{
     board_variables::BoardVariables,
     boards::BoardDefinition,
+    date_names::DateNameTables,
     device_config::DeviceConfigs,
 };
 
@@ -14,10 +15,11 @@ use crate::{
     get::get,
     windows::{
         boards::{
-            board_add::render_board_add, board_delete::render_board_delete, board_editor::render_board_editor, board_list::render_board_list
+            board_add::render_board_add, board_canvas::{CanvasAssetCache, CanvasDrag}, board_delete::render_board_delete, board_editor::render_board_editor, board_list::render_board_list
         },
-        devices::{device_delete::render_device_delete, device_editor::render_device_editor, device_list::render_device_list},
-        vars::{var_add::render_var_add, var_delete::render_var_delete, var_editor::render_var_editor, var_list::render_var_list},
+        devices::{device_delete::render_device_delete, device_editor::render_device_editor, device_list::render_device_list, virtual_device::{render_virtual_device, VirtualDevice}},
+        images::image_manager::{render_image_manager, ImageManager},
+        vars::{var_add::render_var_add, var_delete::render_var_delete, var_editor::{render_var_editor, ExpressionPreview}, var_list::render_var_list},
     },
 };
 
@@ -27,6 +29,8 @@ pub struct MyEguiApp {
     pub board_editor_open: bool,
     pub var_editor_open: bool,
     pub device_editor_open: bool,
+    pub virtual_device_open: bool,
+    pub image_manager_open: bool,
 }
 
 #[derive(Default)]
@@ -49,13 +53,21 @@ pub struct State {
     pub var_name_edit: Option<String>,
     pub rename_var: Option<(String, String)>,
     pub deleting_var: Option<String>,
+    pub expression_preview: ExpressionPreview,
+    /// Custom weekday and month names from the config
+    pub date_names: Arc<Mutex<DateNameTables>>,
     //
     pub devices: Arc<Mutex<DeviceConfigs>>,
     pub current_device: Option<String>,
     pub devices_has_changed: bool,
     pub deleting_device: Option<(String, String)>,
+    pub virtual_device: VirtualDevice,
     //
     pub images: Arc<Mutex<Vec<String>>>,
+    pub image_hashes: Arc<Mutex<HashMap<String, String>>>,
+    pub canvas_assets: Arc<Mutex<CanvasAssetCache>>,
+    pub canvas_drag: Option<CanvasDrag>,
+    pub image_manager: ImageManager,
     //
     pub current_editor: Option<u8>,
 }
@@ -83,15 +95,18 @@ impl MyEguiApp {
         let board_data: Arc<Mutex<HashMap<String, BoardDefinition>>> =
             Arc::new(Mutex::new(HashMap::new()));
         let var_data: Arc<Mutex<BoardVariables>> = Arc::new(Mutex::new(BoardVariables::new()));
+        let date_name_data: Arc<Mutex<DateNameTables>> = Arc::new(Mutex::new(DateNameTables::new()));
         let device_data: Arc<Mutex<DeviceConfigs>> = Arc::new(Mutex::new(DeviceConfigs::new()));
         let font_data: Arc<Mutex<Vec<String>>> = Arc::new(Mutex::new(Vec::new()));
         let image_data: Arc<Mutex<Vec<String>>> = Arc::new(Mutex::new(Vec::new()));
+        let image_hash_data: Arc<Mutex<HashMap<String, String>>> = Arc::new(Mutex::new(HashMap::new()));
 
         // Load Boards from server...
         get("/api/boards", board_data.clone());
 
         // Load Variables from server...
         get("/api/vars", var_data.clone());
+        get("/api/date_names", date_name_data.clone());
 
         // Load Device Configs from server...
         get("/api/devices", device_data.clone());
@@ -101,14 +116,17 @@ impl MyEguiApp {
 
         // Load Images from server...
         get("/api/image_list", image_data.clone());
+        get("/api/images", image_hash_data.clone());
 
         let setup = MyEguiApp {
             state: Arc::new(Mutex::new(State {
                 boards: board_data,
                 vars: var_data,
+                date_names: date_name_data,
                 devices: device_data,
                 fonts: font_data,
                 images: image_data,
+                image_hashes: image_hash_data,
                 ..Default::default()
             })),
             ..Default::default()
@@ -126,7 +144,7 @@ impl eframe::App for MyEguiApp {
     }
     fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
         egui::CentralPanel::default().show(ctx, |_ui| {
-            render_board_list(ctx, self.state.clone(), &mut self.board_editor_open);
+            render_board_list(ctx, self.state.clone(), &mut self.board_editor_open, &mut self.image_manager_open);
             render_board_editor(ctx, self.state.clone(), &mut self.board_editor_open);
             render_board_add(ctx, self.state.clone());
             render_board_delete(ctx, self.state.clone());
@@ -136,9 +154,12 @@ impl eframe::App for MyEguiApp {
             render_var_add(ctx, self.state.clone());
             render_var_delete(ctx, self.state.clone());
             //
-            render_device_list(ctx, self.state.clone(), &mut self.device_editor_open);
+            render_device_list(ctx, self.state.clone(), &mut self.device_editor_open, &mut self.virtual_device_open);
             render_device_editor(ctx, self.state.clone(), &mut self.device_editor_open);
             render_device_delete(ctx, self.state.clone());
+            render_virtual_device(ctx, self.state.clone(), &mut self.virtual_device_open);
+            //
+            render_image_manager(ctx, self.state.clone(), &mut self.image_manager_open);
             //
             // Lock State
             let mut state = self.state.lock().unwrap();
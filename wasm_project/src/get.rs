@@ -30,3 +30,34 @@ where
         *data = new_data.into();
     });
 }
+
+/// Fetches a binary file (fonts, images) and hands the body to `on_load`, or `None` if the request failed
+pub(crate) fn get_bytes<F>(endpoint: &str, on_load: F)
+where
+    F: FnOnce(Option<Vec<u8>>) + 'static,
+{
+    let window = web_sys::window().expect("No window");
+    let opts = RequestInit::new();
+    opts.set_method("GET");
+    //
+    let req = Request::new_with_str_and_init(endpoint, &opts).unwrap();
+    let resp = wasm_bindgen_futures::JsFuture::from(window.fetch_with_request(&req));
+    let endpoint = endpoint.to_string();
+    spawn_local(async move {
+        let resp: Response = match resp.await {
+            Ok(resp_value) => resp_value.dyn_into().unwrap(),
+            Err(_) => {
+                log::error!("Failed to fetch {}", &endpoint);
+                on_load(None);
+                return;
+            }
+        };
+        if !resp.ok() {
+            log::error!("Failed to fetch {} ({})", &endpoint, resp.status());
+            on_load(None);
+            return;
+        }
+        let buffer = JsFuture::from(resp.array_buffer().unwrap()).await.unwrap();
+        on_load(Some(js_sys::Uint8Array::new(&buffer).to_vec()));
+    });
+}
@@ -1,6 +1,6 @@
 use wasm_bindgen::{JsCast, JsValue};
-use wasm_bindgen_futures::spawn_local;
-use web_sys::{Headers, Request, RequestInit, Response};
+use wasm_bindgen_futures::{spawn_local, JsFuture};
+use web_sys::{FormData, Headers, Request, RequestInit, Response};
 
 pub(crate) fn post<T>(endpoint: &str, data: &T)
 where
@@ -26,3 +26,66 @@ where
         }
     });
 }
+
+/// Same as `post`, handing `on_done` the response body, or the server's error message if the request failed
+pub(crate) fn post_then<T, F>(endpoint: &str, data: &T, on_done: F)
+where
+    T: serde::ser::Serialize,
+    F: FnOnce(Result<String, String>) + 'static,
+{
+    let opts = RequestInit::new();
+    opts.set_method("POST");
+    let headers = Headers::new().unwrap();
+    let _ = headers.append("Content-Type", "application/json");
+    opts.set_headers(&headers);
+    opts.set_body(&JsValue::from_str(&serde_json::to_string(data).unwrap()));
+    send(endpoint, &opts, on_done);
+}
+
+/// Posts a multipart form, the browser fills in the content type and boundary itself
+pub(crate) fn post_form<F>(endpoint: &str, form: &FormData, on_done: F)
+where
+    F: FnOnce(Result<String, String>) + 'static,
+{
+    let opts = RequestInit::new();
+    opts.set_method("POST");
+    opts.set_body(form);
+    send(endpoint, &opts, on_done);
+}
+
+pub(crate) fn delete<F>(endpoint: &str, on_done: F)
+where
+    F: FnOnce(Result<String, String>) + 'static,
+{
+    let opts = RequestInit::new();
+    opts.set_method("DELETE");
+    send(endpoint, &opts, on_done);
+}
+
+fn send<F>(endpoint: &str, opts: &RequestInit, on_done: F)
+where
+    F: FnOnce(Result<String, String>) + 'static,
+{
+    let window = web_sys::window().expect("No window");
+    let req = Request::new_with_str_and_init(endpoint, opts).unwrap();
+    let resp = wasm_bindgen_futures::JsFuture::from(window.fetch_with_request(&req));
+    spawn_local(async move {
+        let resp: Response = match resp.await {
+            Ok(resp_value) => resp_value.dyn_into().unwrap(),
+            Err(_) => {
+                on_done(Err(String::from("Unable to reach the server")));
+                return;
+            }
+        };
+        let text = match resp.text() {
+            Ok(text) => JsFuture::from(text).await.ok().and_then(|x| x.as_string()).unwrap_or_default(),
+            Err(_) => String::new(),
+        };
+        if resp.ok() {
+            on_done(Ok(text));
+        } else {
+            log::error!("Request failed ({}): {}", resp.status(), &text);
+            on_done(Err(text));
+        }
+    });
+}
@@ -0,0 +1,641 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use egui::{Align2, Color32, Painter, Rect, Response, Sense, Stroke, Ui, Vec2};
+use image::{codecs::gif::GifDecoder, AnimationDecoder, ImageFormat};
+use shared::{
+    board_variables::{apply_substring, BoardVariable, BoardVariables},
+    boards::{AnimationSettings, BoardDefinition, BoardElement, BoardElementValue, ColourOption, ElementColour, HorizontalAlignment, TextBoxSettings, TextOverflow},
+    canvas::{MatrixCanvas, PixelImage},
+    expressions::{find_cycle, Expression},
+    graphs::element_graph,
+    image_animation::{animation_duration, animation_frame_at, animation_frame_interval, gif_frame_delay, split_sprite_strip},
+    indicators::{element_indicator, parse_number},
+    shapes::element_shape,
+    templates::Template,
+    device_config::TemperatureColours,
+    text_layout::{
+        element_text_box, element_wrap_box, layout_element_text, layout_lines, layout_marquee, marquee_duration,
+        marquee_frame_interval, measure_text, page_at, paginate_text, text_box_duration, text_fits,
+    },
+};
+
+use crate::{app::State, get::get_bytes};
+
+const DEFAULT_FONT: &str = "5x8";
+// Matches the glyph the server falls back to when a font or character is missing
+const MISSING_GLYPH_SIZE: (u32, u32) = (5, 8);
+const PLACEHOLDER_SIZE: i32 = 8;
+const LED_SPACING: f32 = 1.;
+const LED_OFF_COLOUR: Color32 = Color32::from_rgb(20, 20, 20);
+const HIGHLIGHT_COLOUR: Color32 = Color32::from_rgb(255, 200, 0);
+const PLACEHOLDER_COLOUR: Color32 = Color32::from_rgb(120, 120, 120);
+
+pub enum AssetLoad<T> {
+    Loading,
+    Loaded(T),
+    Failed,
+}
+
+/// Fonts and images fetched from the server for the board canvas, keyed by font name and image path
+#[derive(Default)]
+pub struct CanvasAssetCache {
+    fonts: HashMap<String, AssetLoad<bdf2::Font>>,
+    images: HashMap<String, AssetLoad<PixelImage>>,
+    /// Frames and their delays by image path and sprite frame count
+    animations: HashMap<(String, u8), AssetLoad<Vec<(PixelImage, Duration)>>>,
+}
+impl CanvasAssetCache {
+    fn font(&self, font_name: &str) -> Option<&bdf2::Font> {
+        match self.fonts.get(font_name) {
+            Some(AssetLoad::Loaded(font)) => Some(font),
+            _ => None,
+        }
+    }
+    fn image(&self, image_path: &str) -> Option<&PixelImage> {
+        match self.images.get(image_path) {
+            Some(AssetLoad::Loaded(image)) => Some(image),
+            _ => None,
+        }
+    }
+    fn animation(&self, image_path: &str, sprite_frames: u8) -> Option<&Vec<(PixelImage, Duration)>> {
+        match self.animations.get(&(image_path.to_string(), sprite_frames)) {
+            Some(AssetLoad::Loaded(frames)) => Some(frames),
+            _ => None,
+        }
+    }
+    /// Drops every image so uploaded, renamed or replaced images are fetched again
+    pub(crate) fn forget_images(&mut self) {
+        self.images.clear();
+        self.animations.clear();
+    }
+}
+
+/// Element currently being dragged around the canvas
+pub struct CanvasDrag {
+    element: usize,
+    remainder: Vec2,
+}
+
+/// Area an element covers on the board, in board pixels
+struct ElementBounds {
+    element: usize,
+    /// Where the element's x position is, which is not always the left edge
+    origin_x: i32,
+    x: i32,
+    y: i32,
+    width: i32,
+    height: i32,
+    placeholder: bool,
+}
+impl ElementBounds {
+    fn contains(&self, x: i32, y: i32) -> bool {
+        x >= self.x && x < self.x + self.width && y >= self.y && y < self.y + self.height
+    }
+}
+
+pub fn render_board_canvas(ctx: &egui::Context, board: &mut BoardDefinition, state: Arc<Mutex<State>>) {
+    let (assets, image_hashes, vars) = {
+        let state = state.lock().unwrap();
+        let vars = state.vars.lock().unwrap().clone();
+        (state.canvas_assets.clone(), state.image_hashes.clone(), vars)
+    };
+    request_missing_assets(ctx, board, &vars, assets.clone(), image_hashes);
+    // Keep marquees and paged text moving
+    let frame_interval = board.board_elements.iter().filter_map(|element| match &element.value {
+        BoardElementValue::Marquee(_, settings) => Some(marquee_frame_interval(settings)),
+        BoardElementValue::TextBox(_, TextBoxSettings { overflow: TextOverflow::Page(page_time), .. }) => {
+            Some(Duration::from_millis(*page_time.max(&1) as u64))
+        }
+        BoardElementValue::Animation(path, settings) => {
+            let assets = assets.lock().unwrap();
+            let frames = assets.animation(path, settings.sprite_frames)?;
+            let delays = animation_delays(frames, settings);
+            (delays.len() > 1).then(|| animation_frame_interval(&delays, settings))
+        }
+        _ => None,
+    }).min();
+    if let Some(frame_interval) = frame_interval {
+        ctx.request_repaint_after(frame_interval);
+    }
+    let time = Duration::from_secs_f64(ctx.input(|i| i.time));
+    let max_width = ctx.screen_rect().width() * 0.3;
+    let max_height = ctx.screen_rect().height() * 0.45;
+    egui::Window::new("Board Preview")
+        .anchor(Align2::RIGHT_TOP, [-5.0, 5.0])
+        .resizable(false)
+        .movable(false)
+        .show(ctx, |ui| {
+            if board.size.0 == 0 || board.size.1 == 0 {
+                ui.label("Set a board size to preview it");
+                return;
+            }
+            let scale = (max_width / board.size.0 as f32)
+                .min(max_height / board.size.1 as f32)
+                .floor()
+                .clamp(2., 16.);
+            let (canvas, bounds) = draw_board(board, &vars, &assets.lock().unwrap(), time);
+            let (response, painter) = paint_matrix(ui, &canvas, scale, Sense::drag());
+            let origin = response.rect.min;
+            let to_screen = |bounds: &ElementBounds| {
+                Rect::from_min_size(
+                    origin + Vec2::new(bounds.x as f32 * scale, bounds.y as f32 * scale),
+                    Vec2::new(bounds.width as f32 * scale, bounds.height as f32 * scale),
+                )
+            };
+            let hit_test = |pos: egui::Pos2| {
+                let x = ((pos.x - origin.x) / scale).floor() as i32;
+                let y = ((pos.y - origin.y) / scale).floor() as i32;
+                bounds.iter().rev().find(|bounds| bounds.contains(x, y)).map(|bounds| bounds.element)
+            };
+            for element_bounds in bounds.iter().filter(|x| x.placeholder) {
+                painter.rect_stroke(to_screen(element_bounds), 0., Stroke::new(1., PLACEHOLDER_COLOUR));
+            }
+            // Dragging
+            let mut state = state.lock().unwrap();
+            if response.drag_started() {
+                state.canvas_drag = response
+                    .interact_pointer_pos()
+                    .and_then(hit_test)
+                    .map(|element| CanvasDrag { element, remainder: Vec2::ZERO });
+            }
+            if response.dragged() {
+                if let Some(drag) = &mut state.canvas_drag {
+                    drag.remainder += response.drag_delta() / scale;
+                    let step = Vec2::new(drag.remainder.x.trunc(), drag.remainder.y.trunc());
+                    drag.remainder -= step;
+                    let origin_x = bounds.iter().find(|x| x.element == drag.element).map(|x| x.origin_x);
+                    if let (Some(element), Some(origin_x)) = (board.board_elements.get_mut(drag.element), origin_x) {
+                        if step != Vec2::ZERO && move_element(element, origin_x, board.size, step.x as i32, step.y as i32) {
+                            state.boards_has_changed = true;
+                        }
+                    }
+                }
+            }
+            if response.drag_stopped() {
+                state.canvas_drag = None;
+            }
+            // Highlight the dragged or hovered element
+            let active = match &state.canvas_drag {
+                Some(drag) => Some(drag.element),
+                None => response.hover_pos().and_then(hit_test),
+            };
+            drop(state);
+            match active.and_then(|active| bounds.iter().find(|x| x.element == active)) {
+                Some(element_bounds) => {
+                    painter.rect_stroke(to_screen(element_bounds), 0., Stroke::new(1., HIGHLIGHT_COLOUR));
+                    let element = &board.board_elements[element_bounds.element];
+                    ui.label(format!("{} ({}, {})", &element.name, element_bounds.x, element_bounds.y));
+                }
+                None => {
+                    ui.label("Drag an element to move it");
+                }
+            }
+        });
+}
+
+/// Draws a canvas as a grid of LEDs, `scale` screen points per LED
+pub(crate) fn paint_matrix(ui: &mut Ui, canvas: &MatrixCanvas, scale: f32, sense: Sense) -> (Response, Painter) {
+    let (response, painter) = ui.allocate_painter(
+        Vec2::new(canvas.width as f32 * scale, canvas.height as f32 * scale),
+        sense,
+    );
+    let origin = response.rect.min;
+    painter.rect_filled(response.rect, 0., Color32::BLACK);
+    for (idx, pixel) in canvas.displayed_pixels().into_iter().enumerate() {
+        let (x, y) = (idx as u32 % canvas.width, idx as u32 / canvas.width);
+        let fill = match pixel {
+            [0, 0, 0] => LED_OFF_COLOUR,
+            [r, g, b] => Color32::from_rgb(r, g, b),
+        };
+        let min = origin + Vec2::new(x as f32 * scale, y as f32 * scale);
+        painter.rect_filled(Rect::from_min_size(min, Vec2::splat(scale - LED_SPACING)), 0., fill);
+    }
+    (response, painter)
+}
+
+/// Starts fetching any font or image the board needs that has not been requested yet
+fn request_missing_assets(
+    ctx: &egui::Context,
+    board: &BoardDefinition,
+    vars: &BoardVariables,
+    assets: Arc<Mutex<CanvasAssetCache>>,
+    image_hashes: Arc<Mutex<HashMap<String, String>>>,
+) {
+    for element in &board.board_elements {
+        match &element.value {
+            BoardElementValue::Text(_) | BoardElementValue::Marquee(_, _) => {
+                request_font(ctx, get_font_name(element), assets.clone());
+            }
+            BoardElementValue::TextBox(_, settings) => {
+                request_font(ctx, get_font_name(element), assets.clone());
+                if let TextOverflow::Shrink(font_name) = &settings.overflow {
+                    request_font(ctx, truncate_font_name(font_name), assets.clone());
+                }
+            }
+            BoardElementValue::Animation(image_path, settings) => {
+                let key = (image_path.clone(), settings.sprite_frames);
+                if assets.lock().unwrap().animations.contains_key(&key) {
+                    continue;
+                }
+                let Some(image_hash) = find_image_hash(&image_hashes, image_path) else {
+                    continue;
+                };
+                assets.lock().unwrap().animations.insert(key.clone(), AssetLoad::Loading);
+                let (assets, ctx) = (assets.clone(), ctx.clone());
+                get_bytes(&format!("/api/get_image/{}", &image_hash), move |data| {
+                    let frames = data.and_then(|data| decode_animation(&data, key.1));
+                    if frames.is_none() {
+                        log::error!("Unable to load animation {}", &key.0);
+                    }
+                    let frames = frames.map(AssetLoad::Loaded).unwrap_or(AssetLoad::Failed);
+                    assets.lock().unwrap().animations.insert(key, frames);
+                    ctx.request_repaint();
+                });
+            }
+            BoardElementValue::Img(path, dynamic) => {
+                let image_path = if *dynamic { substitute_samples(path, vars) } else { path.clone() };
+                if assets.lock().unwrap().images.contains_key(&image_path) {
+                    continue;
+                }
+                // The image index may still be loading, so try again on a later frame
+                let Some(image_hash) = find_image_hash(&image_hashes, &image_path) else {
+                    continue;
+                };
+                assets.lock().unwrap().images.insert(image_path.clone(), AssetLoad::Loading);
+                let (assets, ctx) = (assets.clone(), ctx.clone());
+                get_bytes(&format!("/api/get_image/{}", &image_hash), move |data| {
+                    let image = data.and_then(|data| decode_image(&data));
+                    if image.is_none() {
+                        log::error!("Unable to load image {}", &image_path);
+                    }
+                    let image = image.map(AssetLoad::Loaded).unwrap_or(AssetLoad::Failed);
+                    assets.lock().unwrap().images.insert(image_path, image);
+                    ctx.request_repaint();
+                });
+            }
+            _ => {}
+        }
+    }
+}
+
+fn find_image_hash(image_hashes: &Arc<Mutex<HashMap<String, String>>>, image_path: &str) -> Option<String> {
+    image_hashes
+        .lock()
+        .unwrap()
+        .iter()
+        .find(|(_, path)| path.as_str() == image_path)
+        .map(|(hash, _)| hash.clone())
+}
+
+fn request_font(ctx: &egui::Context, font_name: String, assets: Arc<Mutex<CanvasAssetCache>>) {
+    if assets.lock().unwrap().fonts.contains_key(&font_name) {
+        return;
+    }
+    assets.lock().unwrap().fonts.insert(font_name.clone(), AssetLoad::Loading);
+    let ctx = ctx.clone();
+    get_bytes(&format!("/api/get_font/{}.bdf", &font_name), move |data| {
+        let font = data.and_then(|data| bdf2::read(data.as_slice()).ok());
+        if font.is_none() {
+            log::error!("Unable to load font {}", &font_name);
+        }
+        let font = font.map(AssetLoad::Loaded).unwrap_or(AssetLoad::Failed);
+        assets.lock().unwrap().fonts.insert(font_name, font);
+        ctx.request_repaint();
+    });
+}
+
+/// Sprite strips are timed by the element, GIFs by their own frame delays
+fn animation_delays(frames: &[(PixelImage, Duration)], settings: &AnimationSettings) -> Vec<Duration> {
+    let frame_time = Duration::from_millis(settings.frame_time as u64);
+    frames.iter().map(|(_, delay)| if settings.sprite_frames > 0 { frame_time } else { *delay }).collect()
+}
+
+/// Frames of a GIF (or a sprite strip cut into `sprite_frames`) flattened onto black, the way the server sends them to devices
+fn decode_animation(data: &[u8], sprite_frames: u8) -> Option<Vec<(PixelImage, Duration)>> {
+    if sprite_frames > 0 || image::guess_format(data).ok()? != ImageFormat::Gif {
+        let image = decode_image(data)?;
+        return Some(split_sprite_strip(&image, sprite_frames.max(1)).into_iter().map(|frame| (frame, Duration::ZERO)).collect());
+    }
+    let frames = GifDecoder::new(std::io::Cursor::new(data)).ok()?.into_frames().collect_frames().ok()?;
+    Some(
+        frames
+            .into_iter()
+            .map(|frame| {
+                let (numerator, denominator) = frame.delay().numer_denom_ms();
+                let pixels = frame.buffer().pixels().map(|pixel| {
+                    let [r, g, b, a] = pixel.0;
+                    [r, g, b].map(|channel| (channel as u16 * a as u16 / 0xFF) as u8)
+                });
+                let image = PixelImage { width: frame.buffer().width(), height: frame.buffer().height(), pixels: pixels.collect() };
+                (image, gif_frame_delay(numerator / denominator.max(1)))
+            })
+            .collect(),
+    )
+}
+
+pub(crate) fn decode_image(data: &[u8]) -> Option<PixelImage> {
+    let image = image::load_from_memory(data).ok()?.to_rgb8();
+    Some(PixelImage {
+        width: image.width(),
+        height: image.height(),
+        pixels: image.pixels().map(|pixel| pixel.0).collect(),
+    })
+}
+
+/// Lays the board out the same way the server renders it, using sample values for variables. Marquees
+/// scroll on repeat, `time` is how far along they are.
+fn draw_board(
+    board: &BoardDefinition,
+    vars: &BoardVariables,
+    assets: &CanvasAssetCache,
+    time: Duration,
+) -> (MatrixCanvas, Vec<ElementBounds>) {
+    let mut canvas = MatrixCanvas::new(board.size);
+    let mut bounds = Vec::new();
+    let temperature_colours = TemperatureColours::default();
+    for (idx, element) in board.board_elements.iter().enumerate() {
+        let x = element.x.unwrap_or_default() as i32;
+        let y = element.y as i32;
+        let element_bounds = match &element.value {
+            BoardElementValue::Text(text) | BoardElementValue::Marquee(text, _) => {
+                let text = substitute_samples(text, vars);
+                let colour = get_colour(&element.colour, &text, &temperature_colours);
+                let Some(font) = assets.font(&get_font_name(element)) else {
+                    let width = text.chars().count() as u32 * MISSING_GLYPH_SIZE.0;
+                    bounds.push(ElementBounds { element: idx, origin_x: x, x, y, width: width as i32, height: MISSING_GLYPH_SIZE.1 as i32, placeholder: true });
+                    continue;
+                };
+                let (layout, clip) = match &element.value {
+                    BoardElementValue::Marquee(_, settings) => {
+                        let clip = element_text_box(element, board.size, font);
+                        let overflow = measure_text(font, &text).saturating_sub(clip.width);
+                        let duration = marquee_duration(settings, overflow);
+                        let elapsed = match duration.as_nanos() {
+                            0 => Duration::ZERO,
+                            duration => Duration::from_nanos((time.as_nanos() % duration) as u64),
+                        };
+                        (layout_marquee(element, board.size, font, &text, settings, elapsed), Some(clip))
+                    }
+                    _ => (layout_element_text(element, board.size, font, &text), None),
+                };
+                for glyph in &layout.glyphs {
+                    canvas.draw_glyph_clipped(font, glyph.character, glyph.x, glyph.y, colour, clip.as_ref());
+                }
+                // Dragging an automatically centered element pins it where it currently is
+                let origin_x = match (element.x, element.horizontal_alignment, element.text_box) {
+                    (None, HorizontalAlignment::Auto, None) => layout.x,
+                    _ => x,
+                };
+                match clip {
+                    // Scrolling text is grabbed by its box so it does not move under the pointer
+                    Some(clip) if layout.width > clip.width => ElementBounds { element: idx, origin_x, x: clip.x, y: clip.y, width: clip.width as i32, height: clip.height as i32, placeholder: false },
+                    _ => ElementBounds { element: idx, origin_x, x: layout.x, y: layout.y, width: layout.width as i32, height: layout.height as i32, placeholder: false },
+                }
+            }
+            BoardElementValue::TextBox(text, settings) => {
+                let text = substitute_samples(text, vars);
+                let colour = get_colour(&element.colour, &text, &temperature_colours);
+                let layout_box = element_wrap_box(element, board.size);
+                let mut font = assets.font(&get_font_name(element));
+                if let (TextOverflow::Shrink(font_name), Some(large_font)) = (&settings.overflow, font) {
+                    if !text_fits(large_font, &text, &layout_box, settings.line_spacing) {
+                        font = assets.font(&truncate_font_name(font_name));
+                    }
+                }
+                let bounds = ElementBounds {
+                    element: idx,
+                    origin_x: x,
+                    x: layout_box.x,
+                    y: layout_box.y,
+                    width: layout_box.width as i32,
+                    height: layout_box.height as i32,
+                    placeholder: font.is_none(),
+                };
+                if let Some(font) = font {
+                    let pages = paginate_text(font, &text, &layout_box, settings);
+                    let duration = text_box_duration(settings, pages.len());
+                    let elapsed = match duration.as_nanos() {
+                        0 => Duration::ZERO,
+                        duration => Duration::from_nanos((time.as_nanos() % duration) as u64),
+                    };
+                    if let Some(page) = pages.get(page_at(settings, pages.len(), elapsed)) {
+                        let layout = layout_lines(font, page, &layout_box, element.get_horizontal_alignment(), element.vertical_alignment, settings.line_spacing);
+                        for glyph in &layout.glyphs {
+                            canvas.draw_glyph(font, glyph.character, glyph.x, glyph.y, colour);
+                        }
+                    }
+                }
+                bounds
+            }
+            BoardElementValue::Img(path, dynamic) => {
+                let image_path = if *dynamic { substitute_samples(path, vars) } else { path.clone() };
+                match assets.image(&image_path) {
+                    Some(image) => {
+                        for image_y in 0..image.height {
+                            for image_x in 0..image.width {
+                                let colour = image.pixels[(image_y * image.width + image_x) as usize];
+                                canvas.set_pixel(x + image_x as i32, y + image_y as i32, colour);
+                            }
+                        }
+                        ElementBounds { element: idx, origin_x: x, x, y, width: image.width as i32, height: image.height as i32, placeholder: false }
+                    }
+                    None => ElementBounds { element: idx, origin_x: x, x, y, width: PLACEHOLDER_SIZE, height: PLACEHOLDER_SIZE, placeholder: true },
+                }
+            }
+            BoardElementValue::Animation(path, settings) => {
+                match assets.animation(path, settings.sprite_frames) {
+                    Some(frames) if !frames.is_empty() => {
+                        let delays = animation_delays(frames, settings);
+                        let duration = animation_duration(&delays, settings);
+                        let elapsed = match duration.as_nanos() {
+                            0 => Duration::ZERO,
+                            duration => Duration::from_nanos((time.as_nanos() % duration) as u64),
+                        };
+                        let (image, _) = &frames[animation_frame_at(&delays, settings, elapsed)];
+                        for image_y in 0..image.height {
+                            for image_x in 0..image.width {
+                                let colour = image.pixels[(image_y * image.width + image_x) as usize];
+                                canvas.set_pixel(x + image_x as i32, y + image_y as i32, colour);
+                            }
+                        }
+                        ElementBounds { element: idx, origin_x: x, x, y, width: image.width as i32, height: image.height as i32, placeholder: false }
+                    }
+                    _ => ElementBounds { element: idx, origin_x: x, x, y, width: PLACEHOLDER_SIZE, height: PLACEHOLDER_SIZE, placeholder: true },
+                }
+            }
+            BoardElementValue::Pixel => {
+                let colour = match element.colour {
+                    ColourOption::Specific(colour) => colour,
+                    _ => ElementColour::default(),
+                };
+                canvas.set_pixel(x, y, quantise(&colour));
+                ElementBounds { element: idx, origin_x: x, x, y, width: 1, height: 1, placeholder: false }
+            }
+            BoardElementValue::Rect(..)
+            | BoardElementValue::RoundedRect(..)
+            | BoardElementValue::Circle(..)
+            | BoardElementValue::Ellipse(..)
+            | BoardElementValue::Triangle(..)
+            | BoardElementValue::Polyline(..) => {
+                let Some((shape, style)) = element_shape(element) else {
+                    continue;
+                };
+                let colour = match element.colour {
+                    ColourOption::Specific(colour) => colour,
+                    _ => ElementColour::default(),
+                };
+                canvas.draw_shape_styled(&shape, quantise(&colour), style.stroke_width as u32, style.fill.as_ref().map(quantise));
+                let (x, y, width, height) = shape.bounds();
+                ElementBounds { element: idx, origin_x: x, x, y, width: width as i32, height: height as i32, placeholder: false }
+            }
+            BoardElementValue::Bar(text, ..) | BoardElementValue::Gauge(text, ..) => {
+                let text = substitute_samples(text, vars);
+                for part in element_indicator(element, &text, &temperature_colours).unwrap_or_default() {
+                    let colour = quantise(&part.colour);
+                    let fill = (part.stroke_width == 0).then_some(colour);
+                    canvas.draw_shape_styled(&part.shape, colour, part.stroke_width, fill);
+                }
+                let (width, height) = match element.value {
+                    BoardElementValue::Bar(_, width, height, ..) => (width, height),
+                    BoardElementValue::Gauge(_, diameter, ..) => (diameter, diameter),
+                    _ => (0, 0),
+                };
+                ElementBounds { element: idx, origin_x: x, x, y, width: width as i32, height: height as i32, placeholder: false }
+            }
+            BoardElementValue::Graph(variable_name, width, height, settings) => {
+                let samples = sample_history(vars.get(variable_name), vars, settings.hours as i64 * 3600, *width as i64);
+                for segment in element_graph(element, &samples, 0, &temperature_colours).unwrap_or_default() {
+                    canvas.draw_line_between(segment.start, segment.end, quantise(&segment.colour));
+                }
+                ElementBounds { element: idx, origin_x: x, x, y, width: *width as i32, height: *height as i32, placeholder: false }
+            }
+            BoardElementValue::Line(x2, y2, text) => {
+                let text = substitute_samples(text, vars);
+                let colour = get_colour(&element.colour, &text, &temperature_colours);
+                let (x2, y2) = (*x2 as i32, *y2 as i32);
+                canvas.draw_line_between((x, y), (x2, y2), colour);
+                ElementBounds {
+                    element: idx,
+                    origin_x: x,
+                    x: x.min(x2),
+                    y: y.min(y2),
+                    width: (x - x2).abs() + 1,
+                    height: (y - y2).abs() + 1,
+                    placeholder: false,
+                }
+            }
+        };
+        bounds.push(element_bounds);
+    }
+    (canvas, bounds)
+}
+
+/// Moves an element by whole pixels, keeping it on the board. Returns true if anything moved
+fn move_element(element: &mut BoardElement, origin_x: i32, board_size: (u8, u8), dx: i32, dy: i32) -> bool {
+    let (x, y) = (origin_x, element.y as i32);
+    let (mut min_x, mut max_x, mut min_y, mut max_y) = (x, x, y, y);
+    for (x2, y2) in other_points(&mut element.value) {
+        min_x = min_x.min(*x2 as i32);
+        max_x = max_x.max(*x2 as i32);
+        min_y = min_y.min(*y2 as i32);
+        max_y = max_y.max(*y2 as i32);
+    }
+    let dx = dx.min(board_size.0 as i32 - 1 - max_x).max(-min_x);
+    let dy = dy.min(board_size.1 as i32 - 1 - max_y).max(-min_y);
+    // Centered elements get a fixed position as soon as they are dragged
+    if dx == 0 && dy == 0 && element.x.is_some() {
+        return false;
+    }
+    element.x = Some((x + dx) as u8);
+    element.y = (y + dy) as u8;
+    for (x2, y2) in other_points(&mut element.value) {
+        *x2 = (*x2 as i32 + dx) as u8;
+        *y2 = (*y2 as i32 + dy) as u8;
+    }
+    true
+}
+
+/// Points of lines and shapes positioned on the board rather than relative to (x, y), which move along with them
+fn other_points(value: &mut BoardElementValue) -> Vec<(&mut u8, &mut u8)> {
+    match value {
+        BoardElementValue::Line(x2, y2, _) => vec![(x2, y2)],
+        BoardElementValue::Triangle((x2, y2), (x3, y3), _) => vec![(x2, y2), (x3, y3)],
+        BoardElementValue::Polyline(points, _) => points.iter_mut().map(|(x, y)| (x, y)).collect(),
+        _ => Vec::new(),
+    }
+}
+
+fn get_font_name(element: &BoardElement) -> String {
+    truncate_font_name(element.font.as_deref().unwrap_or(DEFAULT_FONT))
+}
+
+/// The matrix protocol only has room for 9 characters of font name
+fn truncate_font_name(font_name: &str) -> String {
+    font_name.chars().take(9).collect()
+}
+
+fn get_colour(colour: &ColourOption, text: &str, temperature_colours: &TemperatureColours) -> [u8; 3] {
+    let colour = match colour {
+        ColourOption::Default => ElementColour::default(),
+        ColourOption::Specific(colour) => colour.to_owned(),
+        ColourOption::ParseTemperature => {
+            let re = regex::Regex::new(r"(?P<temp>\d+)").unwrap();
+            re.captures(text)
+                .and_then(|cap| cap["temp"].parse::<i32>().ok())
+                .map(|temp| temperature_colours.get_colour(temp))
+                .unwrap_or_default()
+        }
+    };
+    quantise(&colour)
+}
+
+/// Devices only get 4 bits per channel
+fn quantise(colour: &ElementColour) -> [u8; 3] {
+    [colour.r & 0xF0, colour.g & 0xF0, colour.b & 0xF0]
+}
+
+fn substitute_samples(text: &str, vars: &BoardVariables) -> String {
+    Template::parse(text, |name| vars.contains_key(name))
+        .render(|name| vars.get(name).map(|var| get_sample_value(var, vars)).unwrap_or_default())
+}
+
+/// A representative (and deliberately wide) value for a variable, so the layout can be checked without
+/// evaluating it
+fn get_sample_value(var: &BoardVariable, vars: &BoardVariables) -> String {
+    match var {
+        BoardVariable::URL(..) => String::from("{...}"),
+        BoardVariable::JsonURL(_, _, round_numbers, substring) => {
+            let sample = if *round_numbers { "72" } else { "72.45" };
+            apply_substring(sample, substring)
+        }
+        BoardVariable::Time(time_data) => time_data.sample(),
+        BoardVariable::Countdown(countdown) => countdown.sample(),
+        BoardVariable::Pushed(pushed) if pushed.default.is_empty() => String::from("{...}"),
+        BoardVariable::Pushed(pushed) => pushed.default.clone(),
+        // Worked out from the samples of the variables it uses
+        BoardVariable::Expression(text) => match Expression::parse(text) {
+            Ok(expression) if find_cycle(&expression, vars).is_none() => expression
+                .evaluate((js_sys::Date::now() / 1000.) as i64, &|name| vars.get(name).map(|var| get_sample_value(var, vars)))
+                .map(|value| value.to_string())
+                .unwrap_or_else(|_| String::from("ERR")),
+            _ => String::from("ERR"),
+        },
+    }
+}
+
+/// A made up history that wanders around a variable's sample value, one sample per graph column up to `now` = 0
+fn sample_history(var: Option<&BoardVariable>, vars: &BoardVariables, window: i64, columns: i64) -> Vec<(i64, f64)> {
+    let centre = var.and_then(|var| parse_number(&get_sample_value(var, vars))).unwrap_or(50.);
+    (0..columns.max(1))
+        .map(|column| {
+            let timestamp = -window + (column + 1) * window / columns.max(1);
+            let wave = (column as f64 / 4.).sin() * 4. + (column as f64 / 11.).cos() * 6.;
+            (timestamp, centre + wave)
+        })
+        .collect()
+}
+
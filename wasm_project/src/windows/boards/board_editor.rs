@@ -3,7 +3,7 @@ use std::sync::{Arc, Mutex};
 use egui::{Align2, Ui};
 use shared::boards::{BoardDefinition, BoardElement};
 
-use crate::{app::State, windows::boards::board_element_editor::render_element_editor};
+use crate::{app::State, windows::boards::{board_canvas::render_board_canvas, board_element_editor::render_element_editor}};
 
 
 
@@ -40,7 +40,7 @@ pub fn render_board_editor(
             render_board_elements(ui, board, state.clone());
             render_board_element_add_delete(ui, board, state.clone());
             //
-            render_config_panel(ctx, board);
+            render_board_canvas(ctx, board, state.clone());
         });
 }
 
@@ -158,18 +158,3 @@ fn render_board_element_add_delete(ui: &mut Ui, board: &mut BoardDefinition, sta
         }
     });
 }
-
-fn render_config_panel(ctx: &egui::Context, board: &BoardDefinition) {
-    let mut window_height = ctx.screen_rect().height();
-    window_height-=120.;
-    window_height/=2.;
-    egui::Window::new("Board Config")
-        .anchor(Align2::RIGHT_TOP, [-5.0, 5.0])
-        .min_height(window_height)
-        .max_height(window_height)
-        .scroll([false, true])
-        .movable(false)
-        .show(ctx, |ui| {
-            ui.label(serde_json::to_string(board).unwrap());
-        });
-}
\ No newline at end of file
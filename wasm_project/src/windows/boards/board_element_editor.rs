@@ -1,6 +1,6 @@
 use std::sync::{Arc, Mutex};
 use egui::{Color32, Ui};
-use shared::boards::{BoardElement, BoardElementValue, ColourOption, ElementColour};
+use shared::{boards::{BarDirection, BoardElement, BoardElementValue, ColourOption, ElementColour, GraphScale, GraphStyle, HorizontalAlignment, IndicatorSettings, ScrollDirection, TextOverflow, VerticalAlignment}, graphs::MAX_GRAPH_HOURS, templates::{Template, TEMPLATE_FILTER_HELP}};
 
 use crate::app::State;
 
@@ -21,15 +21,23 @@ pub fn render_element_editor(ui: &mut Ui, board_element: &mut BoardElement, refo
         ui.separator();
         if render_element_position_editor(ui, board_element) { modified = true; }
         ui.separator();
-        if value_type.eq("Text") {
+        if value_type.eq("Text") || value_type.eq("Marquee") || value_type.eq("Text Box") {
             if render_text_style_editor(ui, board_element, state.clone()) { modified = true; }
             ui.separator();
+            if render_text_alignment_editor(ui, board_element) { modified = true; }
+            ui.separator();
         }
         if match value_type.as_str() {
-            "Text" => render_text_value_editor(ui, "Text Editor", &mut board_element.value),
+            "Text" => render_text_value_editor(ui, "Text Editor", &mut board_element.value, state.clone()),
             "Image" => render_image_value_editor(ui, &board_element.name, &mut board_element.value, state.clone()),
             "Pixel" => render_pixel_value_editor(ui, board_element),
-            "Line" => render_line_value_editor(ui, board_element),
+            "Line" => render_line_value_editor(ui, board_element, state.clone()),
+            "Marquee" => render_marquee_value_editor(ui, board_element, state.clone()),
+            "Text Box" => render_text_box_value_editor(ui, board_element, state.clone()),
+            "Rectangle" | "Rounded Rectangle" | "Circle" | "Ellipse" | "Triangle" | "Polyline" => render_shape_value_editor(ui, board_element),
+            "Bar" | "Gauge" => render_indicator_value_editor(ui, board_element, state.clone()),
+            "Graph" => render_graph_value_editor(ui, board_element, state.clone()),
+            "Animation" => render_animation_value_editor(ui, board_element, state.clone()),
             _ => false
         } { modified = true; }
     });
@@ -201,19 +209,208 @@ fn render_text_font_editor(ui: &mut Ui, board_element: &mut BoardElement, state:
     modified
 }
 
-fn render_text_value_editor(ui: &mut Ui, title: &str, value: &mut BoardElementValue) -> bool {
+fn render_text_alignment_editor(ui: &mut Ui, board_element: &mut BoardElement) -> bool {
+    let mut modified = false;
+    let mut horizontal = board_element.horizontal_alignment.get_option();
+    let mut vertical = board_element.vertical_alignment.get_option();
+    ui.label("Alignment");
+    ui.horizontal(|ui| {
+        let mut salt = board_element.name.clone();
+        salt.push_str("0f4e1c47-52a3-4c5e-a2a1-2b7f6d0b8e91");
+        egui::ComboBox::from_id_salt(salt)
+            .selected_text(&horizontal)
+            .show_ui(ui, |ui| {
+                for opt in HorizontalAlignment::get_options() {
+                    ui.selectable_value(&mut horizontal, opt.clone(), opt);
+                }
+            });
+        let mut salt = board_element.name.clone();
+        salt.push_str("8c3d5a90-6b1e-4f27-9d44-c1e0a7f35b62");
+        egui::ComboBox::from_id_salt(salt)
+            .selected_text(&vertical)
+            .show_ui(ui, |ui| {
+                for opt in VerticalAlignment::get_options() {
+                    ui.selectable_value(&mut vertical, opt.clone(), opt);
+                }
+            });
+    });
+    if horizontal.ne(&board_element.horizontal_alignment.get_option()) {
+        board_element.horizontal_alignment = HorizontalAlignment::from_option(&horizontal);
+        modified = true;
+    }
+    if vertical.ne(&board_element.vertical_alignment.get_option()) {
+        board_element.vertical_alignment = VerticalAlignment::from_option(&vertical);
+        modified = true;
+    }
+    // Text Box
+    let mut use_box = board_element.text_box.is_some();
+    if ui.checkbox(&mut use_box, "Align within box").changed() {
+        board_element.text_box = if use_box { Some((0, 0)) } else { None };
+        modified = true;
+    }
+    if let Some((width, height)) = &mut board_element.text_box {
+        let mut width_edit = element_u8_to_string(*width);
+        let mut height_edit = element_u8_to_string(*height);
+        ui.horizontal(|ui| {
+            ui.label("Width:");
+            ui.add(egui::TextEdit::singleline(&mut width_edit).desired_width(64.));
+            ui.separator();
+            ui.label("Height:");
+            ui.add(egui::TextEdit::singleline(&mut height_edit).desired_width(64.));
+        });
+        if width_edit.ne(&element_u8_to_string(*width)) {
+            *width = get_num_from_string(&width_edit).and_then(|x| x.parse::<u8>().ok()).unwrap_or(0);
+            modified = true;
+        }
+        if height_edit.ne(&element_u8_to_string(*height)) {
+            *height = get_num_from_string(&height_edit).and_then(|x| x.parse::<u8>().ok()).unwrap_or(0);
+            modified = true;
+        }
+    }
+    modified
+}
+
+fn render_text_value_editor(ui: &mut Ui, title: &str, value: &mut BoardElementValue, state: Arc<Mutex<State>>) -> bool {
     let mut modified = false;
     ui.label(title);
-    if let BoardElementValue::Text(value) = value {
-        let old_val = value.clone();
-        ui.text_edit_singleline(value);
-        if old_val.ne(value) {
+    if let BoardElementValue::Text(value) | BoardElementValue::Marquee(value, _) | BoardElementValue::TextBox(value, _)
+    | BoardElementValue::Bar(value, ..) | BoardElementValue::Gauge(value, ..) = value {
+        if render_template_editor(ui, value, state) { modified = true; }
+    }
+    modified
+}
+
+/// A text field for text with `__variable|filter__` placeholders, listing any placeholders that can't be used
+fn render_template_editor(ui: &mut Ui, value: &mut String, state: Arc<Mutex<State>>) -> bool {
+    let old_val = value.clone();
+    ui.text_edit_singleline(value).on_hover_text(TEMPLATE_FILTER_HELP);
+    let vars = state.lock().unwrap().vars.clone();
+    let template = {
+        let vars = vars.lock().unwrap();
+        Template::parse(value, |name| vars.contains_key(name))
+    };
+    for error in template.errors() {
+        ui.colored_label(Color32::RED, error.to_string());
+    }
+    old_val.ne(value)
+}
+
+fn render_marquee_value_editor(ui: &mut Ui, board_element: &mut BoardElement, state: Arc<Mutex<State>>) -> bool {
+    let mut modified = false;
+    if render_text_value_editor(ui, "Marquee Text", &mut board_element.value, state) { modified = true; }
+    let mut salt = board_element.name.clone();
+    salt.push_str("5d0b7e2c-3f81-4a6e-b9c4-7e12a8d6f053");
+    if let BoardElementValue::Marquee(_, settings) = &mut board_element.value {
+        let mut speed_edit = element_u8_to_string(settings.speed);
+        let mut pause_edit = settings.pause.to_string();
+        let mut loops_edit = element_u8_to_string(settings.loops);
+        let mut direction = settings.direction.get_option();
+        ui.label("Scrolling");
+        ui.horizontal(|ui| {
+            egui::ComboBox::from_id_salt(salt)
+                .selected_text(&direction)
+                .show_ui(ui, |ui| {
+                    for opt in ScrollDirection::get_options() {
+                        ui.selectable_value(&mut direction, opt.clone(), opt);
+                    }
+                });
+            ui.separator();
+            ui.label("Speed (px/s):");
+            ui.add(egui::TextEdit::singleline(&mut speed_edit).desired_width(32.));
+        });
+        ui.horizontal(|ui| {
+            ui.label("Pause (ms):");
+            ui.add(egui::TextEdit::singleline(&mut pause_edit).desired_width(48.));
+            ui.separator();
+            ui.label("Loops:");
+            ui.add(egui::TextEdit::singleline(&mut loops_edit).desired_width(32.));
+        });
+        if direction.ne(&settings.direction.get_option()) {
+            settings.direction = ScrollDirection::from_option(&direction);
+            modified = true;
+        }
+        if speed_edit.ne(&element_u8_to_string(settings.speed)) {
+            settings.speed = get_num_from_string(&speed_edit).and_then(|x| x.parse::<u8>().ok()).unwrap_or(1).max(1);
+            modified = true;
+        }
+        if pause_edit.ne(&settings.pause.to_string()) {
+            settings.pause = get_num_from_string(&pause_edit).and_then(|x| x.parse::<u16>().ok()).unwrap_or(0);
+            modified = true;
+        }
+        if loops_edit.ne(&element_u8_to_string(settings.loops)) {
+            settings.loops = get_num_from_string(&loops_edit).and_then(|x| x.parse::<u8>().ok()).unwrap_or(1).max(1);
             modified = true;
         }
     }
     modified
 }
 
+fn render_text_box_value_editor(ui: &mut Ui, board_element: &mut BoardElement, state: Arc<Mutex<State>>) -> bool {
+    let mut modified = false;
+    if render_text_value_editor(ui, "Text Box Text", &mut board_element.value, state.clone()) { modified = true; }
+    let mut salt = board_element.name.clone();
+    salt.push_str("b1f7c2e4-8a3d-4f60-9e25-6d4c0a9b7e18");
+    if let BoardElementValue::TextBox(_, settings) = &mut board_element.value {
+        let mut spacing_edit = element_u8_to_string(settings.line_spacing);
+        let mut overflow = settings.overflow.get_option();
+        ui.horizontal(|ui| {
+            ui.label("Line Spacing:");
+            ui.add(egui::TextEdit::singleline(&mut spacing_edit).desired_width(32.));
+            ui.separator();
+            ui.label("Overflow:");
+            egui::ComboBox::from_id_salt(salt.clone())
+                .selected_text(&overflow)
+                .show_ui(ui, |ui| {
+                    for opt in TextOverflow::get_options() {
+                        ui.selectable_value(&mut overflow, opt.clone(), opt);
+                    }
+                });
+        });
+        if spacing_edit.ne(&element_u8_to_string(settings.line_spacing)) {
+            settings.line_spacing = get_num_from_string(&spacing_edit).and_then(|x| x.parse::<u8>().ok()).unwrap_or(0);
+            modified = true;
+        }
+        if overflow.ne(&settings.overflow.get_option()) {
+            settings.overflow = TextOverflow::from_option(&overflow);
+            modified = true;
+        }
+        match &mut settings.overflow {
+            TextOverflow::Ellipsis => {}
+            TextOverflow::Shrink(font) => {
+                let mut font_edit = font.clone();
+                salt.push_str("-font");
+                ui.horizontal(|ui| {
+                    ui.label("Smaller Font:");
+                    egui::ComboBox::from_id_salt(salt)
+                        .selected_text(&font_edit)
+                        .show_ui(ui, |ui| {
+                            let fonts = state.lock().unwrap().fonts.lock().unwrap().clone();
+                            for opt in fonts {
+                                ui.selectable_value(&mut font_edit, opt.clone(), opt);
+                            }
+                        });
+                });
+                if font_edit.ne(font) {
+                    *font = font_edit;
+                    modified = true;
+                }
+            }
+            TextOverflow::Page(page_time) => {
+                let mut page_time_edit = page_time.to_string();
+                ui.horizontal(|ui| {
+                    ui.label("Page Time (ms):");
+                    ui.add(egui::TextEdit::singleline(&mut page_time_edit).desired_width(48.));
+                });
+                if page_time_edit.ne(&page_time.to_string()) {
+                    *page_time = get_num_from_string(&page_time_edit).and_then(|x| x.parse::<u16>().ok()).unwrap_or(0);
+                    modified = true;
+                }
+            }
+        }
+    }
+    modified
+}
+
 fn render_image_value_editor(ui: &mut Ui, board_name: &str, value: &mut BoardElementValue, state: Arc<Mutex<State>>) -> bool {
     let mut modified = false;
     ui.label("Image Editor");
@@ -228,7 +425,7 @@ fn render_image_value_editor(ui: &mut Ui, board_name: &str, value: &mut BoardEle
         salt.push_str("2a59c793-8f64-44c8-a140-b40df0a0fcf4");
         ui.indent(salt, |ui| {
             if *dynamic {
-                if render_image_value_editor_dynamic(ui, value) { modified = true; }
+                if render_image_value_editor_dynamic(ui, value, state.clone()) { modified = true; }
             } else {
                 if render_image_value_editor_static(ui, board_name, value, state) { modified = true; }
             }
@@ -262,14 +459,10 @@ fn render_image_value_editor_static(ui: &mut Ui, board_name: &str, value: &mut S
     modified
 }
 
-fn render_image_value_editor_dynamic(ui: &mut Ui, value: &mut String) -> bool {
+fn render_image_value_editor_dynamic(ui: &mut Ui, value: &mut String, state: Arc<Mutex<State>>) -> bool {
     let mut modified = false;
-    let old_val = value.clone();
     ui.label("Dynamic Image Editor");
-    ui.text_edit_singleline(value);
-    if old_val.ne(value) {
-        modified = true;
-    }
+    if render_template_editor(ui, value, state) { modified = true; }
     modified
 }
 
@@ -279,7 +472,7 @@ fn render_pixel_value_editor(ui: &mut Ui, board_element: &mut BoardElement) -> b
     modified
 }
 
-fn render_line_value_editor(ui: &mut Ui, board_element: &mut BoardElement) -> bool {
+fn render_line_value_editor(ui: &mut Ui, board_element: &mut BoardElement, state: Arc<Mutex<State>>) -> bool {
     let mut modified = false;
     if let BoardElementValue::Line(pos_x_static, pos_y_static, temp_var) = board_element.value.clone() {
         let mut pos_x = pos_x_static;
@@ -292,7 +485,7 @@ fn render_line_value_editor(ui: &mut Ui, board_element: &mut BoardElement) -> bo
         }
         if board_element.colour.get_option() == "Parse Temperature" {
             let mut temp_element = BoardElementValue::Text(temp_var.clone());
-            if render_text_value_editor(ui, "Temperature Source", &mut temp_element) {
+            if render_text_value_editor(ui, "Temperature Source", &mut temp_element, state) {
                 board_element.value = BoardElementValue::Line(pos_x, pos_y, temp_element.extract_element_value().1);
                 modified = true;
             }
@@ -301,6 +494,282 @@ fn render_line_value_editor(ui: &mut Ui, board_element: &mut BoardElement) -> bo
     modified
 }
 
+fn render_shape_value_editor(ui: &mut Ui, board_element: &mut BoardElement) -> bool {
+    let mut modified = false;
+    if render_text_colour_editor(ui, board_element) { modified = true; }
+    let salt = board_element.name.clone();
+    let style = match &mut board_element.value {
+        BoardElementValue::Rect(width, height, style) | BoardElementValue::Ellipse(width, height, style) => {
+            ui.horizontal(|ui| {
+                if render_u8_field(ui, "Width:", width) { modified = true; }
+                if render_u8_field(ui, "Height:", height) { modified = true; }
+            });
+            style
+        }
+        BoardElementValue::RoundedRect(width, height, radius, style) => {
+            ui.horizontal(|ui| {
+                if render_u8_field(ui, "Width:", width) { modified = true; }
+                if render_u8_field(ui, "Height:", height) { modified = true; }
+                if render_u8_field(ui, "Corner Radius:", radius) { modified = true; }
+            });
+            style
+        }
+        BoardElementValue::Circle(diameter, style) => {
+            ui.horizontal(|ui| {
+                if render_u8_field(ui, "Diameter:", diameter) { modified = true; }
+            });
+            style
+        }
+        BoardElementValue::Triangle((x2, y2), (x3, y3), style) => {
+            for (label, x, y) in [("Corner 2", x2, y2), ("Corner 3", x3, y3)] {
+                ui.horizontal(|ui| {
+                    ui.label(label);
+                    if render_u8_field(ui, "X:", x) { modified = true; }
+                    if render_u8_field(ui, "Y:", y) { modified = true; }
+                });
+            }
+            style
+        }
+        BoardElementValue::Polyline(points, style) => {
+            let mut remove = None;
+            for (idx, (x, y)) in points.iter_mut().enumerate() {
+                ui.horizontal(|ui| {
+                    ui.label(format!("Point {}", idx + 2));
+                    if render_u8_field(ui, "X:", x) { modified = true; }
+                    if render_u8_field(ui, "Y:", y) { modified = true; }
+                    if ui.button("Remove").clicked() {
+                        remove = Some(idx);
+                    }
+                });
+            }
+            if let Some(idx) = remove {
+                points.remove(idx);
+                modified = true;
+            }
+            if ui.button("Add Point").clicked() {
+                points.push(points.last().copied().unwrap_or_default());
+                modified = true;
+            }
+            style
+        }
+        _ => return modified,
+    };
+    ui.horizontal(|ui| {
+        if render_u8_field(ui, "Outline Width:", &mut style.stroke_width) { modified = true; }
+        ui.separator();
+        let mut filled = style.fill.is_some();
+        if ui.checkbox(&mut filled, "Fill").changed() {
+            style.fill = if filled { Some(ElementColour::default()) } else { None };
+            modified = true;
+        }
+        if let Some(fill) = &mut style.fill {
+            let mut colour_edit = fill.to_egui_colour();
+            ui.push_id(salt, |ui| ui.color_edit_button_srgba(&mut colour_edit));
+            if colour_edit.ne(&fill.to_egui_colour()) {
+                fill.import_egui_colour(&colour_edit);
+                modified = true;
+            }
+        }
+    });
+    modified
+}
+
+fn render_indicator_value_editor(ui: &mut Ui, board_element: &mut BoardElement, state: Arc<Mutex<State>>) -> bool {
+    let mut modified = false;
+    if render_text_colour_editor(ui, board_element) { modified = true; }
+    if render_text_value_editor(ui, "Value", &mut board_element.value, state) { modified = true; }
+    let mut salt = board_element.name.clone();
+    salt.push_str("7c3e9a41-52d8-4b1f-a6e0-3f9d8c2b5a17");
+    let settings = match &mut board_element.value {
+        BoardElementValue::Bar(_, width, height, direction, settings) => {
+            let mut direction_edit = direction.get_option();
+            ui.horizontal(|ui| {
+                if render_u8_field(ui, "Width:", width) { modified = true; }
+                if render_u8_field(ui, "Height:", height) { modified = true; }
+                ui.separator();
+                egui::ComboBox::from_id_salt(salt.clone())
+                    .selected_text(&direction_edit)
+                    .show_ui(ui, |ui| {
+                        for opt in BarDirection::get_options() {
+                            ui.selectable_value(&mut direction_edit, opt.clone(), opt);
+                        }
+                    });
+            });
+            if direction_edit.ne(&direction.get_option()) {
+                *direction = BarDirection::from_option(&direction_edit);
+                modified = true;
+            }
+            settings
+        }
+        BoardElementValue::Gauge(_, diameter, thickness, settings) => {
+            ui.horizontal(|ui| {
+                if render_u8_field(ui, "Diameter:", diameter) { modified = true; }
+                if render_u8_field(ui, "Thickness:", thickness) { modified = true; }
+            });
+            settings
+        }
+        _ => return modified,
+    };
+    ui.horizontal(|ui| {
+        ui.label("Min:");
+        if ui.add(egui::DragValue::new(&mut settings.min).speed(0.1)).changed() { modified = true; }
+        ui.separator();
+        ui.label("Max:");
+        if ui.add(egui::DragValue::new(&mut settings.max).speed(0.1)).changed() { modified = true; }
+        ui.separator();
+        let mut has_background = settings.background.is_some();
+        if ui.checkbox(&mut has_background, "Background").changed() {
+            settings.background = if has_background { IndicatorSettings::default().background } else { None };
+            modified = true;
+        }
+        if let Some(background) = &mut settings.background {
+            let mut colour_edit = background.to_egui_colour();
+            ui.push_id(salt.clone(), |ui| ui.color_edit_button_srgba(&mut colour_edit));
+            if colour_edit.ne(&background.to_egui_colour()) {
+                background.import_egui_colour(&colour_edit);
+                modified = true;
+            }
+        }
+    });
+    ui.label("Colour Thresholds");
+    let mut remove = None;
+    for (idx, (threshold, colour)) in settings.thresholds.iter_mut().enumerate() {
+        ui.horizontal(|ui| {
+            ui.label("From:");
+            if ui.add(egui::DragValue::new(threshold).speed(0.1)).changed() { modified = true; }
+            let mut colour_edit = colour.to_egui_colour();
+            ui.push_id((salt.clone(), idx), |ui| ui.color_edit_button_srgba(&mut colour_edit));
+            if colour_edit.ne(&colour.to_egui_colour()) {
+                colour.import_egui_colour(&colour_edit);
+                modified = true;
+            }
+            if ui.button("Remove").clicked() {
+                remove = Some(idx);
+            }
+        });
+    }
+    if let Some(idx) = remove {
+        settings.thresholds.remove(idx);
+        modified = true;
+    }
+    if ui.button("Add Threshold").clicked() {
+        let threshold = settings.thresholds.last().map(|x| x.0).unwrap_or(settings.max);
+        settings.thresholds.push((threshold, ElementColour::default()));
+        modified = true;
+    }
+    modified
+}
+
+fn render_graph_value_editor(ui: &mut Ui, board_element: &mut BoardElement, state: Arc<Mutex<State>>) -> bool {
+    let mut modified = false;
+    if render_text_colour_editor(ui, board_element) { modified = true; }
+    let mut salt = board_element.name.clone();
+    salt.push_str("e4a2d9f6-1b7c-4c38-8f05-92d6b3a7c1e0");
+    if let BoardElementValue::Graph(variable_name, width, height, settings) = &mut board_element.value {
+        let mut variable_edit = variable_name.clone();
+        ui.horizontal(|ui| {
+            ui.label("Variable:");
+            egui::ComboBox::from_id_salt(salt.clone())
+                .selected_text(&variable_edit)
+                .show_ui(ui, |ui| {
+                    let mut vars: Vec<String> = state.lock().unwrap().vars.lock().unwrap().keys().cloned().collect();
+                    vars.sort();
+                    for opt in vars {
+                        ui.selectable_value(&mut variable_edit, opt.clone(), opt);
+                    }
+                });
+        });
+        if variable_edit.ne(variable_name) {
+            *variable_name = variable_edit;
+            modified = true;
+        }
+        let mut style = settings.style.get_option();
+        let mut hours_edit = settings.hours.to_string();
+        ui.horizontal(|ui| {
+            if render_u8_field(ui, "Width:", width) { modified = true; }
+            if render_u8_field(ui, "Height:", height) { modified = true; }
+            ui.separator();
+            egui::ComboBox::from_id_salt(format!("{}-style", salt))
+                .selected_text(&style)
+                .show_ui(ui, |ui| {
+                    for opt in GraphStyle::get_options() {
+                        ui.selectable_value(&mut style, opt.clone(), opt);
+                    }
+                });
+            ui.separator();
+            ui.label("History (hours):");
+            ui.add(egui::TextEdit::singleline(&mut hours_edit).desired_width(32.));
+        });
+        if style.ne(&settings.style.get_option()) {
+            settings.style = GraphStyle::from_option(&style);
+            modified = true;
+        }
+        if hours_edit.ne(&settings.hours.to_string()) {
+            settings.hours = get_num_from_string(&hours_edit).and_then(|x| x.parse::<u16>().ok()).unwrap_or(1).clamp(1, MAX_GRAPH_HOURS);
+            modified = true;
+        }
+        let mut scale = settings.scale.get_option();
+        ui.horizontal(|ui| {
+            ui.label("Scale:");
+            egui::ComboBox::from_id_salt(format!("{}-scale", salt))
+                .selected_text(&scale)
+                .show_ui(ui, |ui| {
+                    for opt in GraphScale::get_options() {
+                        ui.selectable_value(&mut scale, opt.clone(), opt);
+                    }
+                });
+            if let GraphScale::Fixed(min, max) = &mut settings.scale {
+                ui.separator();
+                ui.label("Min:");
+                if ui.add(egui::DragValue::new(min).speed(0.1)).changed() { modified = true; }
+                ui.label("Max:");
+                if ui.add(egui::DragValue::new(max).speed(0.1)).changed() { modified = true; }
+            }
+        });
+        if scale.ne(&settings.scale.get_option()) {
+            settings.scale = GraphScale::from_option(&scale);
+            modified = true;
+        }
+    }
+    modified
+}
+
+fn render_animation_value_editor(ui: &mut Ui, board_element: &mut BoardElement, state: Arc<Mutex<State>>) -> bool {
+    let mut modified = false;
+    ui.label("Animation Editor");
+    if let BoardElementValue::Animation(image_path, settings) = &mut board_element.value {
+        if render_image_value_editor_static(ui, &board_element.name, image_path, state) { modified = true; }
+        ui.horizontal(|ui| {
+            if render_u8_field(ui, "Sprite Frames:", &mut settings.sprite_frames) { modified = true; }
+            ui.label("(0 for a GIF)");
+        });
+        ui.horizontal(|ui| {
+            if settings.sprite_frames > 0 {
+                ui.label("Frame Time (ms):");
+                if ui.add(egui::DragValue::new(&mut settings.frame_time).range(20..=60000)).changed() { modified = true; }
+                ui.separator();
+            }
+            ui.label("Speed (%):");
+            if ui.add(egui::DragValue::new(&mut settings.speed).range(1..=1000)).changed() { modified = true; }
+            ui.separator();
+            ui.label("Loops:");
+            if ui.add(egui::DragValue::new(&mut settings.loops).range(1..=255)).changed() { modified = true; }
+        });
+    }
+    modified
+}
+
+fn render_u8_field(ui: &mut Ui, label: &str, value: &mut u8) -> bool {
+    let mut value_edit = element_u8_to_string(*value);
+    ui.label(label);
+    ui.add(egui::TextEdit::singleline(&mut value_edit).desired_width(32.));
+    if value_edit.ne(&element_u8_to_string(*value)) {
+        *value = get_num_from_string(&value_edit).and_then(|x| x.parse::<u8>().ok()).unwrap_or(0);
+        return true;
+    }
+    false
+}
+
 fn render_line_end_position_editor(ui: &mut Ui, pos_x: &mut u8, pos_y: &mut u8) {
     let mut x_edit = element_u8_to_string(*pos_x);
     let mut y_edit = element_u8_to_string(*pos_y);
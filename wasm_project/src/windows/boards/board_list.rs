@@ -4,7 +4,7 @@ use egui::{popup, Align2, Color32, RichText};
 
 use crate::{app::State, post::post};
 
-pub fn render_board_list(ctx: &egui::Context, state: Arc<Mutex<State>>, board_editor_open: &mut bool) {
+pub fn render_board_list(ctx: &egui::Context, state: Arc<Mutex<State>>, board_editor_open: &mut bool, image_manager_open: &mut bool) {
     let mut window_height = ctx.screen_rect().height();
     window_height-=120.;
     window_height/=2.;
@@ -60,9 +60,14 @@ pub fn render_board_list(ctx: &egui::Context, state: Arc<Mutex<State>>, board_ed
                     ui.add_space(spacer);
                 }
                 ui.separator();
-                if ui.button("Add Board").clicked() {
-                    state.lock().unwrap().add_board_dialog.open = true;
-                }
+                ui.horizontal(|ui| {
+                    if ui.button("Add Board").clicked() {
+                        state.lock().unwrap().add_board_dialog.open = true;
+                    }
+                    if ui.button("Images").clicked() {
+                        *image_manager_open = true;
+                    }
+                });
                 if boards_changed {
                     if ui.button(RichText::new("Post Changes").color(Color32::RED)).clicked() {
                         post("/api/update/boards", &boards);
@@ -2,4 +2,5 @@ pub mod board_editor;
 pub mod board_list;
 pub mod board_add;
 pub mod board_element_editor;
-pub mod board_delete;
\ No newline at end of file
+pub mod board_delete;
+pub mod board_canvas;
\ No newline at end of file
@@ -3,9 +3,9 @@ use std::
 ;
 use regex::Regex;
 use egui::{Align2, Ui};
-use shared::device_config::{Brightness, DeviceConfig, DeviceConfigs};
+use shared::{device_config::{Brightness, DeviceConfig, DeviceConfigs}, transitions::{BoardTransition, TransitionEffect, MAX_TRANSITION_TIME}};
 
-use crate::app::State;
+use crate::{app::State, windows::vars::var_editor::{render_locale_selector, render_time_zone_editor}};
 
 pub fn render_device_editor(
     ctx: &egui::Context,
@@ -34,9 +34,11 @@ pub fn render_device_editor(
                 render_proto_version(ui, &device_ip, &devices);
                 render_potd_brightness_editor(ui, &device_ip, &mut devices, state.clone());
             });
+            render_device_time_zone_editor(ui, &device_ip, &mut devices, state.clone());
             render_temperature_colours_editor(ui, &device_ip, &mut devices, state.clone());
             render_brightness_editor(ui, &device_ip, &mut devices, state.clone());
             render_board_list_editor(ui, &device_ip, &mut devices, state.clone());
+            render_transitions_editor(ui, &device_ip, &mut devices, state.clone());
             //
             render_config_panel(ctx, &devices.get(&device_ip).unwrap());
         });
@@ -60,6 +62,23 @@ fn render_name_editor(
     });
 }
 
+fn render_device_time_zone_editor(ui: &mut Ui, device_ip: &str, devices: &mut DeviceConfigs, state: Arc<Mutex<State>>) {
+    let custom_names = state.lock().unwrap().date_names.lock().unwrap().clone();
+    ui.group(|ui| {
+        ui.label("Time Zone");
+        let device = devices.get_mut(device_ip).unwrap();
+        if render_time_zone_editor(ui, "7e2a9c41-5d3b-4f86-b1e7-0a4c8d2f6b93", &mut device.time_zone, "Server's time zone") {
+            state.lock().unwrap().devices_has_changed = true;
+        }
+        ui.horizontal(|ui| {
+            ui.label("Language:");
+            if render_locale_selector(ui, "a61c0e94-7f25-4b3d-9d82-1e5b8c3f7a40", &mut device.locale, "English", &custom_names) {
+                state.lock().unwrap().devices_has_changed = true;
+            }
+        });
+    });
+}
+
 fn render_size(ui: &mut Ui, device_ip: &str, devices: &DeviceConfigs) {
     ui.group(|ui| {
         ui.vertical(|ui| {
@@ -215,6 +234,65 @@ fn render_board_list_editor(ui: &mut Ui, device_ip: &str, devices: &mut DeviceCo
     });
 }
 
+fn render_transitions_editor(ui: &mut Ui, device_ip: &str, devices: &mut DeviceConfigs, state: Arc<Mutex<State>>) {
+    ui.group(|ui| {
+        ui.collapsing("Transitions", |ui| {
+            let device = devices.get_mut(device_ip).unwrap();
+            ui.horizontal(|ui| {
+                ui.label("All Boards:");
+                if render_transition_item_editor(ui, "c4f1e7a2-5b39-4d08-9e6a-3f2b8d71c0a5", &mut device.transition) {
+                    state.lock().unwrap().devices_has_changed = true;
+                }
+            });
+            ui.separator();
+            let mut boards = device.boards.clone();
+            boards.sort();
+            boards.dedup();
+            for board in boards {
+                ui.horizontal(|ui| {
+                    let mut overridden = device.board_transitions.contains_key(&board);
+                    if ui.checkbox(&mut overridden, format!("To {}", &board)).changed() {
+                        if overridden {
+                            device.board_transitions.insert(board.clone(), device.transition);
+                        } else {
+                            device.board_transitions.remove(&board);
+                        }
+                        state.lock().unwrap().devices_has_changed = true;
+                    }
+                    if let Some(transition) = device.board_transitions.get_mut(&board) {
+                        if render_transition_item_editor(ui, &format!("{}-transition", &board), transition) {
+                            state.lock().unwrap().devices_has_changed = true;
+                        }
+                    }
+                });
+            }
+        });
+    });
+}
+
+fn render_transition_item_editor(ui: &mut Ui, salt: &str, transition: &mut BoardTransition) -> bool {
+    let mut modified = false;
+    let mut effect = transition.effect.get_option();
+    egui::ComboBox::from_id_salt(salt)
+        .selected_text(&effect)
+        .show_ui(ui, |ui| {
+            for opt in TransitionEffect::get_options() {
+                ui.selectable_value(&mut effect, opt.clone(), opt);
+            }
+        });
+    if effect.ne(&transition.effect.get_option()) {
+        transition.effect = TransitionEffect::from_option(&effect);
+        modified = true;
+    }
+    if transition.effect != TransitionEffect::None {
+        ui.label("Time (ms):");
+        if ui.add(egui::DragValue::new(&mut transition.duration).range(0..=MAX_TRANSITION_TIME).speed(10)).changed() {
+            modified = true;
+        }
+    }
+    modified
+}
+
 #[derive(Debug)]
 enum Consequences {
     None, Delete, MoveUp, MoveDown
@@ -5,7 +5,7 @@ use shared::device_config::DeviceConfig;
 
 use crate::{app::State, post::post};
 
-pub fn render_device_list(ctx: &egui::Context, state: Arc<Mutex<State>>, device_editor_open: &mut bool) {
+pub fn render_device_list(ctx: &egui::Context, state: Arc<Mutex<State>>, device_editor_open: &mut bool, virtual_device_open: &mut bool) {
     let mut window_height = ctx.screen_rect().height();
     window_height-=120.;
     window_height/=2.;
@@ -17,6 +17,10 @@ pub fn render_device_list(ctx: &egui::Context, state: Arc<Mutex<State>>, device_
         .movable(false)
         .show(ctx, |ui| {
             let devices = state.lock().unwrap().devices.lock().unwrap().clone();
+            if ui.button("Virtual Device").clicked() {
+                *virtual_device_open = true;
+            }
+            ui.separator();
             // Render Default First
             if let Some(device) = devices.get("default") {
                 render_device(ui, "default", device, state.clone(), device_editor_open);
@@ -1,3 +1,4 @@
 pub mod device_editor;
 pub mod device_list;
-pub mod device_delete;
\ No newline at end of file
+pub mod device_delete;
+pub mod virtual_device;
\ No newline at end of file
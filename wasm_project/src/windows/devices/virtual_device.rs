@@ -0,0 +1,273 @@
+use std::sync::{Arc, Mutex};
+
+use egui::{Color32, RichText, Sense, Ui};
+use shared::canvas::{LoadedAssets, MatrixCanvas, COMMAND_LENGTH};
+use wasm_bindgen::{closure::Closure, JsCast};
+use web_sys::{BinaryType, CloseEvent, MessageEvent, WebSocket};
+
+use crate::{
+    app::State,
+    windows::boards::board_canvas::{decode_image, paint_matrix},
+};
+
+// The browser has nowhere to cache assets, so always ask for them in-band
+const VIRTUAL_DEVICE_PROTO_VERSION: u64 = 2;
+
+/// Settings and connection of the browser's own matrix display
+pub struct VirtualDevice {
+    pub name: String,
+    pub size: (u8, u8),
+    session: Option<VirtualDeviceSession>,
+}
+impl Default for VirtualDevice {
+    fn default() -> Self {
+        VirtualDevice {
+            name: String::from("browser"),
+            size: (64, 32),
+            session: None,
+        }
+    }
+}
+
+#[derive(Clone, PartialEq)]
+enum ConnectionStatus {
+    Connecting,
+    Connected,
+    Closed(String),
+}
+
+/// Everything the socket callbacks update
+struct VirtualDisplay {
+    status: ConnectionStatus,
+    buffer: Vec<u8>,
+    canvas: MatrixCanvas,
+    assets: LoadedAssets,
+}
+
+struct VirtualDeviceSession {
+    socket: WebSocket,
+    display: Arc<Mutex<VirtualDisplay>>,
+    _on_open: Closure<dyn FnMut()>,
+    _on_message: Closure<dyn FnMut(MessageEvent)>,
+    _on_close: Closure<dyn FnMut(CloseEvent)>,
+}
+impl Drop for VirtualDeviceSession {
+    fn drop(&mut self) {
+        // Callbacks must not fire once their closures are gone
+        self.socket.set_onopen(None);
+        self.socket.set_onmessage(None);
+        self.socket.set_onclose(None);
+        let _ = self.socket.close();
+    }
+}
+
+pub fn render_virtual_device(ctx: &egui::Context, state: Arc<Mutex<State>>, virtual_device_open: &mut bool) {
+    if !*virtual_device_open {
+        // Closing the window disconnects
+        state.lock().unwrap().virtual_device.session = None;
+        return;
+    }
+    egui::Window::new("Virtual Device")
+        .open(virtual_device_open)
+        .default_width(ctx.screen_rect().width() * 0.5)
+        .show(ctx, |ui| {
+            let mut state = state.lock().unwrap();
+            let virtual_device = &mut state.virtual_device;
+            render_connection_settings(ctx, ui, virtual_device);
+            let Some(session) = &virtual_device.session else {
+                return;
+            };
+            let display = session.display.lock().unwrap();
+            match &display.status {
+                ConnectionStatus::Connecting => {
+                    ui.label("Connecting...");
+                }
+                ConnectionStatus::Connected => {}
+                ConnectionStatus::Closed(reason) => {
+                    ui.label(RichText::new(format!("Disconnected: {}", reason)).color(Color32::RED));
+                }
+            }
+            let scale = (ui.available_width() / display.canvas.width as f32).floor().clamp(1., 32.);
+            paint_matrix(ui, &display.canvas, scale, Sense::hover());
+        });
+}
+
+fn render_connection_settings(ctx: &egui::Context, ui: &mut Ui, virtual_device: &mut VirtualDevice) {
+    let connected = virtual_device.session.is_some();
+    ui.horizontal(|ui| {
+        ui.add_enabled_ui(!connected, |ui| {
+            ui.label("Name");
+            ui.add(egui::TextEdit::singleline(&mut virtual_device.name).desired_width(96.));
+            ui.label("Size");
+            let mut width = virtual_device.size.0.to_string();
+            ui.add(egui::TextEdit::singleline(&mut width).desired_width(24.));
+            let mut height = virtual_device.size.1.to_string();
+            ui.add(egui::TextEdit::singleline(&mut height).desired_width(24.));
+            if let (Ok(width), Ok(height)) = (width.parse::<u8>(), height.parse::<u8>()) {
+                if width > 0 && height > 0 {
+                    virtual_device.size = (width, height);
+                }
+            }
+        });
+        if connected {
+            if ui.button("Disconnect").clicked() {
+                virtual_device.session = None;
+            }
+        } else if ui.button("Connect").clicked() {
+            virtual_device.session = connect(ctx, &virtual_device.name, virtual_device.size);
+        }
+    });
+    ui.label(format!("Shows up in the device list as \"virtual:{}\"", &virtual_device.name));
+}
+
+fn connect(ctx: &egui::Context, name: &str, size: (u8, u8)) -> Option<VirtualDeviceSession> {
+    let location = web_sys::window().expect("No window").location();
+    let protocol = match location.protocol().ok()?.as_str() {
+        "https:" => "wss",
+        _ => "ws",
+    };
+    let url = format!("{}://{}/api/virtual_device/{}", protocol, location.host().ok()?, name);
+    let socket = match WebSocket::new(&url) {
+        Ok(socket) => socket,
+        Err(e) => {
+            log::error!("Unable to open virtual device socket: {:?}", e);
+            return None;
+        }
+    };
+    socket.set_binary_type(BinaryType::Arraybuffer);
+    let display = Arc::new(Mutex::new(VirtualDisplay {
+        status: ConnectionStatus::Connecting,
+        buffer: Vec::new(),
+        canvas: MatrixCanvas::new(size),
+        assets: LoadedAssets::default(),
+    }));
+    // Same handshake as a physical device: protocol version, then width and height
+    let on_open = {
+        let (socket, display, ctx) = (socket.clone(), display.clone(), ctx.clone());
+        Closure::<dyn FnMut()>::new(move || {
+            display.lock().unwrap().status = ConnectionStatus::Connected;
+            let _ = socket.send_with_str(&format!("{}\n{}\n{}\n", VIRTUAL_DEVICE_PROTO_VERSION, size.0, size.1));
+            ctx.request_repaint();
+        })
+    };
+    let on_message = {
+        let (socket, display, ctx) = (socket.clone(), display.clone(), ctx.clone());
+        Closure::<dyn FnMut(MessageEvent)>::new(move |event: MessageEvent| {
+            let Ok(data) = event.data().dyn_into::<js_sys::ArrayBuffer>() else {
+                return;
+            };
+            let replies = {
+                let mut display = display.lock().unwrap();
+                display.buffer.extend(js_sys::Uint8Array::new(&data).to_vec());
+                display.process_commands()
+            };
+            for reply in replies {
+                let _ = socket.send_with_str(&reply);
+            }
+            ctx.request_repaint();
+        })
+    };
+    let on_close = {
+        let (display, ctx) = (display.clone(), ctx.clone());
+        Closure::<dyn FnMut(CloseEvent)>::new(move |event: CloseEvent| {
+            let reason = match event.reason() {
+                reason if reason.is_empty() => String::from("connection closed"),
+                reason => reason,
+            };
+            display.lock().unwrap().status = ConnectionStatus::Closed(reason);
+            ctx.request_repaint();
+        })
+    };
+    socket.set_onopen(Some(on_open.as_ref().unchecked_ref()));
+    socket.set_onmessage(Some(on_message.as_ref().unchecked_ref()));
+    socket.set_onclose(Some(on_close.as_ref().unchecked_ref()));
+    Some(VirtualDeviceSession {
+        socket,
+        display,
+        _on_open: on_open,
+        _on_message: on_message,
+        _on_close: on_close,
+    })
+}
+
+impl VirtualDisplay {
+    /// Runs every complete command in the buffer and returns the ack/nack lines to send back
+    fn process_commands(&mut self) -> Vec<String> {
+        let mut replies = Vec::new();
+        let mut pos = 0;
+        while self.buffer.len() - pos >= COMMAND_LENGTH {
+            let command = &self.buffer[pos..pos + COMMAND_LENGTH];
+            if command[0] == b'a' {
+                // `a{kind}{length:08}`, then a 10 byte id and the payload itself
+                let Some(length) = std::str::from_utf8(&command[2..]).ok().and_then(|x| x.parse::<usize>().ok()) else {
+                    log::warn!("Malformed asset header: {}", String::from_utf8_lossy(command));
+                    pos += COMMAND_LENGTH;
+                    continue;
+                };
+                let frame_length = COMMAND_LENGTH * 2 + length;
+                if self.buffer.len() - pos < frame_length {
+                    break;
+                }
+                let kind = command[1] as char;
+                let asset_id = String::from_utf8_lossy(&self.buffer[pos + COMMAND_LENGTH..pos + COMMAND_LENGTH * 2])
+                    .trim_end_matches('=')
+                    .to_string();
+                let stored = store_asset(&mut self.assets, kind, &asset_id, &self.buffer[pos + COMMAND_LENGTH * 2..pos + frame_length]);
+                replies.push(format!("{}{}{}\n", if stored { 'k' } else { 'n' }, kind, asset_id));
+                pos += frame_length;
+                continue;
+            }
+            let command = String::from_utf8_lossy(command).to_string();
+            if let Some(missing) = self.missing_asset(&command) {
+                if !replies.contains(&missing) {
+                    replies.push(missing);
+                }
+            }
+            self.canvas.interpret(&command, &self.assets);
+            pos += COMMAND_LENGTH;
+        }
+        self.buffer.drain(..pos);
+        replies
+    }
+
+    /// Nack line for an asset a command needs that was never received
+    fn missing_asset(&self, command: &str) -> Option<String> {
+        match command.chars().next() {
+            Some('f') => {
+                let font_name = command.get(1..)?.trim_end_matches('=');
+                (!self.assets.fonts.contains_key(font_name)).then(|| format!("nf{}\n", font_name))
+            }
+            Some('i') => {
+                let image_id = command.get(5..)?.trim_end_matches('=');
+                (!self.assets.images.contains_key(image_id)).then(|| format!("ni{}\n", image_id))
+            }
+            _ => None,
+        }
+    }
+}
+
+fn store_asset(assets: &mut LoadedAssets, kind: char, asset_id: &str, payload: &[u8]) -> bool {
+    match kind {
+        'f' => match bdf2::read(payload) {
+            Ok(font) => {
+                assets.fonts.insert(asset_id.to_string(), font);
+                true
+            }
+            Err(_) => {
+                log::error!("Unable to parse font {}", asset_id);
+                false
+            }
+        },
+        'i' => match decode_image(payload) {
+            Some(image) => {
+                assets.images.insert(asset_id.to_string(), image);
+                true
+            }
+            None => {
+                log::error!("Unable to decode image {}", asset_id);
+                false
+            }
+        },
+        _ => false,
+    }
+}
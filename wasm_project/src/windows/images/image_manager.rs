@@ -0,0 +1,371 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+
+use egui::{Color32, ColorImage, RichText, TextureHandle, TextureOptions, Ui};
+use shared::image_upload::{Dithering, ImageFit, ImageFolder, ImageRename, ImageUploadOptions, MAX_UPLOAD_SIZE};
+use wasm_bindgen::{closure::Closure, JsCast};
+use web_sys::{File, FormData, HtmlInputElement};
+
+use crate::{
+    app::State,
+    get::{get, get_bytes},
+    post::{delete, post_form, post_then},
+    windows::boards::board_canvas::{decode_image, AssetLoad},
+};
+
+const ACCEPTED_TYPES: &str = ".bmp,.png,.jpg,.jpeg,.gif,.qoi";
+const THUMBNAIL_SIZE: egui::Vec2 = egui::vec2(64., 32.);
+
+/// Browsing, uploading and organising the server's images folder
+#[derive(Default)]
+pub struct ImageManager {
+    folders: Arc<Mutex<Vec<String>>>,
+    folders_requested: bool,
+    /// Folder being shown, empty for the top level
+    folder: String,
+    new_folder: String,
+    upload: ImageUploadOptions,
+    /// Path being renamed and its new path
+    renaming: Option<(String, String)>,
+    deleting: Option<String>,
+    /// Outcome of the last change, shown at the top of the window
+    status: Arc<Mutex<Option<Result<String, String>>>>,
+    thumbnails: Arc<Mutex<HashMap<String, AssetLoad<TextureHandle>>>>,
+}
+
+pub fn render_image_manager(ctx: &egui::Context, state: Arc<Mutex<State>>, image_manager_open: &mut bool) {
+    if !*image_manager_open {
+        return;
+    }
+    {
+        let mut state = state.lock().unwrap();
+        if !state.image_manager.folders_requested {
+            state.image_manager.folders_requested = true;
+            get("/api/image_folders", state.image_manager.folders.clone());
+        }
+    }
+    egui::Window::new("Images")
+        .open(image_manager_open)
+        .default_width(ctx.screen_rect().width() * 0.4)
+        .default_height(ctx.screen_rect().height() * 0.6)
+        .show(ctx, |ui| {
+            let status = state.lock().unwrap().image_manager.status.lock().unwrap().clone();
+            match status {
+                Some(Ok(message)) => { ui.label(RichText::new(message).color(Color32::GREEN)); }
+                Some(Err(message)) => { ui.label(RichText::new(message).color(Color32::RED)); }
+                None => {}
+            }
+            render_upload_options(ui, state.clone());
+            ui.separator();
+            render_folder_bar(ui, state.clone());
+            ui.separator();
+            egui::ScrollArea::vertical().auto_shrink([false, true]).show(ui, |ui| {
+                render_folder_contents(ui, state.clone());
+            });
+        });
+    render_image_delete(ctx, state);
+}
+
+fn render_upload_options(ui: &mut Ui, state: Arc<Mutex<State>>) {
+    let mut options = state.lock().unwrap().image_manager.upload.clone();
+    ui.horizontal(|ui| {
+        ui.label("Fit:");
+        let mut fit = options.fit.get_option();
+        egui::ComboBox::from_id_salt("image_upload_fit")
+            .selected_text(&fit)
+            .show_ui(ui, |ui| {
+                for opt in ImageFit::get_options() {
+                    ui.selectable_value(&mut fit, opt.clone(), opt);
+                }
+            });
+        options.fit = ImageFit::from_option(&fit);
+        if options.fit != ImageFit::Original {
+            ui.add(egui::DragValue::new(&mut options.width).range(1..=MAX_UPLOAD_SIZE));
+            ui.label("x");
+            ui.add(egui::DragValue::new(&mut options.height).range(1..=MAX_UPLOAD_SIZE));
+        }
+    });
+    ui.horizontal(|ui| {
+        let mut quantize = options.levels > 0;
+        ui.checkbox(&mut quantize, "Reduce Colours");
+        if !quantize {
+            options.levels = 0;
+        } else {
+            if options.levels == 0 {
+                options.levels = 16;
+            }
+            ui.label("Levels:");
+            ui.add(egui::DragValue::new(&mut options.levels).range(2..=255));
+            let mut dithering = options.dithering.get_option();
+            egui::ComboBox::from_id_salt("image_upload_dithering")
+                .selected_text(&dithering)
+                .show_ui(ui, |ui| {
+                    for opt in Dithering::get_options() {
+                        ui.selectable_value(&mut dithering, opt.clone(), opt);
+                    }
+                });
+            options.dithering = Dithering::from_option(&dithering);
+        }
+    });
+    ui.horizontal(|ui| {
+        ui.checkbox(&mut options.overwrite, "Replace Existing");
+        if ui.button("Upload Images").clicked() {
+            let state = state.clone();
+            let options = options.clone();
+            pick_files(move |files| {
+                for file in files {
+                    upload_image(state.clone(), &file, &options);
+                }
+            });
+        }
+    });
+    let mut state = state.lock().unwrap();
+    options.folder = state.image_manager.folder.clone();
+    state.image_manager.upload = options;
+}
+
+fn render_folder_bar(ui: &mut Ui, state: Arc<Mutex<State>>) {
+    let mut state = state.lock().unwrap();
+    let manager = &mut state.image_manager;
+    ui.horizontal(|ui| {
+        if ui.add_enabled(!manager.folder.is_empty(), egui::Button::new("⬆")).clicked() {
+            manager.folder = parent_folder(&manager.folder).to_string();
+        }
+        ui.label(format!("images/{}", &manager.folder));
+    });
+    ui.horizontal(|ui| {
+        ui.text_edit_singleline(&mut manager.new_folder);
+        if ui.add_enabled(!manager.new_folder.is_empty(), egui::Button::new("New Folder")).clicked() {
+            let path = join_path(&manager.folder, &manager.new_folder);
+            manager.new_folder.clear();
+            let status = manager.status.clone();
+            let folders = manager.folders.clone();
+            post_then("/api/image_folders", &ImageFolder { path: path.clone() }, move |result| {
+                if result.is_ok() {
+                    get("/api/image_folders", folders);
+                }
+                *status.lock().unwrap() = Some(result.map(|_| format!("Created {}", &path)));
+            });
+        }
+    });
+}
+
+fn render_folder_contents(ui: &mut Ui, state: Arc<Mutex<State>>) {
+    let (folder, folders, images, image_hashes) = {
+        let state = state.lock().unwrap();
+        let manager = &state.image_manager;
+        let folders = manager.folders.lock().unwrap().clone();
+        let images = state.images.lock().unwrap().clone();
+        let image_hashes = state.image_hashes.lock().unwrap().clone();
+        (manager.folder.clone(), folders, images, image_hashes)
+    };
+    let mut sub_folders: Vec<&String> = folders.iter().filter(|path| parent_folder(path) == folder).collect();
+    sub_folders.sort();
+    for path in sub_folders {
+        ui.horizontal(|ui| {
+            render_item_actions(ui, state.clone(), path);
+            if ui.button(format!("📁 {}", file_name(path))).clicked() {
+                state.lock().unwrap().image_manager.folder = path.to_string();
+            }
+        });
+    }
+    let mut folder_images: Vec<&String> = images.iter().filter(|path| parent_folder(path) == folder).collect();
+    folder_images.sort();
+    for path in folder_images {
+        ui.horizontal(|ui| {
+            render_item_actions(ui, state.clone(), path);
+            let hash = image_hashes.iter().find(|(_, image_path)| *image_path == path).map(|(hash, _)| hash.clone());
+            render_thumbnail(ui, state.clone(), path, hash);
+            ui.label(file_name(path));
+        });
+    }
+}
+
+/// Delete and rename buttons for an image or folder, the rename editor takes their place while it is open
+fn render_item_actions(ui: &mut Ui, state: Arc<Mutex<State>>, path: &str) {
+    let refresh_state = state.clone();
+    let mut state = state.lock().unwrap();
+    let manager = &mut state.image_manager;
+    if let Some((from, to)) = &mut manager.renaming {
+        if from == path {
+            ui.text_edit_singleline(to);
+            if ui.button("✔").clicked() {
+                let rename = ImageRename { from: from.clone(), to: to.clone() };
+                let message = format!("Renamed {} to {}", from, to);
+                manager.renaming = None;
+                let status = manager.status.clone();
+                post_then("/api/rename_image", &rename, move |result| {
+                    if result.is_ok() {
+                        refresh_images(refresh_state);
+                    }
+                    *status.lock().unwrap() = Some(result.map(|_| message));
+                });
+            }
+            if ui.button("✖").clicked() {
+                manager.renaming = None;
+            }
+            return;
+        }
+    }
+    if ui.button("🗑").clicked() {
+        manager.deleting = Some(path.to_string());
+    }
+    if ui.button("✏").clicked() {
+        manager.renaming = Some((path.to_string(), path.to_string()));
+    }
+}
+
+fn render_thumbnail(ui: &mut Ui, state: Arc<Mutex<State>>, path: &str, hash: Option<String>) {
+    let thumbnails = state.lock().unwrap().image_manager.thumbnails.clone();
+    let mut loaded = thumbnails.lock().unwrap();
+    match loaded.get(path) {
+        Some(AssetLoad::Loaded(texture)) => {
+            ui.add_sized(THUMBNAIL_SIZE, egui::Image::new(texture).fit_to_exact_size(THUMBNAIL_SIZE));
+            return;
+        }
+        Some(AssetLoad::Failed) => {
+            ui.add_sized(THUMBNAIL_SIZE, egui::Label::new(RichText::new("?").color(Color32::RED)));
+            return;
+        }
+        Some(AssetLoad::Loading) => {}
+        None => {
+            if let Some(hash) = hash {
+                loaded.insert(path.to_string(), AssetLoad::Loading);
+                let (thumbnails, ctx, path) = (thumbnails.clone(), ui.ctx().clone(), path.to_string());
+                get_bytes(&format!("/api/get_image/{}", &hash), move |data| {
+                    let thumbnail = match data.and_then(|data| decode_image(&data)) {
+                        Some(image) => {
+                            let pixels: Vec<u8> = image.pixels.iter().flatten().copied().collect();
+                            let image = ColorImage::from_rgb([image.width as usize, image.height as usize], &pixels);
+                            AssetLoad::Loaded(ctx.load_texture(&path, image, TextureOptions::NEAREST))
+                        }
+                        None => AssetLoad::Failed,
+                    };
+                    thumbnails.lock().unwrap().insert(path, thumbnail);
+                    ctx.request_repaint();
+                });
+            }
+        }
+    }
+    ui.add_sized(THUMBNAIL_SIZE, egui::Spinner::new());
+}
+
+fn render_image_delete(ctx: &egui::Context, state: Arc<Mutex<State>>) {
+    let deleting = state.lock().unwrap().image_manager.deleting.clone();
+    if let Some(path) = deleting {
+        let mut delete_dialog_open = true;
+        egui::Window::new("Delete Image")
+            .open(&mut delete_dialog_open)
+            .anchor(egui::Align2::CENTER_CENTER, [0., 0.])
+            .show(ctx, |ui| {
+                ui.label(&path);
+                ui.horizontal(|ui| {
+                    if ui.button("Delete").clicked() {
+                        let status = {
+                            let mut state = state.lock().unwrap();
+                            state.image_manager.deleting = None;
+                            state.image_manager.status.clone()
+                        };
+                        let refresh_state = state.clone();
+                        let endpoint = format!("/api/images/{}", encode_path(&path));
+                        let path = path.clone();
+                        delete(&endpoint, move |result| {
+                            if result.is_ok() {
+                                refresh_images(refresh_state);
+                            }
+                            *status.lock().unwrap() = Some(result.map(|_| format!("Deleted {}", &path)));
+                        });
+                    }
+                    if ui.button("Cancel").clicked() {
+                        state.lock().unwrap().image_manager.deleting = None;
+                    }
+                });
+            });
+        if !delete_dialog_open {
+            state.lock().unwrap().image_manager.deleting = None;
+        }
+    }
+}
+
+fn upload_image(state: Arc<Mutex<State>>, file: &File, options: &ImageUploadOptions) {
+    let form = FormData::new().unwrap();
+    for (name, value) in options.form_fields() {
+        let _ = form.append_with_str(name, &value);
+    }
+    let _ = form.append_with_blob_and_filename("file", file, &file.name());
+    let status = state.lock().unwrap().image_manager.status.clone();
+    let file_name = file.name();
+    post_form("/api/images", &form, move |result| {
+        if result.is_ok() {
+            refresh_images(state);
+        }
+        let result = result.map(|response| {
+            let path = serde_json::from_str::<serde_json::Value>(&response)
+                .ok()
+                .and_then(|response| response["path"].as_str().map(|x| x.to_string()))
+                .unwrap_or(file_name.clone());
+            format!("Uploaded {}", path)
+        });
+        *status.lock().unwrap() = Some(result.map_err(|e| format!("{}: {}", file_name, e)));
+    });
+}
+
+/// Reloads the image lists after a change, dropping anything drawn from the old files
+fn refresh_images(state: Arc<Mutex<State>>) {
+    let state = state.lock().unwrap();
+    get("/api/image_list", state.images.clone());
+    get("/api/image_folders", state.image_manager.folders.clone());
+    state.image_manager.thumbnails.lock().unwrap().clear();
+    let (image_hashes, canvas_assets) = (state.image_hashes.clone(), state.canvas_assets.clone());
+    // The canvas finds images by these hashes, so only forget its images once the new ones have arrived
+    get_bytes("/api/images", move |data| {
+        let Some(hashes) = data.and_then(|data| serde_json::from_slice::<HashMap<String, String>>(&data).ok()) else {
+            return;
+        };
+        *image_hashes.lock().unwrap() = hashes;
+        canvas_assets.lock().unwrap().forget_images();
+    });
+}
+
+/// Opens the browser's file picker and hands over the chosen files
+fn pick_files<F>(on_pick: F)
+where
+    F: FnOnce(Vec<File>) + 'static,
+{
+    let document = web_sys::window().expect("No window").document().expect("No document");
+    let input: HtmlInputElement = document.create_element("input").unwrap().dyn_into().unwrap();
+    input.set_type("file");
+    input.set_multiple(true);
+    input.set_accept(ACCEPTED_TYPES);
+    let picked = input.clone();
+    let on_change = Closure::once_into_js(move || {
+        let Some(files) = picked.files() else {
+            return;
+        };
+        on_pick((0..files.length()).filter_map(|idx| files.get(idx)).collect());
+    });
+    input.set_onchange(Some(on_change.unchecked_ref()));
+    input.click();
+}
+
+fn parent_folder(path: &str) -> &str {
+    path.rsplit_once('/').map(|(parent, _)| parent).unwrap_or_default()
+}
+
+fn file_name(path: &str) -> &str {
+    path.rsplit_once('/').map(|(_, name)| name).unwrap_or(path)
+}
+
+fn join_path(folder: &str, name: &str) -> String {
+    if folder.is_empty() {
+        name.to_string()
+    } else {
+        format!("{}/{}", folder, name)
+    }
+}
+
+fn encode_path(path: &str) -> String {
+    path.split('/').map(|segment| String::from(js_sys::encode_uri_component(segment))).collect::<Vec<String>>().join("/")
+}
@@ -0,0 +1 @@
+pub mod image_manager;
\ No newline at end of file
@@ -1,3 +1,4 @@
 pub mod boards;
 pub mod vars;
-pub mod devices;
\ No newline at end of file
+pub mod devices;
+pub mod images;
\ No newline at end of file
@@ -1,9 +1,41 @@
-use std::{str::FromStr, sync::{Arc, Mutex}};
+use std::{sync::{Arc, Mutex}, time::Duration};
 
-use egui::{Align2, Ui};
-use shared::board_variables::{BoardVariable, BoardVariables, TimeData};
+use chrono::Utc;
+use egui::{Align2, Color32, Ui};
+use shared::{
+    board_variables::{
+        parse_time_zone, time_zone_names, BoardVariable, BoardVariables, DeviceTimeSettings, HttpAuth, HttpMethod, RequestBody,
+        MAX_REQUEST_RETRIES, TIME_FORMAT_PRESETS,
+    },
+    countdowns::{AfterExpiry, CountdownTarget, CountdownUnits, COUNTDOWN_TARGET_HELP},
+    date_names::{built_in_locales, DateNameTables},
+    expressions::{find_cycle, find_variable_cycle, Expression, EXPRESSION_HELP},
+};
 
-use crate::app::State;
+use crate::{app::State, post::post_then};
+
+const JSON_PATH_HELP: &str = "0.title, $.list[-1].name or $..temp\n\
+    Filters: $.list[?(@.type == 'rain' && @.mm > 2)].mm\n\
+    Functions: length, min(), max(), sum(), avg(), join(', ')";
+const TIME_FORMAT_HELP: &str = "%H:%M 24 hour, %I:%M %p 12 hour, %S seconds\n\
+    %A Wednesday, %a Wed, %-d 30, %B September, %b Sep, %m 09, %Y 2026\n\
+    %Z time zone, %:z UTC offset, %% a literal %";
+/// Time zone names listed under a time zone field at once
+const MAX_TIME_ZONE_SUGGESTIONS: usize = 40;
+/// Labels of a repeating countdown's weekday checkboxes, Monday first
+const WEEKDAY_LABELS: [&str; 7] = ["Mon", "Tue", "Wed", "Thu", "Fri", "Sat", "Sun"];
+/// Seconds between asking the server for the value of the expression being edited, so it follows the variables it uses
+const EXPRESSION_REFRESH_INTERVAL: f64 = 5.;
+
+/// The server's value for the expression being edited
+#[derive(Default)]
+pub struct ExpressionPreview {
+    /// Expression the value was last asked for
+    expression: String,
+    requested_at: f64,
+    /// When the shown value was asked for, so slow responses don't replace newer ones
+    result: Arc<Mutex<Option<(f64, Result<String, String>)>>>,
+}
 
 pub fn render_var_editor(
     ctx: &egui::Context,
@@ -32,9 +64,12 @@ pub fn render_var_editor(
         render_var_type_selector(ui, &var_name, &mut vars, state.clone());
         let var_type = vars.get(&var_name).unwrap();
         match var_type {
-            BoardVariable::URL(_, _, _, _) => render_url_var_editor(ui, &var_name, &mut vars, state.clone()),
+            BoardVariable::URL(_) => render_url_var_editor(ui, &var_name, &mut vars, state.clone()),
             BoardVariable::JsonURL(_, _, _, _) => render_json_extractor_var_editor(ui, &var_name, &mut vars, state.clone()),
             BoardVariable::Time(_) => render_datetime_var_editor(ui, &var_name, &mut vars, state.clone()),
+            BoardVariable::Expression(_) => render_expression_var_editor(ui, &var_name, &mut vars, state.clone()),
+            BoardVariable::Countdown(_) => render_countdown_var_editor(ui, &var_name, &mut vars, state.clone()),
+            BoardVariable::Pushed(_) => render_pushed_var_editor(ui, &var_name, &mut vars, state.clone()),
         }
         render_config_panel(ctx, &vars);
     });
@@ -88,89 +123,236 @@ fn render_datetime_var_editor(ui: &mut Ui, var_name: &str, vars: &mut BoardVaria
     ui.group(|ui| {
         ui.label("DateTime Variable Editor");
         ui.separator();
-        if let BoardVariable::Time(data) = vars.get(var_name).unwrap() {
-            let mut selected = data.to_string();
-            egui::ComboBox::from_id_salt("e6c62f61-9be4-4b63-85b7-fc27ba1cf88a")
-                .selected_text(&selected)
-                .show_ui(ui, |ui| {
-                    for td in TimeData::get_all_time_data_types() {
-                        ui.selectable_value(&mut selected, td.clone(), td.clone());
-                    }
-                });
-            {
-                let orig_val = data.to_string();
-                if selected.ne(&orig_val) {
-                    log::info!("Selection Changed: {} -> {}", &orig_val, &selected);
-                    vars.insert(var_name.to_string(), BoardVariable::Time(TimeData::from_str(&selected).unwrap()));
-                    state.lock().unwrap().vars_has_changed = true;
-                    return;
-                }
+        if let BoardVariable::Time(real_data) = vars.get(var_name).unwrap().clone() {
+            let mut data = real_data.clone();
+            let preset = TIME_FORMAT_PRESETS.iter()
+                .find(|(_, format)| data.format.eq(format))
+                .map(|(name, _)| *name)
+                .unwrap_or("Custom");
+            ui.horizontal(|ui| {
+                ui.label("Format: ");
+                egui::ComboBox::from_id_salt("e6c62f61-9be4-4b63-85b7-fc27ba1cf88a")
+                    .selected_text(preset)
+                    .show_ui(ui, |ui| {
+                        for (name, format) in TIME_FORMAT_PRESETS {
+                            if ui.selectable_label(preset == name, name).clicked() {
+                                data.format = format.to_string();
+                            }
+                        }
+                    });
+                ui.text_edit_singleline(&mut data.format).on_hover_text(TIME_FORMAT_HELP);
+            });
+            ui.horizontal(|ui| {
+                ui.label("Time Zone: ");
+                render_time_zone_editor(ui, "0b5e3c8a-2f71-4d96-a4e0-6c9d1b7f3a58", &mut data.time_zone, "Device's time zone");
+            });
+            let custom_names = state.lock().unwrap().date_names.lock().unwrap().clone();
+            ui.horizontal(|ui| {
+                ui.label("Language: ");
+                render_locale_selector(ui, "3d8f1a6c-9b2e-4c57-8e0a-5f7b2d4c1e96", &mut data.locale, "Device's language", &custom_names);
+            });
+            ui.horizontal(|ui| {
+                ui.label("Day Offset: ");
+                ui.add(egui::DragValue::new(&mut data.day_offset).range(-366..=366));
+            });
+            data.substring = render_substring_editor(ui, &data.substring);
+            ui.separator();
+            ui.horizontal(|ui| {
+                ui.label(if data.time_zone.is_empty() { "Now (in your time zone):" } else { "Now:" });
+                match data.format(&Utc::now(), &DeviceTimeSettings::default(), &custom_names) {
+                    Ok(value) => ui.monospace(value),
+                    Err(message) => ui.colored_label(Color32::RED, message),
+                };
+            });
+            ui.ctx().request_repaint_after(Duration::from_secs(1));
+            if data.ne(&real_data) {
+                vars.insert(var_name.to_string(), BoardVariable::Time(data));
+                state.lock().unwrap().vars_has_changed = true;
+            }
+        }
+    });
+}
+
+/// Picks a built-in locale or one of the config's custom name tables, empty for `empty_label`. Returns whether it changed.
+pub(crate) fn render_locale_selector(ui: &mut Ui, salt: &str, locale: &mut String, empty_label: &str, custom_names: &DateNameTables) -> bool {
+    let old_locale = locale.clone();
+    let mut custom: Vec<&String> = custom_names.keys().collect();
+    custom.sort();
+    let selected = if locale.is_empty() {
+        empty_label.to_string()
+    } else {
+        built_in_locales().find(|(code, _)| code == locale).map(|(_, name)| name.to_string()).unwrap_or(locale.clone())
+    };
+    egui::ComboBox::from_id_salt(salt)
+        .selected_text(selected)
+        .show_ui(ui, |ui| {
+            ui.selectable_value(locale, String::new(), empty_label);
+            for (code, name) in built_in_locales() {
+                ui.selectable_value(locale, code.to_string(), name);
             }
-            if let TimeData::Weekday(offset, substring) = data.clone() {
-                let mut offset_edit = offset.to_string();
+            if !custom.is_empty() {
                 ui.separator();
-                ui.label("Offset:");
-                ui.text_edit_singleline(&mut offset_edit);
-                if offset_edit.ne(&offset.to_string()) {
-                    log::info!("Offset changed: {} -> {}", offset, &offset_edit);
-                    if let Ok(new_val) = offset_edit.parse::<u8>() {
-                        vars.insert(var_name.to_string(), BoardVariable::Time(TimeData::Weekday(new_val, substring.to_owned())));
-                        state.lock().unwrap().vars_has_changed = true;
-                        return;
-                    }
-                }
-                let new_substr = render_substring_editor(ui, &substring);
-                if new_substr.ne(&substring) {
-                    vars.insert(var_name.to_string(), BoardVariable::Time(TimeData::Weekday(offset, new_substr)));
-                    state.lock().unwrap().vars_has_changed = true;
-                    return;
+                for name in custom {
+                    ui.selectable_value(locale, name.clone(), name);
                 }
             }
+        });
+    *locale != old_locale
+}
+
+/// A field for an IANA time zone name, suggesting names that match what's been typed. Returns whether it changed.
+pub(crate) fn render_time_zone_editor(ui: &mut Ui, salt: &str, time_zone: &mut String, empty_hint: &str) -> bool {
+    let old_time_zone = time_zone.clone();
+    ui.vertical(|ui| {
+        ui.horizontal(|ui| {
+            ui.add(egui::TextEdit::singleline(time_zone).hint_text(empty_hint));
+            let search = time_zone.to_lowercase();
+            egui::ComboBox::from_id_salt(salt)
+                .selected_text("")
+                .width(24.)
+                .show_ui(ui, |ui| {
+                    let matches = time_zone_names().filter(|name| name.to_lowercase().contains(&search)).take(MAX_TIME_ZONE_SUGGESTIONS);
+                    for name in matches {
+                        if ui.selectable_label(time_zone == name, name).clicked() {
+                            *time_zone = name.to_string();
+                        }
+                    }
+                });
+        });
+        if !time_zone.is_empty() {
+            if let Err(message) = parse_time_zone(time_zone) {
+                ui.colored_label(Color32::RED, message);
+            }
         }
     });
+    time_zone.ne(&&old_time_zone)
 }
 
 fn render_url_var_editor(ui: &mut Ui, var_name: &str, vars: &mut BoardVariables, state: Arc<Mutex<State>>) {
     ui.group(|ui| {
         ui.label("URL Variable Editor");
-        if let BoardVariable::URL(id, url, timeout_secs, real_headers) = vars.get(var_name).unwrap().clone() {
+        if let BoardVariable::URL(real_request) = vars.get(var_name).unwrap().clone() {
+            let mut request = real_request.clone();
             ui.group(|ui| {
                 ui.label("URL ID");
-                ui.label(id.to_string());
+                ui.label(request.var_id.to_string());
             });
             ui.separator();
+            ui.horizontal(|ui| {
+                let mut method = request.method.get_option();
+                egui::ComboBox::from_id_salt("2f0d4c7e-6b0a-4c2e-9a8e-3f1b5d7c9e21")
+                    .selected_text(&method)
+                    .width(64.)
+                    .show_ui(ui, |ui| {
+                        for opt in HttpMethod::get_options() {
+                            ui.selectable_value(&mut method, opt.clone(), opt);
+                        }
+                    });
+                request.method = HttpMethod::from_option(&method);
+                ui.label("URL: ");
+                ui.text_edit_singleline(&mut request.url);
+            });
             {
-                let mut edit_url = url.clone();
+                let mut edit_expiry = request.expiry.to_string();
                 ui.horizontal(|ui| {
-                    ui.label("URL: ");
-                    ui.text_edit_singleline(&mut edit_url);
+                    ui.label("Refresh (s): ");
+                    ui.text_edit_singleline(&mut edit_expiry);
                 });
-                if edit_url.ne(&url) {
-                    vars.insert(var_name.to_string(), BoardVariable::URL(id.to_owned(), edit_url, timeout_secs.to_owned(), real_headers.to_owned()));
-                    state.lock().unwrap().vars_has_changed = true;
-                    return;
+                if edit_expiry.ne(&request.expiry.to_string()) {
+                    if let Ok(val) = edit_expiry.parse::<i64>() {
+                        request.expiry = val.max(15);
+                    }
                 }
             }
-            {
-                let mut edit_timeout = timeout_secs.clone().to_string();
-                ui.horizontal(|ui| {
-                    ui.label("Timeout: ");
-                    ui.text_edit_singleline(&mut edit_timeout);
-                });
-                if edit_timeout.ne(&timeout_secs.to_string()) {
-                    if let Ok(mut val) = edit_timeout.parse::<i64>() {
-                        if val < 15 {
-                            val = 15;
+            ui.horizontal(|ui| {
+                ui.label("Timeout (s): ");
+                ui.add(egui::DragValue::new(&mut request.timeout).range(1..=120));
+                ui.label("Retries: ");
+                ui.add(egui::DragValue::new(&mut request.retries).range(0..=MAX_REQUEST_RETRIES));
+            });
+            ui.checkbox(&mut request.cache_errors, "Show error responses");
+            ui.collapsing("Authentication", |ui| {
+                let mut auth = request.auth.get_option();
+                egui::ComboBox::from_id_salt("9c3e5a1f-7d2b-4e8a-b6c4-1a9f3e7d5b02")
+                    .selected_text(&auth)
+                    .show_ui(ui, |ui| {
+                        for opt in HttpAuth::get_options() {
+                            ui.selectable_value(&mut auth, opt.clone(), opt);
                         }
-                        vars.insert(var_name.to_string(), BoardVariable::URL(id.to_owned(), url.to_owned(), val, real_headers.to_owned()));
-                        state.lock().unwrap().vars_has_changed = true;
-                        return;
+                    });
+                if auth.ne(&request.auth.get_option()) {
+                    request.auth = HttpAuth::from_option(&auth);
+                }
+                match &mut request.auth {
+                    HttpAuth::None => {}
+                    HttpAuth::Basic { username, password } => {
+                        ui.horizontal(|ui| {
+                            ui.label("Username: ");
+                            ui.text_edit_singleline(username);
+                        });
+                        ui.horizontal(|ui| {
+                            ui.label("Password: ");
+                            ui.add(egui::TextEdit::singleline(password).password(true));
+                        });
+                    }
+                    HttpAuth::Bearer(token) => {
+                        ui.horizontal(|ui| {
+                            ui.label("Token: ");
+                            ui.add(egui::TextEdit::singleline(token).password(true));
+                        });
                     }
                 }
-            }
-            ui.collapsing("GET Headers", |ui| {
-                let headers = real_headers.clone();
-                let mut headers = headers.iter().collect::<Vec<(&String, &String)>>();
+            });
+            ui.collapsing("Query Parameters", |ui| {
+                ui.label("Values can use other variables, like __time__");
+                let mut removed = None;
+                for (idx, (param, value)) in request.query.iter_mut().enumerate() {
+                    ui.horizontal(|ui| {
+                        if ui.button("🗑").clicked() {
+                            removed = Some(idx);
+                        }
+                        egui::TextEdit::singleline(param)
+                            .desired_width(ui.available_width()*0.4).show(ui);
+                        egui::TextEdit::singleline(value)
+                            .desired_width(ui.available_width()).show(ui);
+                    });
+                }
+                if let Some(idx) = removed {
+                    request.query.remove(idx);
+                }
+                ui.separator();
+                if ui.button("Add Parameter").clicked() {
+                    request.query.push((String::from("param"), String::new()));
+                }
+            });
+            ui.collapsing("Body", |ui| {
+                let mut body = request.body.get_option();
+                egui::ComboBox::from_id_salt("5b7d9f1a-3c6e-4a2b-8d0f-7e1c9a3b5d64")
+                    .selected_text(&body)
+                    .show_ui(ui, |ui| {
+                        for opt in RequestBody::get_options() {
+                            ui.selectable_value(&mut body, opt.clone(), opt);
+                        }
+                    });
+                if body.ne(&request.body.get_option()) {
+                    request.body = RequestBody::from_option(&body);
+                }
+                match &mut request.body {
+                    RequestBody::None => {}
+                    RequestBody::Json(body) | RequestBody::Text(body) => {
+                        ui.label("Can use other variables, like __time__");
+                        ui.add(egui::TextEdit::multiline(body).code_editor().desired_rows(4));
+                    }
+                    RequestBody::GraphQL { query, variables } => {
+                        ui.label("Query (always POSTed):");
+                        ui.add(egui::TextEdit::multiline(query).code_editor().desired_rows(4));
+                        ui.label("Variables (JSON, can use other variables):");
+                        ui.add(egui::TextEdit::multiline(variables).code_editor().desired_rows(2));
+                    }
+                }
+            });
+            ui.collapsing("Headers", |ui| {
+                let mut headers = real_request.headers.iter().collect::<Vec<(&String, &String)>>();
                 headers.sort_by(|a,b|a.0.cmp(b.0));
                 for (header, value) in headers {
                     let mut header_edit = header.clone();
@@ -182,46 +364,34 @@ fn render_url_var_editor(ui: &mut Ui, var_name: &str, vars: &mut BoardVariables,
                             .desired_width(ui.available_width()).show(ui);
                     });
                     if header_edit.ne(header) {
-                        let mut headers = real_headers.clone();
-                        headers.remove(header);
-                        headers.insert(header_edit, value_edit);
-                        vars.insert(var_name.to_string(), BoardVariable::URL(id.to_owned(), url.to_owned(), timeout_secs.to_owned(), headers));
-                        state.lock().unwrap().vars_has_changed = true;
-                        return;
+                        request.headers.remove(header);
+                        request.headers.insert(header_edit, value_edit);
                     } else if value_edit.ne(value) {
-                        let mut headers = real_headers.clone();
-                        headers.insert(header.to_owned(), value_edit);
-                        vars.insert(var_name.to_string(), BoardVariable::URL(id.to_owned(), url.to_owned(), timeout_secs.to_owned(), headers));
-                        state.lock().unwrap().vars_has_changed = true;
-                        return;
+                        request.headers.insert(header.to_owned(), value_edit);
                     }
                 }
                 ui.separator();
                 ui.horizontal(|ui| {
                     if ui.button("Add Header").clicked() {
-                        let mut headers = real_headers.to_owned();
-                        headers.insert(String::from("X-New-Header"), String::new());
-                        vars.insert(var_name.to_string(), BoardVariable::URL(id.to_owned(), url.to_owned(), timeout_secs.to_owned(), headers));
-                        state.lock().unwrap().vars_has_changed = true;
-                        return;
+                        request.headers.insert(String::from("X-New-Header"), String::new());
                     }
                     let mut selection = String::from("Delete Header");
                     egui::ComboBox::from_id_salt("4fb55c28-9d00-448a-93ca-39214ee36970")
                         .selected_text(&selection)
                         .show_ui(ui, |ui|{
-                            for header in real_headers.keys() {
+                            for header in real_request.headers.keys() {
                                 ui.selectable_value(&mut selection, header.clone(), header.clone());
                             }
                         });
                     if selection.ne("Delete Header") {
-                        let mut headers = real_headers.to_owned();
-                        headers.remove(&selection);
-                        vars.insert(var_name.to_string(), BoardVariable::URL(id.to_owned(), url.to_owned(), timeout_secs.to_owned(), headers));
-                        state.lock().unwrap().vars_has_changed = true;
-                        return;
+                        request.headers.remove(&selection);
                     }
                 });
             });
+            if request.ne(&real_request) {
+                vars.insert(var_name.to_string(), BoardVariable::URL(request));
+                state.lock().unwrap().vars_has_changed = true;
+            }
         }
     });
 }
@@ -249,7 +419,7 @@ fn render_json_extractor_var_editor(ui: &mut Ui, var_name: &str, vars: &mut Boar
                 let mut edit_path = json_path.clone();
                 ui.horizontal(|ui| {
                     ui.label("JSON Path: ");
-                    ui.text_edit_singleline(&mut edit_path);
+                    ui.text_edit_singleline(&mut edit_path).on_hover_text(JSON_PATH_HELP);
                 });
                 if edit_path.ne(json_path) {
                     vars.insert(var_name.to_string(), BoardVariable::JsonURL(url_id.to_owned(), edit_path, round_numbers.to_owned(), *substring));
@@ -278,6 +448,230 @@ fn render_json_extractor_var_editor(ui: &mut Ui, var_name: &str, vars: &mut Boar
     });
 }
 
+fn render_expression_var_editor(ui: &mut Ui, var_name: &str, vars: &mut BoardVariables, state: Arc<Mutex<State>>) {
+    ui.group(|ui| {
+        ui.label("Expression Variable Editor");
+        ui.separator();
+        if let BoardVariable::Expression(real_expression) = vars.get(var_name).unwrap().clone() {
+            let mut expression = real_expression.clone();
+            ui.add(egui::TextEdit::multiline(&mut expression).code_editor().desired_rows(3))
+                .on_hover_text(EXPRESSION_HELP);
+            if expression.ne(&real_expression) {
+                vars.insert(var_name.to_string(), BoardVariable::Expression(expression.clone()));
+                state.lock().unwrap().vars_has_changed = true;
+            }
+            ui.separator();
+            match check_expression(&expression, vars) {
+                Ok(uses) => {
+                    if !uses.is_empty() {
+                        ui.label(format!("Uses: {}", uses.join(", ")));
+                    }
+                    render_expression_value(ui, &expression, state.clone());
+                }
+                Err(message) => {
+                    ui.colored_label(Color32::RED, message);
+                }
+            }
+        }
+    });
+}
+
+fn render_countdown_var_editor(ui: &mut Ui, var_name: &str, vars: &mut BoardVariables, state: Arc<Mutex<State>>) {
+    ui.group(|ui| {
+        ui.label("Countdown Variable Editor");
+        ui.separator();
+        if let BoardVariable::Countdown(real_data) = vars.get(var_name).unwrap().clone() {
+            let mut data = real_data.clone();
+            ui.horizontal(|ui| {
+                ui.label("Target: ");
+                let mut target = data.target.get_option();
+                egui::ComboBox::from_id_salt("c2e84b17-5a3f-4d09-9e61-7b0d3f8a2c54")
+                    .selected_text(&target)
+                    .show_ui(ui, |ui| {
+                        for opt in CountdownTarget::get_options() {
+                            ui.selectable_value(&mut target, opt.clone(), opt);
+                        }
+                    });
+                if target.ne(&data.target.get_option()) {
+                    data.target = CountdownTarget::from_option(&target);
+                }
+            });
+            match &mut data.target {
+                CountdownTarget::Fixed(time) => {
+                    ui.horizontal(|ui| {
+                        ui.label("Time: ");
+                        ui.text_edit_singleline(time).on_hover_text(COUNTDOWN_TARGET_HELP);
+                    });
+                }
+                CountdownTarget::Repeating { time, weekdays } => {
+                    ui.horizontal(|ui| {
+                        ui.label("At: ");
+                        ui.add(egui::TextEdit::singleline(time).hint_text("17:00"));
+                    });
+                    ui.horizontal(|ui| {
+                        for (label, checked) in WEEKDAY_LABELS.iter().zip(weekdays.iter_mut()) {
+                            ui.checkbox(checked, *label);
+                        }
+                    });
+                }
+                CountdownTarget::Variable(target_var) => {
+                    let mut names: Vec<&String> = vars.keys().filter(|name| name.as_str().ne(var_name)).collect();
+                    names.sort();
+                    ui.horizontal(|ui| {
+                        ui.label("Variable: ");
+                        egui::ComboBox::from_id_salt("8f1d6a3b-0c7e-4e52-a9d4-2b6f8e1c5a07")
+                            .selected_text(target_var.as_str())
+                            .show_ui(ui, |ui| {
+                                for name in names {
+                                    ui.selectable_value(target_var, name.clone(), name);
+                                }
+                            });
+                    }).response.on_hover_text(COUNTDOWN_TARGET_HELP);
+                }
+            }
+            ui.horizontal(|ui| {
+                ui.label("Show: ");
+                let mut units = data.units.get_option();
+                egui::ComboBox::from_id_salt("4a9e2d71-b8c3-4f06-8d5e-1c7a3f9b0e62")
+                    .selected_text(&units)
+                    .show_ui(ui, |ui| {
+                        for opt in CountdownUnits::get_options() {
+                            ui.selectable_value(&mut units, opt.clone(), opt);
+                        }
+                    });
+                data.units = CountdownUnits::from_option(&units);
+            });
+            if !matches!(data.target, CountdownTarget::Repeating { .. }) {
+                ui.horizontal(|ui| {
+                    ui.label("Once passed: ");
+                    let mut after_expiry = data.after_expiry.get_option();
+                    egui::ComboBox::from_id_salt("e5b03c8f-6d1a-4b97-a2e4-9f8c0d7b3a16")
+                        .selected_text(&after_expiry)
+                        .show_ui(ui, |ui| {
+                            for opt in AfterExpiry::get_options() {
+                                ui.selectable_value(&mut after_expiry, opt.clone(), opt);
+                            }
+                        });
+                    if after_expiry.ne(&data.after_expiry.get_option()) {
+                        data.after_expiry = AfterExpiry::from_option(&after_expiry);
+                    }
+                    if let AfterExpiry::Text(text) = &mut data.after_expiry {
+                        ui.add(egui::TextEdit::singleline(text).hint_text("Nothing"));
+                    }
+                });
+            }
+            ui.horizontal(|ui| {
+                ui.label("Time Zone: ");
+                render_time_zone_editor(ui, "1d7c4f0a-3e9b-4a58-b6d2-8c5e0f3a7b91", &mut data.time_zone, "Device's time zone");
+            });
+            if data.ne(&real_data) {
+                vars.insert(var_name.to_string(), BoardVariable::Countdown(data.clone()));
+                state.lock().unwrap().vars_has_changed = true;
+            }
+            ui.separator();
+            match &data.target {
+                CountdownTarget::Variable(target_var) => {
+                    if target_var.is_empty() {
+                        ui.weak("Pick the variable holding the target time");
+                    } else if let Some(cycle) = find_variable_cycle(target_var, vars) {
+                        ui.colored_label(Color32::RED, format!("Variables refer to each other in a loop: {}", cycle.join(" -> ")));
+                    } else {
+                        // Only the server has the target variable's value
+                        render_expression_value(ui, var_name, state.clone());
+                    }
+                }
+                _ => {
+                    ui.horizontal(|ui| {
+                        ui.label(if data.time_zone.is_empty() { "Now (in your time zone):" } else { "Now:" });
+                        match data.format(&Utc::now(), "", None) {
+                            Ok(value) => ui.monospace(value),
+                            Err(message) => ui.colored_label(Color32::RED, message),
+                        };
+                    });
+                    ui.ctx().request_repaint_after(Duration::from_secs(1));
+                }
+            }
+        }
+    });
+}
+
+fn render_pushed_var_editor(ui: &mut Ui, var_name: &str, vars: &mut BoardVariables, state: Arc<Mutex<State>>) {
+    ui.group(|ui| {
+        ui.label("Pushed Value Variable Editor");
+        ui.separator();
+        if let BoardVariable::Pushed(real_data) = vars.get(var_name).unwrap().clone() {
+            let mut data = real_data.clone();
+            ui.horizontal(|ui| {
+                ui.label("Default: ");
+                ui.add(egui::TextEdit::singleline(&mut data.default).hint_text("Shown until a value is pushed"));
+            });
+            ui.horizontal(|ui| {
+                ui.label("Expires after (s): ");
+                ui.add(egui::DragValue::new(&mut data.ttl).range(0..=u32::MAX));
+                if data.ttl == 0 {
+                    ui.weak("never");
+                }
+            });
+            if data.ne(&real_data) {
+                vars.insert(var_name.to_string(), BoardVariable::Pushed(data));
+                state.lock().unwrap().vars_has_changed = true;
+            }
+            ui.separator();
+            ui.label("Set the value with a PUT or POST of plain text or JSON to:");
+            ui.monospace(format!("/api/vars/{}/value", var_name));
+            ui.weak("Add ?path=$.some.field to pick the value out of a JSON body");
+            render_expression_value(ui, var_name, state.clone());
+        }
+    });
+}
+
+/// The variables an expression uses, or why it can't be evaluated
+fn check_expression(expression: &str, vars: &BoardVariables) -> Result<Vec<String>, String> {
+    let expression = Expression::parse(expression).map_err(|e| e.to_string())?;
+    let uses = expression.variables();
+    if let Some(missing) = uses.iter().find(|name| !vars.contains_key(*name)) {
+        return Err(format!("No variable named '{}'", missing));
+    }
+    if let Some(cycle) = find_cycle(&expression, vars) {
+        return Err(format!("Variables refer to each other in a loop: {}", cycle.join(" -> ")));
+    }
+    Ok(uses)
+}
+
+/// Shows what the server evaluates the expression to, asking again whenever it changes and every few seconds
+fn render_expression_value(ui: &mut Ui, expression: &str, state: Arc<Mutex<State>>) {
+    let now = ui.input(|i| i.time);
+    let mut state = state.lock().unwrap();
+    let vars_changed = state.vars_has_changed;
+    let preview = &mut state.expression_preview;
+    if preview.expression.ne(expression) || now - preview.requested_at >= EXPRESSION_REFRESH_INTERVAL {
+        preview.expression = expression.to_string();
+        preview.requested_at = now;
+        let result = preview.result.clone();
+        let ctx = ui.ctx().clone();
+        post_then("/api/vars/evaluate", &preview.expression, move |value| {
+            let mut result = result.lock().unwrap();
+            if result.as_ref().is_none_or(|(requested_at, _)| *requested_at <= now) {
+                *result = Some((now, value));
+            }
+            ctx.request_repaint();
+        });
+    }
+    ui.horizontal(|ui| {
+        ui.label("Value:");
+        match &*preview.result.lock().unwrap() {
+            Some((_, Ok(value))) if value.is_empty() => ui.weak("(waiting for the variables it uses)"),
+            Some((_, Ok(value))) => ui.monospace(value),
+            Some((_, Err(message))) => ui.colored_label(Color32::RED, message),
+            None => ui.spinner(),
+        };
+    });
+    if vars_changed {
+        ui.weak("Evaluated with the server's variables, post changes to include your edits");
+    }
+    ui.ctx().request_repaint_after(Duration::from_secs_f64(EXPRESSION_REFRESH_INTERVAL));
+}
+
 fn render_substring_editor(ui: &mut Ui, substring: &Option<(u8, i16)>) -> Option<(u8, i16)> {
     let substring_extract = if let Some(substring) = substring {
         substring